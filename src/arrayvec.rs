@@ -0,0 +1,269 @@
+use core::*;
+use core::mem::MaybeUninit;
+
+/// Fixed capacity vector stored inline, it never allocates.
+pub struct ArrayVec<T, const N: usize> {
+    elements    : [MaybeUninit<T>; N],
+    count       : usize,
+}
+
+impl<T, const N: usize> ArrayVec<T, N> {
+    pub fn new() -> Self {
+        Self {
+            elements    : unsafe { MaybeUninit::<[MaybeUninit<T>; N]>::uninit().assume_init() },
+            count       : 0,
+        }
+    }
+
+    #[inline]
+    fn ptr(&self) -> *const T { self.elements.as_ptr() as *const T }
+    #[inline]
+    fn mutPtr(&mut self) -> *mut T { self.elements.as_mut_ptr() as *mut T }
+
+    pub fn asArray(&self) -> &[T] { unsafe { slice::from_raw_parts(self.ptr(), self.count) } }
+    pub fn asMutArray(&mut self) -> &mut [T] { unsafe { slice::from_raw_parts_mut(self.mutPtr(), self.count) } }
+
+    pub fn len(&self) -> usize { self.count }
+    pub fn isEmpty(&self) -> bool { self.count == 0 }
+    pub fn capacity(&self) -> usize { N }
+    pub fn isFull(&self) -> bool { self.count == N }
+
+    /// Push `t` at the end, or hand it back when full.
+    pub fn pushBack(&mut self, t: T) -> Result<(), T> {
+        if self.count == N { return Err(t) }
+        unsafe { self.mutPtr().add(self.count).write(t) };
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.count == 0 { return None }
+        self.count -= 1;
+        Some(unsafe { self.ptr().add(self.count).read() })
+    }
+
+    #[inline]
+    pub fn get(&self, idx: usize) -> &T { &self.asArray()[idx] }
+    #[inline]
+    pub fn getMut(&mut self, idx: usize) -> &mut T { &mut self.asMutArray()[idx] }
+
+    pub fn toIter(&self) -> slice::Iter<'_, T> { self.asArray().iter() }
+
+    pub fn last(&self) -> Option<&T> { self.asArray().last() }
+
+    /// Insert `t` at `index`, shifting the following elements up, or hand it back when full.
+    pub fn insert(&mut self, index: usize, t: T) -> Result<(), T> {
+        assert!(index <= self.count, "insert index {} out of bounds (len {})", index, self.count);
+        if self.count == N { return Err(t) }
+        unsafe {
+            let p = self.mutPtr().add(index);
+            ptr::copy(p, p.add(1), self.count - index);
+            p.write(t);
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Remove the element at `index`, shifting the following elements down.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.count, "remove index {} out of bounds (len {})", index, self.count);
+        unsafe {
+            let p = self.mutPtr().add(index);
+            let v = p.read();
+            ptr::copy(p.add(1), p, self.count - index - 1);
+            self.count -= 1;
+            v
+        }
+    }
+
+    /// Remove the element at `index` and replace it with the last one (O(1), doesn't keep the order).
+    pub fn swapRemove(&mut self, index: usize) -> T {
+        assert!(index < self.count, "swapRemove index {} out of bounds (len {})", index, self.count);
+        unsafe {
+            let p = self.mutPtr().add(index);
+            let v = p.read();
+            self.count -= 1;
+            ptr::copy(self.ptr().add(self.count), p, 1);
+            v
+        }
+    }
+
+    /// Drop the elements past `len`.
+    pub fn truncate(&mut self, len: usize) {
+        while self.count > len {
+            self.count -= 1;
+            unsafe { ptr::drop_in_place(self.mutPtr().add(self.count)) };
+        }
+    }
+
+    pub fn clear(&mut self) { self.truncate(0) }
+}
+
+impl<T, const N: usize> Default for ArrayVec<T, N> {
+    fn default() -> Self { Self::new() }
+}
+
+/// Error of `ArrayVec::tryAppend`, there isn't enough room left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CapacityError;
+
+impl<T: Copy, const N: usize> ArrayVec<T, N> {
+    /// Append all of `arr`, or nothing if it doesn't fit.
+    pub fn tryAppend(&mut self, arr: &[T]) -> Result<(), CapacityError> {
+        if arr.len() > N - self.count { return Err(CapacityError) }
+        unsafe { ptr::copy_nonoverlapping(arr.as_ptr(), self.mutPtr().add(self.count), arr.len()) };
+        self.count += arr.len();
+        Ok(())
+    }
+}
+
+impl<T, const N: usize, I: slice::SliceIndex<[T]>> ops::Index<I> for ArrayVec<T, N> {
+    type Output = I::Output;
+    #[inline]
+    fn index(&self, idx: I) -> &I::Output { &self.asArray()[idx] }
+}
+
+impl<T, const N: usize, I: slice::SliceIndex<[T]>> ops::IndexMut<I> for ArrayVec<T, N> {
+    #[inline]
+    fn index_mut(&mut self, idx: I) -> &mut I::Output { &mut self.asMutArray()[idx] }
+}
+
+impl<T, const N: usize> ops::Deref for ArrayVec<T, N> {
+    type Target = [T];
+    fn deref(&self) -> &[T] { self.asArray() }
+}
+
+impl<T, const N: usize> ops::DerefMut for ArrayVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] { self.asMutArray() }
+}
+
+impl<T, const N: usize> Drop for ArrayVec<T, N> {
+    fn drop(&mut self) { self.clear() }
+}
+
+impl<T: Clone, const N: usize> Clone for ArrayVec<T, N> {
+    fn clone(&self) -> Self {
+        let mut c = Self::new();
+        for t in self.iter() {
+            // same capacity, can't fail
+            let _ = c.pushBack(t.clone());
+        }
+        c
+    }
+}
+
+/// Owning iterator for `ArrayVec`, the elements not consumed are dropped with it.
+pub struct IntoIter<T, const N: usize> {
+    vec     : ArrayVec<T, N>,
+    index   : usize,
+    end     : usize,
+}
+
+impl<T, const N: usize> Iterator for IntoIter<T, N> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        if self.index == self.end { return None }
+        let v = unsafe { self.vec.ptr().add(self.index).read() };
+        self.index += 1;
+        Some(v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let l = self.end - self.index;
+        (l, Some(l))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for IntoIter<T, N> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.index == self.end { return None }
+        self.end -= 1;
+        Some(unsafe { self.vec.ptr().add(self.end).read() })
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> {}
+
+impl<T, const N: usize> Drop for IntoIter<T, N> {
+    fn drop(&mut self) {
+        self.by_ref().for_each(drop);
+    }
+}
+
+impl<T, const N: usize> IntoIterator for ArrayVec<T, N> {
+    type Item = T;
+    type IntoIter = IntoIter<T, N>;
+    fn into_iter(mut self) -> IntoIter<T, N> {
+        // the iterator owns the elements from now on
+        let end = self.count;
+        self.count = 0;
+        IntoIter { vec: self, index: 0, end }
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a ArrayVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+    fn into_iter(self) -> slice::Iter<'a, T> { self.asArray().iter() }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a mut ArrayVec<T, N> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+    fn into_iter(self) -> slice::IterMut<'a, T> { self.asMutArray().iter_mut() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec::*;
+    use crate::testutil::*;
+
+    #[test]
+    fn testPushFull() {
+        let mut a = ArrayVec::<i32, 4>::new();
+        assert!(a.isEmpty());
+        for i in 0..4 {
+            assert!(a.pushBack(i).is_ok());
+        }
+        assert!(a.isFull() && a.pushBack(4) == Err(4));
+        assert!(a.insert(0, 5) == Err(5));
+        assert!(a[..] == [0, 1, 2, 3]);
+        assert!(a.pop() == Some(3) && a.len() == 3);
+        assert!(a.tryAppend(&[7, 8]).is_err() && a.tryAppend(&[7]).is_ok());
+        assert!(a.remove(0) == 0 && a.swapRemove(0) == 1);
+        assert!(a[..] == [7, 2]);
+        assert!(a.insert(1, 9).is_ok() && a[..] == [7, 9, 2]);
+
+        let mut z = ArrayVec::<i32, 0>::new();
+        assert!(z.pushBack(1) == Err(1) && z.pop().is_none() && z.isEmpty());
+    }
+
+    #[test]
+    fn testDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let mut a = ArrayVec::<Vec<i32>, 8>::new();
+            for i in 0..8 {
+                assert!(a.pushBack(item(i)).is_ok());
+            }
+            match a.pushBack(item(8)) {
+                Err(v) => assert!(v[0] == 8),
+                Ok(_) => panic!("pushed into a full ArrayVec"),
+            }
+            let c = a.clone();
+            a.truncate(5);
+            drop(a.remove(1));
+            assert!(a.len() == 4 && c.len() == 8);
+
+            let mut it = c.into_iter();
+            assert!(it.next().unwrap()[0] == 0);
+            assert!(it.next_back().unwrap()[0] == 7);
+            let mut n = 0;
+            for v in &mut a {
+                v[0] += 1;
+                n += v[0];
+            }
+            assert!(n == 1 + 3 + 4 + 5);
+        });
+    }
+}
@@ -0,0 +1,265 @@
+use core::*;
+use core::cmp::Ordering;
+use crate::mem::*;
+use crate::vec::*;
+
+pub use core::cmp::Reverse;
+
+/// Max-heap priority queue on top of `Vec`.
+pub struct BinaryHeap<T: Ord, A: Allocator = LibcAllocator> {
+    data    : Vec<T, A>,
+}
+
+/// Min-heap adapter: the smallest `T` is popped first, elements are wrapped in `Reverse`.
+pub type MinHeap<T, A = LibcAllocator> = BinaryHeap<Reverse<T>, A>;
+
+impl<T: Ord> BinaryHeap<T> {
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+    pub fn withCapacity(c: usize) -> Self { Self::withCapacityIn(c, LibcAllocator) }
+}
+
+impl<T: Ord, A: Allocator + Default> Default for BinaryHeap<T, A> {
+    fn default() -> Self { Self::newIn(A::default()) }
+}
+
+impl<T: Ord, A: Allocator> BinaryHeap<T, A> {
+    pub fn newIn(alloc: A) -> Self { Self { data: Vec::newIn(alloc) } }
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacityIn(c: usize, alloc: A) -> Self { Self { data: Vec::withCapacityIn(c, alloc) } }
+
+    /// Build a heap from the elements of `v` in O(n).
+    pub fn fromVec(v: Vec<T, A>) -> Self {
+        let mut h = Self { data: v };
+        let n = h.data.len();
+        for i in (0..n / 2).rev() {
+            h.siftDown(i, n);
+        }
+        h
+    }
+
+    pub fn allocator(&self) -> &A { self.data.allocator() }
+    pub fn len(&self) -> usize { self.data.len() }
+    pub fn isEmpty(&self) -> bool { self.data.is_empty() }
+    pub fn capacity(&self) -> usize { self.data.capacity() }
+
+    /// The elements in heap order (not sorted).
+    pub fn asArray(&self) -> &[T] { self.data.asArray() }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn reserve(&mut self, additional: usize) { self.data.reserve(additional) }
+    pub fn tryReserve(&mut self, additional: usize) -> Result<(), AllocError> { self.data.tryReserve(additional) }
+
+    fn siftUp(&mut self, mut i: usize) {
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if self.data[i] <= self.data[parent] { break }
+            self.data.swap(i, parent);
+            i = parent;
+        }
+    }
+
+    // sift the element at `i` down, only considering the first `end` elements
+    fn siftDown(&mut self, mut i: usize, end: usize) {
+        loop {
+            let mut child = 2 * i + 1;
+            if child >= end { break }
+            if child + 1 < end && self.data[child + 1].cmp(&self.data[child]) == Ordering::Greater { child += 1 }
+            if self.data[i] >= self.data[child] { break }
+            self.data.swap(i, child);
+            i = child;
+        }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn push(&mut self, t: T) {
+        self.data.pushBack(t);
+        self.siftUp(self.data.len() - 1);
+    }
+
+    pub fn tryPush(&mut self, t: T) -> Result<(), AllocError> {
+        self.data.tryPushBack(t)?;
+        self.siftUp(self.data.len() - 1);
+        Ok(())
+    }
+
+    /// Remove and return the greatest element.
+    pub fn pop(&mut self) -> Option<T> {
+        let n = self.data.len();
+        if n == 0 { return None }
+        self.data.swap(0, n - 1);
+        let t = self.data.pop();
+        self.siftDown(0, n - 1);
+        t
+    }
+
+    /// The greatest element.
+    pub fn peek(&self) -> Option<&T> {
+        if self.isEmpty() { None } else { Some(&self.data[0]) }
+    }
+
+    /// Mutable access to the greatest element, the heap is restored when the guard is dropped.
+    pub fn peekMut(&mut self) -> Option<PeekMut<'_, T, A>> {
+        if self.isEmpty() { None } else { Some(PeekMut { heap: self }) }
+    }
+
+    pub fn clear(&mut self) { self.data.clear() }
+
+    /// The underlying vector, in heap order.
+    pub fn intoVec(self) -> Vec<T, A> { self.data }
+
+    /// The elements in ascending order, in O(n log n) without allocating.
+    pub fn intoSortedVec(mut self) -> Vec<T, A> {
+        let mut end = self.data.len();
+        while end > 1 {
+            end -= 1;
+            self.data.swap(0, end);
+            self.siftDown(0, end);
+        }
+        self.data
+    }
+}
+
+/// Guard returned by `BinaryHeap::peekMut`.
+pub struct PeekMut<'a, T: Ord, A: Allocator = LibcAllocator> {
+    heap    : &'a mut BinaryHeap<T, A>,
+}
+
+impl<'a, T: Ord, A: Allocator> PeekMut<'a, T, A> {
+    /// Remove the peeked element from the heap.
+    pub fn pop(this: Self) -> T {
+        let t = this.heap.pop().unwrap();
+        mem::forget(this);
+        t
+    }
+}
+
+impl<'a, T: Ord, A: Allocator> ops::Deref for PeekMut<'a, T, A> {
+    type Target = T;
+    fn deref(&self) -> &T { &self.heap.data[0] }
+}
+
+impl<'a, T: Ord, A: Allocator> ops::DerefMut for PeekMut<'a, T, A> {
+    fn deref_mut(&mut self) -> &mut T { &mut self.heap.data[0] }
+}
+
+impl<'a, T: Ord, A: Allocator> Drop for PeekMut<'a, T, A> {
+    fn drop(&mut self) {
+        let n = self.heap.data.len();
+        self.heap.siftDown(0, n);
+    }
+}
+
+impl<T: Ord + Clone, A: Allocator + Clone> Clone for BinaryHeap<T, A> {
+    fn clone(&self) -> Self { Self { data: self.data.clone() } }
+}
+
+impl<T: Ord, A: Allocator> iter::Extend<T> for BinaryHeap<T, A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        let it = it.into_iter();
+        self.reserve(it.size_hint().0);
+        for t in it {
+            self.push(t);
+        }
+    }
+}
+
+impl<T: Ord, A: Allocator + Default> iter::FromIterator<T> for BinaryHeap<T, A> {
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Self {
+        Self::fromVec(it.into_iter().collect())
+    }
+}
+
+impl<T: Ord, A: Allocator> IntoIterator for BinaryHeap<T, A> {
+    type Item = T;
+    type IntoIter = IntoIter<T, A>;
+    /// Iterate in heap order (not sorted).
+    fn into_iter(self) -> IntoIter<T, A> { self.data.into_iter() }
+}
+
+impl<'a, T: Ord, A: Allocator> IntoIterator for &'a BinaryHeap<T, A> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+    fn into_iter(self) -> slice::Iter<'a, T> { self.data.asArray().iter() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    #[test]
+    fn testPushPop() {
+        let mut rng = Lcg(1);
+        for n in [0, 1, 2, 3, 10, 100, 1000] {
+            let v = randomVec(&mut rng, n, 50);
+            let mut reference = v.clone();
+            reference.sort_unstable();
+
+            let mut h = BinaryHeap::new();
+            for x in v.iter() {
+                h.push(*x);
+            }
+            assert!(h.len() == n);
+            for x in reference.iter().rev() {
+                assert!(h.peek() == Some(x));
+                assert!(h.pop() == Some(*x));
+            }
+            assert!(h.pop().is_none() && h.peek().is_none());
+        }
+    }
+
+    #[test]
+    fn testFromVecSorted() {
+        let mut rng = Lcg(7);
+        for n in [0, 1, 2, 5, 64, 999] {
+            let v = randomVec(&mut rng, n, 1 << 20);
+            let mut reference = v.clone();
+            reference.sort_unstable();
+
+            let h = BinaryHeap::fromVec(v.clone());
+            assert!(h.clone().intoSortedVec().asArray() == reference.asArray());
+
+            let mut h : MinHeap<u32> = v.iter().map(|x| Reverse(*x)).collect();
+            for x in reference.iter() {
+                assert!(h.pop() == Some(Reverse(*x)));
+            }
+        }
+    }
+
+    #[test]
+    fn testPeekMut() {
+        let mut h : BinaryHeap<i32> = [5, 3, 8, 1].iter().cloned().collect();
+        {
+            let mut top = h.peekMut().unwrap();
+            assert!(*top == 8);
+            *top = 0;
+        }
+        assert!(h.peek() == Some(&5));
+        assert!(PeekMut::pop(h.peekMut().unwrap()) == 5);
+        assert!(h.intoSortedVec().asArray() == [0, 1, 3]);
+    }
+
+    // ordered by the key only, owns an allocation
+    #[derive(Clone)]
+    struct Item(i32, Vec<i32>);
+
+    impl PartialEq for Item { fn eq(&self, o: &Self) -> bool { self.0 == o.0 } }
+    impl Eq for Item {}
+    impl PartialOrd for Item { fn partial_cmp(&self, o: &Self) -> Option<Ordering> { Some(self.cmp(o)) } }
+    impl Ord for Item { fn cmp(&self, o: &Self) -> Ordering { self.0.cmp(&o.0) } }
+
+    #[test]
+    fn testDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let mut h = BinaryHeap::new();
+            for i in 0..100 {
+                let mut v = Vec::new();
+                v.pushBack(i);
+                h.push(Item((i * 37) % 100, v));
+            }
+            assert!(h.pop().unwrap().0 == 99);
+            let s = h.clone().intoSortedVec();
+            assert!(s[0].0 == 0 && s[98].0 == 98 && s[98].1.len() == 1);
+        });
+    }
+}
@@ -0,0 +1,520 @@
+use core::*;
+use crate::mem::*;
+use crate::vec::*;
+use crate::hash::*;
+
+const WORD_BITS: usize = 64;
+
+#[inline]
+fn wordCount(bits: usize) -> usize { bits.div_ceil(WORD_BITS) }
+
+// the number of words up to the last non zero one
+fn significantWords(words: &[u64]) -> usize {
+    let mut n = words.len();
+    while n > 0 && words[n - 1] == 0 { n -= 1 }
+    n
+}
+
+/// Growable array of bits packed in `u64` words. The bits past `len` in the last word are kept clear.
+pub struct BitVec<A: Allocator = LibcAllocator> {
+    words   : Vec<u64, A>,
+    count   : usize,
+}
+
+impl BitVec {
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacity(bits: usize) -> Self { Self::withCapacityIn(bits, LibcAllocator) }
+}
+
+impl<A: Allocator + Default> Default for BitVec<A> {
+    fn default() -> Self { Self::newIn(A::default()) }
+}
+
+impl<A: Allocator> BitVec<A> {
+    pub fn newIn(alloc: A) -> Self { Self { words: Vec::newIn(alloc), count: 0 } }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacityIn(bits: usize, alloc: A) -> Self {
+        Self { words: Vec::withCapacityIn(wordCount(bits), alloc), count: 0 }
+    }
+
+    pub fn allocator(&self) -> &A { self.words.allocator() }
+
+    pub fn len(&self) -> usize { self.count }
+    pub fn isEmpty(&self) -> bool { self.count == 0 }
+    pub fn capacity(&self) -> usize { self.words.capacity() * WORD_BITS }
+
+    /// The backing words, bit `i` is bit `i % 64` of word `i / 64`.
+    pub fn asWords(&self) -> &[u64] { self.words.asArray() }
+
+    // clear the bits past `count` in the last word
+    fn maskLast(&mut self) {
+        let rem = self.count % WORD_BITS;
+        if rem != 0 {
+            let last = self.words.len() - 1;
+            self.words[last] &= (1u64 << rem) - 1;
+        }
+    }
+
+    #[inline]
+    fn checkIndex(&self, idx: usize) {
+        assert!(idx < self.count, "index {} out of bounds (len {})", idx, self.count);
+    }
+
+    #[inline]
+    pub fn get(&self, idx: usize) -> bool {
+        self.checkIndex(idx);
+        self.words[idx / WORD_BITS] & (1u64 << (idx % WORD_BITS)) != 0
+    }
+
+    #[inline]
+    pub fn set(&mut self, idx: usize, value: bool) {
+        self.checkIndex(idx);
+        let mask = 1u64 << (idx % WORD_BITS);
+        if value { self.words[idx / WORD_BITS] |= mask } else { self.words[idx / WORD_BITS] &= !mask }
+    }
+
+    /// Flip the bit at `idx`, returning its new value.
+    #[inline]
+    pub fn toggle(&mut self, idx: usize) -> bool {
+        self.checkIndex(idx);
+        let w = &mut self.words[idx / WORD_BITS];
+        *w ^= 1u64 << (idx % WORD_BITS);
+        *w & (1u64 << (idx % WORD_BITS)) != 0
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn pushBack(&mut self, value: bool) {
+        if self.count & (WORD_BITS - 1) == 0 { self.words.pushBack(0) }
+        self.count += 1;
+        self.set(self.count - 1, value);
+    }
+
+    pub fn pop(&mut self) -> Option<bool> {
+        if self.count == 0 { return None }
+        let v = self.get(self.count - 1);
+        self.truncate(self.count - 1);
+        Some(v)
+    }
+
+    /// Drop the bits past `len`.
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.count { return }
+        self.words.truncate(wordCount(len));
+        self.count = len;
+        self.maskLast();
+    }
+
+    /// Grow or shrink to `len` bits, the new bits are set to `value`.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn resize(&mut self, len: usize, value: bool) {
+        if len <= self.count { return self.truncate(len) }
+        let fill = if value { !0u64 } else { 0 };
+        let rem = self.count % WORD_BITS;
+        if rem != 0 && value {
+            let last = self.words.len() - 1;
+            self.words[last] |= !0u64 << rem;
+        }
+        self.words.reserve(wordCount(len) - self.words.len());
+        while self.words.len() < wordCount(len) {
+            self.words.pushBack(fill);
+        }
+        self.count = len;
+        self.maskLast();
+    }
+
+    pub fn clear(&mut self) {
+        self.words.clear();
+        self.count = 0;
+    }
+
+    /// Set every bit to `value`.
+    pub fn fill(&mut self, value: bool) {
+        let fill = if value { !0u64 } else { 0 };
+        for w in self.words.iter_mut() {
+            *w = fill;
+        }
+        self.maskLast();
+    }
+
+    pub fn countOnes(&self) -> usize { self.words.iter().map(|w| w.count_ones() as usize).sum() }
+    pub fn countZeros(&self) -> usize { self.count - self.countOnes() }
+
+    /// Index of the first set bit.
+    pub fn firstSet(&self) -> Option<usize> {
+        self.words.iter().position(|w| *w != 0).map(|i| i * WORD_BITS + self.words[i].trailing_zeros() as usize)
+    }
+
+    /// Index of the first clear bit.
+    pub fn firstClear(&self) -> Option<usize> {
+        let i = self.words.iter().position(|w| *w != !0u64)?;
+        let bit = i * WORD_BITS + (!self.words[i]).trailing_zeros() as usize;
+        if bit < self.count { Some(bit) } else { None }
+    }
+
+    /// Iterate over the indices of the set bits, in increasing order.
+    pub fn iterOnes(&self) -> Ones<'_> { Ones::new(self.words.asArray()) }
+
+    /// Iterate over all the bits.
+    pub fn iter(&self) -> Bits<'_, A> { Bits { vec: self, index: 0 } }
+}
+
+impl<A: Allocator> ops::Index<usize> for BitVec<A> {
+    type Output = bool;
+    fn index(&self, idx: usize) -> &bool { if self.get(idx) { &true } else { &false } }
+}
+
+impl<A: Allocator, B: Allocator> PartialEq<BitVec<B>> for BitVec<A> {
+    fn eq(&self, other: &BitVec<B>) -> bool { self.count == other.count && self.asWords() == other.asWords() }
+}
+
+impl<A: Allocator> Eq for BitVec<A> {}
+
+impl<A: Allocator> Hash for BitVec<A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.writeU64(self.count as u64);
+        for w in self.asWords() {
+            state.writeU64(*w);
+        }
+    }
+}
+
+impl<A: Allocator + Clone> Clone for BitVec<A> {
+    fn clone(&self) -> Self { Self { words: self.words.clone(), count: self.count } }
+}
+
+impl<A: Allocator> iter::Extend<bool> for BitVec<A> {
+    fn extend<I: IntoIterator<Item = bool>>(&mut self, it: I) {
+        for b in it {
+            self.pushBack(b);
+        }
+    }
+}
+
+impl<A: Allocator + Default> iter::FromIterator<bool> for BitVec<A> {
+    fn from_iter<I: IntoIterator<Item = bool>>(it: I) -> Self {
+        let mut v = Self::newIn(A::default());
+        v.extend(it);
+        v
+    }
+}
+
+/// Iterator over the bits of a `BitVec`.
+pub struct Bits<'a, A: Allocator = LibcAllocator> {
+    vec     : &'a BitVec<A>,
+    index   : usize,
+}
+
+impl<'a, A: Allocator> Iterator for Bits<'a, A> {
+    type Item = bool;
+    fn next(&mut self) -> Option<bool> {
+        if self.index == self.vec.len() { return None }
+        self.index += 1;
+        Some(self.vec.get(self.index - 1))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let l = self.vec.len() - self.index;
+        (l, Some(l))
+    }
+}
+
+/// Iterator over the indices of the set bits, a word at a time.
+pub struct Ones<'a> {
+    words   : &'a [u64],
+    index   : usize,
+    current : u64,
+}
+
+impl<'a> Ones<'a> {
+    fn new(words: &'a [u64]) -> Self {
+        Self { words, index: 0, current: if words.is_empty() { 0 } else { words[0] } }
+    }
+}
+
+impl<'a> Iterator for Ones<'a> {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        while self.current == 0 {
+            self.index += 1;
+            if self.index >= self.words.len() { return None }
+            self.current = self.words[self.index];
+        }
+        let bit = self.current.trailing_zeros() as usize;
+        // clear the lowest set bit
+        self.current &= self.current - 1;
+        Some(self.index * WORD_BITS + bit)
+    }
+}
+
+/// Set of small integers, one bit per possible element.
+pub struct BitSet<A: Allocator = LibcAllocator> {
+    words   : Vec<u64, A>,
+}
+
+impl BitSet {
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+}
+
+impl<A: Allocator + Default> Default for BitSet<A> {
+    fn default() -> Self { Self::newIn(A::default()) }
+}
+
+impl<A: Allocator> BitSet<A> {
+    pub fn newIn(alloc: A) -> Self { Self { words: Vec::newIn(alloc) } }
+
+    pub fn allocator(&self) -> &A { self.words.allocator() }
+
+    /// The backing words, element `i` is bit `i % 64` of word `i / 64`.
+    pub fn asWords(&self) -> &[u64] { self.words.asArray() }
+
+    /// Number of elements.
+    pub fn len(&self) -> usize { self.words.iter().map(|w| w.count_ones() as usize).sum() }
+    pub fn isEmpty(&self) -> bool { self.words.iter().all(|w| *w == 0) }
+
+    pub fn contains(&self, v: usize) -> bool {
+        let i = v / WORD_BITS;
+        i < self.words.len() && self.words[i] & (1u64 << (v % WORD_BITS)) != 0
+    }
+
+    /// Insert `v`, returns false if it was already there.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn insert(&mut self, v: usize) -> bool {
+        let i = v / WORD_BITS;
+        if i >= self.words.len() {
+            self.words.reserve(i + 1 - self.words.len());
+            while self.words.len() <= i {
+                self.words.pushBack(0);
+            }
+        }
+        let mask = 1u64 << (v % WORD_BITS);
+        let absent = self.words[i] & mask == 0;
+        self.words[i] |= mask;
+        absent
+    }
+
+    /// Remove `v`, returns false if it wasn't there.
+    pub fn remove(&mut self, v: usize) -> bool {
+        if !self.contains(v) { return false }
+        self.words[v / WORD_BITS] &= !(1u64 << (v % WORD_BITS));
+        true
+    }
+
+    pub fn clear(&mut self) { self.words.clear() }
+
+    /// Release the trailing empty words.
+    pub fn shrinkToFit(&mut self) {
+        self.words.truncate(significantWords(self.asWords()));
+        self.words.shrinkToFit();
+    }
+
+    /// Smallest element.
+    pub fn first(&self) -> Option<usize> { self.iter().next() }
+
+    /// Iterate over the elements in increasing order.
+    pub fn iter(&self) -> Ones<'_> { Ones::new(self.words.asArray()) }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn unionWith<B: Allocator>(&mut self, other: &BitSet<B>) {
+        let n = significantWords(other.asWords());
+        if n > self.words.len() {
+            self.words.reserve(n - self.words.len());
+            while self.words.len() < n {
+                self.words.pushBack(0);
+            }
+        }
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a |= *b;
+        }
+    }
+
+    pub fn intersectWith<B: Allocator>(&mut self, other: &BitSet<B>) {
+        self.words.truncate(usize::min(self.words.len(), other.words.len()));
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a &= *b;
+        }
+    }
+
+    pub fn differenceWith<B: Allocator>(&mut self, other: &BitSet<B>) {
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a &= !*b;
+        }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn symmetricDifferenceWith<B: Allocator>(&mut self, other: &BitSet<B>) {
+        let n = significantWords(other.asWords());
+        while self.words.len() < n {
+            self.words.pushBack(0);
+        }
+        for (a, b) in self.words.iter_mut().zip(other.words.iter()) {
+            *a ^= *b;
+        }
+    }
+
+    pub fn isSubset<B: Allocator>(&self, other: &BitSet<B>) -> bool {
+        self.words.iter().enumerate().all(|(i, w)| {
+            let o = if i < other.words.len() { other.words[i] } else { 0 };
+            *w & !o == 0
+        })
+    }
+
+    pub fn isSuperset<B: Allocator>(&self, other: &BitSet<B>) -> bool { other.isSubset(self) }
+
+    pub fn isDisjoint<B: Allocator>(&self, other: &BitSet<B>) -> bool {
+        self.words.iter().zip(other.words.iter()).all(|(a, b)| *a & *b == 0)
+    }
+}
+
+impl<A: Allocator + Clone> BitSet<A> {
+    pub fn union<B: Allocator>(&self, other: &BitSet<B>) -> Self {
+        let mut s = self.clone();
+        s.unionWith(other);
+        s
+    }
+
+    pub fn intersection<B: Allocator>(&self, other: &BitSet<B>) -> Self {
+        let mut s = self.clone();
+        s.intersectWith(other);
+        s
+    }
+
+    pub fn difference<B: Allocator>(&self, other: &BitSet<B>) -> Self {
+        let mut s = self.clone();
+        s.differenceWith(other);
+        s
+    }
+
+    pub fn symmetricDifference<B: Allocator>(&self, other: &BitSet<B>) -> Self {
+        let mut s = self.clone();
+        s.symmetricDifferenceWith(other);
+        s
+    }
+}
+
+// trailing empty words don't change the set
+impl<A: Allocator, B: Allocator> PartialEq<BitSet<B>> for BitSet<A> {
+    fn eq(&self, other: &BitSet<B>) -> bool {
+        let a = self.asWords();
+        let b = other.asWords();
+        a[..significantWords(a)] == b[..significantWords(b)]
+    }
+}
+
+impl<A: Allocator> Eq for BitSet<A> {}
+
+impl<A: Allocator> Hash for BitSet<A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let w = self.asWords();
+        w[..significantWords(w)].hash(state)
+    }
+}
+
+impl<A: Allocator + Clone> Clone for BitSet<A> {
+    fn clone(&self) -> Self { Self { words: self.words.clone() } }
+}
+
+impl<A: Allocator> iter::Extend<usize> for BitSet<A> {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, it: I) {
+        for v in it {
+            self.insert(v);
+        }
+    }
+}
+
+impl<A: Allocator + Default> iter::FromIterator<usize> for BitSet<A> {
+    fn from_iter<I: IntoIterator<Item = usize>>(it: I) -> Self {
+        let mut s = Self::newIn(A::default());
+        s.extend(it);
+        s
+    }
+}
+
+impl<'a, A: Allocator> IntoIterator for &'a BitSet<A> {
+    type Item = usize;
+    type IntoIter = Ones<'a>;
+    fn into_iter(self) -> Ones<'a> { self.iter() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashOf<T: Hash>(v: &T) -> u64 { BuildMurmurHasher::default().hashOne(v) }
+
+    #[test]
+    fn testBitVec() {
+        let mut v = BitVec::new();
+        for i in 0..200 {
+            v.pushBack(i % 3 == 0);
+        }
+        assert!(v.len() == 200 && v.countOnes() == 67 && v.countZeros() == 133);
+        assert!(v[0] && !v[1] && v.get(198) && !v.get(199));
+        assert!(v.firstSet() == Some(0) && v.firstClear() == Some(1));
+
+        v.set(0, false);
+        assert!(v.toggle(1) && v.firstSet() == Some(1));
+        let ones : Vec<usize> = v.iterOnes().collect();
+        assert!(ones.len() == 67 && ones[0] == 1 && ones[1] == 3 && ones[66] == 198);
+        assert!(v.iter().filter(|b| *b).count() == 67);
+
+        assert!(v.pop() == Some(false) && v.pop() == Some(true));
+        assert!(v.len() == 198);
+
+        v.fill(true);
+        assert!(v.countOnes() == 198 && v.firstClear().is_none());
+        v.clear();
+        assert!(v.isEmpty() && v.firstSet().is_none() && v.iterOnes().next().is_none());
+    }
+
+    #[test]
+    fn testBitVecResize() {
+        let mut v : BitVec = [true, false, true].iter().cloned().collect();
+        v.resize(130, true);
+        assert!(v.len() == 130 && v.countOnes() == 129 && !v[1] && v[129]);
+        assert!(v.asWords().len() == 3 && v.asWords()[2] == 0b11);
+
+        v.truncate(65);
+        assert!(v.countOnes() == 64 && v.asWords()[1] == 1);
+        v.resize(70, false);
+        assert!(v.countOnes() == 64 && v.firstClear() == Some(1));
+
+        let mut w = v.clone();
+        assert!(w == v && hashOf(&w) == hashOf(&v));
+        w.toggle(69);
+        assert!(w != v);
+        w.toggle(69);
+        w.pushBack(false);
+        assert!(w != v && hashOf(&w) != hashOf(&v));
+    }
+
+    #[test]
+    fn testBitSet() {
+        let a : BitSet = [1, 5, 64, 200].iter().cloned().collect();
+        let mut b = BitSet::new();
+        assert!(b.insert(5) && !b.insert(5) && b.insert(64) && b.insert(70));
+
+        assert!(a.len() == 4 && a.contains(200) && !a.contains(201) && !a.contains(10000));
+        assert!(a.union(&b).iter().collect::<Vec<usize>>().asArray() == [1, 5, 64, 70, 200]);
+        assert!(a.intersection(&b).iter().collect::<Vec<usize>>().asArray() == [5, 64]);
+        assert!(a.difference(&b).iter().collect::<Vec<usize>>().asArray() == [1, 200]);
+        assert!(b.difference(&a).iter().collect::<Vec<usize>>().asArray() == [70]);
+        assert!(a.symmetricDifference(&b).iter().collect::<Vec<usize>>().asArray() == [1, 70, 200]);
+
+        assert!(a.intersection(&b).isSubset(&a) && !a.isSubset(&b) && a.isSuperset(&a.intersection(&b)));
+        assert!(!a.isDisjoint(&b) && a.difference(&b).isDisjoint(&b));
+        assert!(a.first() == Some(1));
+
+        // trailing empty words don't matter
+        let mut c = a.clone();
+        c.insert(5000);
+        c.remove(5000);
+        assert!(c == a && hashOf(&c) == hashOf(&a));
+        c.shrinkToFit();
+        assert!(c.asWords().len() == 4);
+        assert!(!c.remove(5000) && c.remove(1) && c != a);
+        c.clear();
+        assert!(c.isEmpty() && c.first().is_none());
+    }
+}
@@ -0,0 +1,1183 @@
+//
+// Ordered map and set on a B-tree. Nodes hold up to `CAPACITY` sorted keys, every node but the
+// root holds at least `MIN_DEGREE - 1`. Insertion splits full nodes on the way down and removal
+// refills the small ones on the way down (CLRS), so both are single pass.
+//
+use core::*;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::marker::PhantomData;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use crate::mem::*;
+use crate::arrayvec::ArrayVec;
+
+const MIN_DEGREE : usize = 6;
+const CAPACITY : usize = 2 * MIN_DEGREE - 1;
+// a tree that deep would have more than 6^24 nodes
+const MAX_HEIGHT : usize = 24;
+
+struct Node<K, V> {
+    len     : usize,
+    leaf    : bool,
+    keys    : [MaybeUninit<K>; CAPACITY],
+    vals    : [MaybeUninit<V>; CAPACITY],
+    edges   : [*mut Node<K, V>; CAPACITY + 1],
+}
+
+#[inline]
+unsafe fn keys<K, V>(n: *mut Node<K, V>) -> *mut K { ptr::addr_of_mut!((*n).keys) as *mut K }
+#[inline]
+unsafe fn vals<K, V>(n: *mut Node<K, V>) -> *mut V { ptr::addr_of_mut!((*n).vals) as *mut V }
+#[inline]
+unsafe fn edges<K, V>(n: *mut Node<K, V>) -> *mut *mut Node<K, V> { ptr::addr_of_mut!((*n).edges) as *mut *mut Node<K, V> }
+#[inline]
+unsafe fn edge<K, V>(n: *mut Node<K, V>, i: usize) -> *mut Node<K, V> { *edges(n).add(i) }
+
+// `Ok(i)` if `k` is the key at `i`, `Err(i)` with the edge to follow otherwise
+unsafe fn search<K: Borrow<Q>, V, Q: ?Sized + Ord>(n: *mut Node<K, V>, k: &Q) -> Result<usize, usize> {
+    let ks = keys(n);
+    for i in 0..(*n).len {
+        match k.cmp((*ks.add(i)).borrow()) {
+            Ordering::Greater   => (),
+            Ordering::Equal     => return Ok(i),
+            Ordering::Less      => return Err(i),
+        }
+    }
+    Err((*n).len)
+}
+
+// insert `k, v` at `i`, and `e` at `i + 1` for internal nodes
+unsafe fn insertAt<K, V>(n: *mut Node<K, V>, i: usize, k: K, v: V, e: *mut Node<K, V>) {
+    let len = (*n).len;
+    ptr::copy(keys(n).add(i), keys(n).add(i + 1), len - i);
+    ptr::copy(vals(n).add(i), vals(n).add(i + 1), len - i);
+    keys(n).add(i).write(k);
+    vals(n).add(i).write(v);
+    if !(*n).leaf {
+        ptr::copy(edges(n).add(i + 1), edges(n).add(i + 2), len - i);
+        *edges(n).add(i + 1) = e;
+    }
+    (*n).len += 1;
+}
+
+// remove the key at `i`, and the edge at `i + 1` for internal nodes
+unsafe fn removeAt<K, V>(n: *mut Node<K, V>, i: usize) -> (K, V) {
+    let len = (*n).len;
+    let k = keys(n).add(i).read();
+    let v = vals(n).add(i).read();
+    ptr::copy(keys(n).add(i + 1), keys(n).add(i), len - i - 1);
+    ptr::copy(vals(n).add(i + 1), vals(n).add(i), len - i - 1);
+    if !(*n).leaf {
+        ptr::copy(edges(n).add(i + 2), edges(n).add(i + 1), len - i - 1);
+    }
+    (*n).len -= 1;
+    (k, v)
+}
+
+/// Ordered map, iterating in increasing key order.
+pub struct BTreeMap<K, V, A: Allocator = LibcAllocator> {
+    root    : *mut Node<K, V>,
+    count   : usize,
+    alloc   : A,
+}
+
+unsafe impl<K: Send, V: Send, A: Allocator + Send> Send for BTreeMap<K, V, A> {}
+unsafe impl<K: Sync, V: Sync, A: Allocator + Sync> Sync for BTreeMap<K, V, A> {}
+
+impl<K: Ord, V> BTreeMap<K, V> {
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+}
+
+impl<K: Ord, V, A: Allocator + Default> Default for BTreeMap<K, V, A> {
+    fn default() -> Self { Self::newIn(A::default()) }
+}
+
+impl<K, V, A: Allocator> BTreeMap<K, V, A> {
+    pub fn allocator(&self) -> &A { &self.alloc }
+
+    pub fn len(&self) -> usize { self.count }
+    pub fn isEmpty(&self) -> bool { self.count == 0 }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    fn allocNode(&self, leaf: bool) -> *mut Node<K, V> {
+        let size = mem::size_of::<Node<K, V>>();
+        let align = mem::align_of::<Node<K, V>>();
+        let n = unsafe { self.alloc.alloc(size, align) } as *mut Node<K, V>;
+        if n.is_null() { handleAllocError(AllocError { size, align }) }
+        unsafe {
+            ptr::addr_of_mut!((*n).len).write(0);
+            ptr::addr_of_mut!((*n).leaf).write(leaf);
+            ptr::addr_of_mut!((*n).edges).write([ptr::null_mut(); CAPACITY + 1]);
+        }
+        n
+    }
+
+    unsafe fn freeNode(&self, n: *mut Node<K, V>) {
+        self.alloc.free(n as *mut u8, mem::size_of::<Node<K, V>>(), mem::align_of::<Node<K, V>>())
+    }
+
+    unsafe fn freeTree(&self, n: *mut Node<K, V>) {
+        if !(*n).leaf {
+            for i in 0..=(*n).len {
+                self.freeTree(edge(n, i));
+            }
+        }
+        ptr::drop_in_place(ptr::slice_from_raw_parts_mut(keys(n), (*n).len));
+        ptr::drop_in_place(ptr::slice_from_raw_parts_mut(vals(n), (*n).len));
+        self.freeNode(n);
+    }
+
+    pub fn clear(&mut self) {
+        // detach the tree first so a panicking destructor can't leave `root` dangling
+        let r = mem::replace(&mut self.root, ptr::null_mut());
+        self.count = 0;
+        if !r.is_null() {
+            unsafe { self.freeTree(r) };
+        }
+    }
+
+    // split the full child `i` of `x` around its median key, which moves up into `x`
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    unsafe fn splitChild(&self, x: *mut Node<K, V>, i: usize) {
+        let y = edge(x, i);
+        let z = self.allocNode((*y).leaf);
+        let t = MIN_DEGREE;
+        ptr::copy_nonoverlapping(keys(y).add(t), keys(z), t - 1);
+        ptr::copy_nonoverlapping(vals(y).add(t), vals(z), t - 1);
+        if !(*y).leaf {
+            ptr::copy_nonoverlapping(edges(y).add(t), edges(z), t);
+        }
+        (*z).len = t - 1;
+        (*y).len = t - 1;
+        insertAt(x, i, keys(y).add(t - 1).read(), vals(y).add(t - 1).read(), z);
+    }
+
+    // merge the child `i + 1` of `x` and the key between them into the child `i`
+    unsafe fn merge(&self, x: *mut Node<K, V>, i: usize) {
+        let y = edge(x, i);
+        let z = edge(x, i + 1);
+        let (k, v) = removeAt(x, i);
+        let yl = (*y).len;
+        let zl = (*z).len;
+        keys(y).add(yl).write(k);
+        vals(y).add(yl).write(v);
+        ptr::copy_nonoverlapping(keys(z), keys(y).add(yl + 1), zl);
+        ptr::copy_nonoverlapping(vals(z), vals(y).add(yl + 1), zl);
+        if !(*y).leaf {
+            ptr::copy_nonoverlapping(edges(z), edges(y).add(yl + 1), zl + 1);
+        }
+        (*y).len = yl + 1 + zl;
+        self.freeNode(z);
+    }
+
+    // rotate a key from the child `i - 1` of `x` through `x` into the child `i`
+    unsafe fn borrowFromLeft(x: *mut Node<K, V>, i: usize) {
+        let c = edge(x, i);
+        let l = edge(x, i - 1);
+        let cl = (*c).len;
+        let ll = (*l).len;
+        ptr::copy(keys(c), keys(c).add(1), cl);
+        ptr::copy(vals(c), vals(c).add(1), cl);
+        keys(c).write(keys(x).add(i - 1).read());
+        vals(c).write(vals(x).add(i - 1).read());
+        keys(x).add(i - 1).write(keys(l).add(ll - 1).read());
+        vals(x).add(i - 1).write(vals(l).add(ll - 1).read());
+        if !(*c).leaf {
+            ptr::copy(edges(c), edges(c).add(1), cl + 1);
+            *edges(c) = edge(l, ll);
+        }
+        (*l).len -= 1;
+        (*c).len += 1;
+    }
+
+    // rotate a key from the child `i + 1` of `x` through `x` into the child `i`
+    unsafe fn borrowFromRight(x: *mut Node<K, V>, i: usize) {
+        let c = edge(x, i);
+        let r = edge(x, i + 1);
+        let cl = (*c).len;
+        let rl = (*r).len;
+        keys(c).add(cl).write(keys(x).add(i).read());
+        vals(c).add(cl).write(vals(x).add(i).read());
+        keys(x).add(i).write(keys(r).read());
+        vals(x).add(i).write(vals(r).read());
+        ptr::copy(keys(r).add(1), keys(r), rl - 1);
+        ptr::copy(vals(r).add(1), vals(r), rl - 1);
+        if !(*c).leaf {
+            *edges(c).add(cl + 1) = edge(r, 0);
+            ptr::copy(edges(r).add(1), edges(r), rl);
+        }
+        (*r).len -= 1;
+        (*c).len += 1;
+    }
+
+    // make sure the child `i` of `x` has at least `MIN_DEGREE` keys before descending into it,
+    // returns the index of the child that now covers the same keys
+    unsafe fn fillChild(&self, x: *mut Node<K, V>, i: usize) -> usize {
+        if (*edge(x, i)).len >= MIN_DEGREE { return i }
+        if i > 0 && (*edge(x, i - 1)).len >= MIN_DEGREE {
+            Self::borrowFromLeft(x, i);
+            i
+        } else if i < (*x).len && (*edge(x, i + 1)).len >= MIN_DEGREE {
+            Self::borrowFromRight(x, i);
+            i
+        } else if i < (*x).len {
+            self.merge(x, i);
+            i
+        } else {
+            self.merge(x, i - 1);
+            i - 1
+        }
+    }
+
+    // remove the greatest (or smallest) entry under `n`, which must not be a minimal non root node
+    unsafe fn removeLast(&self, mut n: *mut Node<K, V>, max: bool) -> (K, V) {
+        loop {
+            if (*n).leaf {
+                let i = if max { (*n).len - 1 } else { 0 };
+                return removeAt(n, i)
+            }
+            let i = if max { (*n).len } else { 0 };
+            let j = self.fillChild(n, i);
+            n = edge(n, j);
+        }
+    }
+
+    // drop the root if removals emptied it
+    unsafe fn fixRoot(alloc: &A, root: &mut *mut Node<K, V>) {
+        let r = *root;
+        if (*r).len == 0 {
+            *root = if (*r).leaf { ptr::null_mut() } else { edge(r, 0) };
+            alloc.free(r as *mut u8, mem::size_of::<Node<K, V>>(), mem::align_of::<Node<K, V>>());
+        }
+    }
+
+    pub fn popFirst(&mut self) -> Option<(K, V)> {
+        if self.root.is_null() { return None }
+        unsafe {
+            let kv = self.removeLast(self.root, false);
+            Self::fixRoot(&self.alloc, &mut self.root);
+            self.count -= 1;
+            Some(kv)
+        }
+    }
+
+    pub fn popLast(&mut self) -> Option<(K, V)> {
+        if self.root.is_null() { return None }
+        unsafe {
+            let kv = self.removeLast(self.root, true);
+            Self::fixRoot(&self.alloc, &mut self.root);
+            self.count -= 1;
+            Some(kv)
+        }
+    }
+
+    pub fn first(&self) -> Option<(&K, &V)> { self.iter().next() }
+    pub fn last(&self) -> Option<(&K, &V)> { self.iter().next_back() }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { raw: RawRange::full(self.root), remaining: self.count, marker: PhantomData }
+    }
+
+    pub fn iterMut(&mut self) -> IterMut<'_, K, V> {
+        IterMut { raw: RawRange::full(self.root), remaining: self.count, marker: PhantomData }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> { Keys { iter: self.iter() } }
+    pub fn values(&self) -> Values<'_, K, V> { Values { iter: self.iter() } }
+    pub fn valuesMut(&mut self) -> ValuesMut<'_, K, V> { ValuesMut { iter: self.iterMut() } }
+}
+
+impl<K: Ord, V, A: Allocator> BTreeMap<K, V, A> {
+    pub fn newIn(alloc: A) -> Self { Self { root: ptr::null_mut(), count: 0, alloc } }
+
+    // position of the entry for `k`
+    fn find<Q: ?Sized + Ord>(&self, k: &Q) -> Option<(*mut Node<K, V>, usize)> where K: Borrow<Q> {
+        let mut n = self.root;
+        while !n.is_null() {
+            match unsafe { search(n, k) } {
+                Ok(i) => return Some((n, i)),
+                Err(i) => n = if unsafe { (*n).leaf } { ptr::null_mut() } else { unsafe { edge(n, i) } },
+            }
+        }
+        None
+    }
+
+    pub fn get<Q: ?Sized + Ord>(&self, k: &Q) -> Option<&V> where K: Borrow<Q> {
+        self.find(k).map(|(n, i)| unsafe { &*vals(n).add(i) })
+    }
+
+    pub fn getMut<Q: ?Sized + Ord>(&mut self, k: &Q) -> Option<&mut V> where K: Borrow<Q> {
+        self.find(k).map(|(n, i)| unsafe { &mut *vals(n).add(i) })
+    }
+
+    pub fn getKeyValue<Q: ?Sized + Ord>(&self, k: &Q) -> Option<(&K, &V)> where K: Borrow<Q> {
+        self.find(k).map(|(n, i)| unsafe { (&*keys(n).add(i), &*vals(n).add(i)) })
+    }
+
+    pub fn containsKey<Q: ?Sized + Ord>(&self, k: &Q) -> bool where K: Borrow<Q> { self.find(k).is_some() }
+
+    // insert or replace, returns where the value is stored and the replaced one
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    fn insertEntry(&mut self, k: K, v: V) -> (*mut V, Option<V>) {
+        unsafe {
+            if self.root.is_null() {
+                self.root = self.allocNode(true);
+            } else if (*self.root).len == CAPACITY {
+                let r = self.allocNode(false);
+                *edges(r) = self.root;
+                self.splitChild(r, 0);
+                self.root = r;
+            }
+
+            let mut n = self.root;
+            loop {
+                match search(n, &k) {
+                    Ok(i) => return (vals(n).add(i), Some(mem::replace(&mut *vals(n).add(i), v))),
+                    Err(i) if (*n).leaf => {
+                        insertAt(n, i, k, v, ptr::null_mut());
+                        self.count += 1;
+                        return (vals(n).add(i), None)
+                    }
+                    Err(mut i) => {
+                        if (*edge(n, i)).len == CAPACITY {
+                            self.splitChild(n, i);
+                            match k.cmp(&*keys(n).add(i)) {
+                                Ordering::Less      => (),
+                                Ordering::Greater   => i += 1,
+                                Ordering::Equal     => return (vals(n).add(i), Some(mem::replace(&mut *vals(n).add(i), v))),
+                            }
+                        }
+                        n = edge(n, i);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Insert `v` for `k`, returns the previous value (the key already in the map is kept).
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> { self.insertEntry(k, v).1 }
+
+    pub fn removeEntry<Q: ?Sized + Ord>(&mut self, k: &Q) -> Option<(K, V)> where K: Borrow<Q> {
+        if self.root.is_null() { return None }
+        unsafe {
+            let mut n = self.root;
+            let kv = loop {
+                match search(n, k) {
+                    Ok(i) if (*n).leaf => break Some(removeAt(n, i)),
+                    Ok(i) => {
+                        // replace by the predecessor or the successor, or merge and descend
+                        let replacement = if (*edge(n, i)).len >= MIN_DEGREE {
+                            self.removeLast(edge(n, i), true)
+                        } else if (*edge(n, i + 1)).len >= MIN_DEGREE {
+                            self.removeLast(edge(n, i + 1), false)
+                        } else {
+                            self.merge(n, i);
+                            n = edge(n, i);
+                            continue
+                        };
+                        let k = mem::replace(&mut *keys(n).add(i), replacement.0);
+                        let v = mem::replace(&mut *vals(n).add(i), replacement.1);
+                        break Some((k, v))
+                    }
+                    Err(_) if (*n).leaf => break None,
+                    Err(i) => {
+                        let j = self.fillChild(n, i);
+                        n = edge(n, j);
+                    }
+                }
+            };
+            Self::fixRoot(&self.alloc, &mut self.root);
+            if kv.is_some() { self.count -= 1 }
+            kv
+        }
+    }
+
+    pub fn remove<Q: ?Sized + Ord>(&mut self, k: &Q) -> Option<V> where K: Borrow<Q> {
+        self.removeEntry(k).map(|(_, v)| v)
+    }
+
+    /// Keep only the entries for which `f` returns true.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        // walk the entries in order and remove the rejected ones in place. A removal reshapes the
+        // tree, so the walk then restarts at the key that followed, looked up through a bitwise
+        // copy that is only used while the original is still in the tree
+        let mut raw = RawRange::full(self.root);
+        while let Some((n, i)) = raw.next() {
+            if unsafe { f(&*keys(n).add(i), &mut *vals(n).add(i)) } { continue }
+            let k = unsafe { ManuallyDrop::new(keys(n).add(i).read()) };
+            let next = raw.next().map(|(n, i)| unsafe { ManuallyDrop::new(keys(n).add(i).read()) });
+            let kv = self.removeEntry(&*k);
+            debug_assert!(kv.is_some());
+            match &next {
+                Some(nk) => raw = RawRange::bounded(self.root, &(ops::Bound::Included(&**nk), ops::Bound::Unbounded)),
+                None => break,
+            }
+        }
+    }
+
+    /// Iterate over the entries with a key in `range`, in increasing order.
+    pub fn range<Q: ?Sized + Ord, R: ops::RangeBounds<Q>>(&self, range: R) -> Range<'_, K, V> where K: Borrow<Q> {
+        Range { raw: RawRange::bounded(self.root, &range), marker: PhantomData }
+    }
+
+    pub fn rangeMut<Q: ?Sized + Ord, R: ops::RangeBounds<Q>>(&mut self, range: R) -> RangeMut<'_, K, V> where K: Borrow<Q> {
+        RangeMut { raw: RawRange::bounded(self.root, &range), marker: PhantomData }
+    }
+
+    /// The entry for `k`, to update or insert in place.
+    pub fn entry(&mut self, k: K) -> Entry<'_, K, V, A> {
+        match self.find(&k) {
+            Some((n, i)) => Entry::Occupied(OccupiedEntry { map: self, node: n, index: i }),
+            None => Entry::Vacant(VacantEntry { map: self, key: k }),
+        }
+    }
+}
+
+impl<K, V, A: Allocator> Drop for BTreeMap<K, V, A> {
+    fn drop(&mut self) { self.clear() }
+}
+
+impl<K: Ord + Clone, V: Clone, A: Allocator + Clone> Clone for BTreeMap<K, V, A> {
+    fn clone(&self) -> Self {
+        let mut m = Self::newIn(self.alloc.clone());
+        for (k, v) in self.iter() {
+            m.insert(k.clone(), v.clone());
+        }
+        m
+    }
+}
+
+impl<K: Ord + Borrow<Q>, Q: ?Sized + Ord, V, A: Allocator> ops::Index<&Q> for BTreeMap<K, V, A> {
+    type Output = V;
+    fn index(&self, k: &Q) -> &V { self.get(k).expect("key not in BTreeMap") }
+}
+
+impl<K: Ord, V, A: Allocator> iter::Extend<(K, V)> for BTreeMap<K, V, A> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, it: I) {
+        for (k, v) in it {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K: Ord, V, A: Allocator + Default> iter::FromIterator<(K, V)> for BTreeMap<K, V, A> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(it: I) -> Self {
+        let mut m = Self::newIn(A::default());
+        m.extend(it);
+        m
+    }
+}
+
+// Positions of the next entries at both ends, as stacks of `(node, index)` from the root down.
+// At the front `index` is the next key of `node` to visit, at the back it is one past it.
+struct RawRange<K, V> {
+    front   : ArrayVec<(*mut Node<K, V>, usize), MAX_HEIGHT>,
+    back    : ArrayVec<(*mut Node<K, V>, usize), MAX_HEIGHT>,
+    done    : bool,
+}
+
+#[inline]
+fn push<K, V>(stack: &mut ArrayVec<(*mut Node<K, V>, usize), MAX_HEIGHT>, n: *mut Node<K, V>, i: usize) {
+    let r = stack.pushBack((n, i));
+    debug_assert!(r.is_ok());
+}
+
+impl<K, V> RawRange<K, V> {
+    fn full(root: *mut Node<K, V>) -> Self {
+        let mut r = Self { front: ArrayVec::new(), back: ArrayVec::new(), done: root.is_null() };
+        if !root.is_null() {
+            unsafe {
+                r.pushLeft(root);
+                r.pushRight(root);
+            }
+        }
+        r
+    }
+
+    fn bounded<Q: ?Sized + Ord, R: ops::RangeBounds<Q>>(root: *mut Node<K, V>, range: &R) -> Self where K: Borrow<Q> + Ord {
+        let mut r = Self { front: ArrayVec::new(), back: ArrayVec::new(), done: root.is_null() };
+        if root.is_null() { return r }
+        unsafe {
+            // the first key not below the start bound and the last not above the end bound
+            let mut n = root;
+            loop {
+                let ks = slice::from_raw_parts(keys(n), (*n).len);
+                let i = match range.start_bound() {
+                    ops::Bound::Included(b) => ks.iter().position(|k| k.borrow() >= b),
+                    ops::Bound::Excluded(b) => ks.iter().position(|k| k.borrow() > b),
+                    ops::Bound::Unbounded   => Some(0),
+                }.unwrap_or(ks.len());
+                push(&mut r.front, n, i);
+                if (*n).leaf { break }
+                n = edge(n, i);
+            }
+
+            let mut n = root;
+            loop {
+                let ks = slice::from_raw_parts(keys(n), (*n).len);
+                let i = match range.end_bound() {
+                    ops::Bound::Included(b) => ks.iter().filter(|k| (*k).borrow() <= b).count(),
+                    ops::Bound::Excluded(b) => ks.iter().filter(|k| (*k).borrow() < b).count(),
+                    ops::Bound::Unbounded   => ks.len(),
+                };
+                push(&mut r.back, n, i);
+                if (*n).leaf { break }
+                n = edge(n, i);
+            }
+
+            // empty when the first candidate is past the last one
+            r.done = match (r.frontPos(), r.backPos()) {
+                (Some((f, i)), Some((b, j))) => *keys(f).add(i) > *keys(b).add(j),
+                _ => true,
+            };
+        }
+        r
+    }
+
+    unsafe fn pushLeft(&mut self, mut n: *mut Node<K, V>) {
+        loop {
+            push(&mut self.front, n, 0);
+            if (*n).leaf { break }
+            n = edge(n, 0);
+        }
+    }
+
+    unsafe fn pushRight(&mut self, mut n: *mut Node<K, V>) {
+        loop {
+            push(&mut self.back, n, (*n).len);
+            if (*n).leaf { break }
+            n = edge(n, (*n).len);
+        }
+    }
+
+    // the next entry at the front, dropping the exhausted nodes
+    fn frontPos(&mut self) -> Option<(*mut Node<K, V>, usize)> {
+        loop {
+            let &(n, i) = self.front.last()?;
+            if i < unsafe { (*n).len } { return Some((n, i)) }
+            self.front.pop();
+        }
+    }
+
+    fn backPos(&mut self) -> Option<(*mut Node<K, V>, usize)> {
+        loop {
+            let &(n, i) = self.back.last()?;
+            if i > 0 { return Some((n, i - 1)) }
+            self.back.pop();
+        }
+    }
+
+    fn next(&mut self) -> Option<(*mut Node<K, V>, usize)> {
+        if self.done { return None }
+        let f = self.frontPos();
+        if f.is_none() || f == self.backPos() { self.done = true }
+        let (n, i) = f?;
+        let l = self.front.len();
+        self.front[l - 1].1 = i + 1;
+        if unsafe { !(*n).leaf } { unsafe { self.pushLeft(edge(n, i + 1)) } }
+        Some((n, i))
+    }
+
+    fn nextBack(&mut self) -> Option<(*mut Node<K, V>, usize)> {
+        if self.done { return None }
+        let b = self.backPos();
+        if b.is_none() || b == self.frontPos() { self.done = true }
+        let (n, i) = b?;
+        let l = self.back.len();
+        self.back[l - 1].1 = i;
+        if unsafe { !(*n).leaf } { unsafe { self.pushRight(edge(n, i)) } }
+        Some((n, i))
+    }
+}
+
+/// Iterator over the entries of a `BTreeMap`, in key order.
+pub struct Iter<'a, K, V> {
+    raw         : RawRange<K, V>,
+    remaining   : usize,
+    marker      : PhantomData<(&'a K, &'a V)>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let (n, i) = self.raw.next()?;
+        self.remaining -= 1;
+        Some(unsafe { (&*keys(n).add(i), &*vals(n).add(i)) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.remaining, Some(self.remaining)) }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        let (n, i) = self.raw.nextBack()?;
+        self.remaining -= 1;
+        Some(unsafe { (&*keys(n).add(i), &*vals(n).add(i)) })
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+/// Iterator over the entries of a `BTreeMap` with mutable values, in key order.
+pub struct IterMut<'a, K, V> {
+    raw         : RawRange<K, V>,
+    remaining   : usize,
+    marker      : PhantomData<(&'a K, &'a mut V)>,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        let (n, i) = self.raw.next()?;
+        self.remaining -= 1;
+        Some(unsafe { (&*keys(n).add(i), &mut *vals(n).add(i)) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.remaining, Some(self.remaining)) }
+}
+
+impl<'a, K, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
+        let (n, i) = self.raw.nextBack()?;
+        self.remaining -= 1;
+        Some(unsafe { (&*keys(n).add(i), &mut *vals(n).add(i)) })
+    }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+/// Iterator over the entries of a `BTreeMap` in a key range.
+pub struct Range<'a, K, V> {
+    raw     : RawRange<K, V>,
+    marker  : PhantomData<(&'a K, &'a V)>,
+}
+
+impl<'a, K, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let (n, i) = self.raw.next()?;
+        Some(unsafe { (&*keys(n).add(i), &*vals(n).add(i)) })
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a V)> {
+        let (n, i) = self.raw.nextBack()?;
+        Some(unsafe { (&*keys(n).add(i), &*vals(n).add(i)) })
+    }
+}
+
+/// Iterator over the entries of a `BTreeMap` in a key range, with mutable values.
+pub struct RangeMut<'a, K, V> {
+    raw     : RawRange<K, V>,
+    marker  : PhantomData<(&'a K, &'a mut V)>,
+}
+
+impl<'a, K, V> Iterator for RangeMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        let (n, i) = self.raw.next()?;
+        Some(unsafe { (&*keys(n).add(i), &mut *vals(n).add(i)) })
+    }
+}
+
+impl<'a, K, V> DoubleEndedIterator for RangeMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<(&'a K, &'a mut V)> {
+        let (n, i) = self.raw.nextBack()?;
+        Some(unsafe { (&*keys(n).add(i), &mut *vals(n).add(i)) })
+    }
+}
+
+pub struct Keys<'a, K, V> {
+    iter    : Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> { self.iter.next().map(|(k, _)| k) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a K> { self.iter.next_back().map(|(k, _)| k) }
+}
+
+pub struct Values<'a, K, V> {
+    iter    : Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<&'a V> { self.iter.next().map(|(_, v)| v) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+impl<'a, K, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> { self.iter.next_back().map(|(_, v)| v) }
+}
+
+pub struct ValuesMut<'a, K, V> {
+    iter    : IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+    fn next(&mut self) -> Option<&'a mut V> { self.iter.next().map(|(_, v)| v) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+impl<'a, K, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a mut V> { self.iter.next_back().map(|(_, v)| v) }
+}
+
+/// Owning iterator for `BTreeMap`, the entries not consumed are dropped with it.
+pub struct IntoIter<K, V, A: Allocator = LibcAllocator> {
+    map     : BTreeMap<K, V, A>,
+}
+
+impl<K, V, A: Allocator> Iterator for IntoIter<K, V, A> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<(K, V)> { self.map.popFirst() }
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.map.len(), Some(self.map.len())) }
+}
+
+impl<K, V, A: Allocator> DoubleEndedIterator for IntoIter<K, V, A> {
+    fn next_back(&mut self) -> Option<(K, V)> { self.map.popLast() }
+}
+
+impl<K, V, A: Allocator> ExactSizeIterator for IntoIter<K, V, A> {}
+
+impl<K, V, A: Allocator> IntoIterator for BTreeMap<K, V, A> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V, A>;
+    fn into_iter(self) -> IntoIter<K, V, A> { IntoIter { map: self } }
+}
+
+impl<'a, K, V, A: Allocator> IntoIterator for &'a BTreeMap<K, V, A> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Iter<'a, K, V> { self.iter() }
+}
+
+impl<'a, K, V, A: Allocator> IntoIterator for &'a mut BTreeMap<K, V, A> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+    fn into_iter(self) -> IterMut<'a, K, V> { self.iterMut() }
+}
+
+/// A `BTreeMap` entry, see `BTreeMap::entry`.
+pub enum Entry<'a, K: Ord, V, A: Allocator = LibcAllocator> {
+    Occupied(OccupiedEntry<'a, K, V, A>),
+    Vacant(VacantEntry<'a, K, V, A>),
+}
+
+pub struct OccupiedEntry<'a, K: Ord, V, A: Allocator = LibcAllocator> {
+    map     : &'a mut BTreeMap<K, V, A>,
+    node    : *mut Node<K, V>,
+    index   : usize,
+}
+
+pub struct VacantEntry<'a, K: Ord, V, A: Allocator = LibcAllocator> {
+    map     : &'a mut BTreeMap<K, V, A>,
+    key     : K,
+}
+
+impl<'a, K: Ord, V, A: Allocator> Entry<'a, K, V, A> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+
+    /// The value, inserting `v` first if the entry is vacant.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn orInsert(self, v: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.intoMut(),
+            Entry::Vacant(e) => e.insert(v),
+        }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn orInsertWith<F: FnOnce() -> V>(self, f: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.intoMut(),
+            Entry::Vacant(e) => e.insert(f()),
+        }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn orDefault(self) -> &'a mut V where V: Default { self.orInsertWith(V::default) }
+
+    /// Update the value in place if the entry is occupied.
+    pub fn andModify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut e) => {
+                f(e.getMut());
+                Entry::Occupied(e)
+            }
+            Entry::Vacant(e) => Entry::Vacant(e),
+        }
+    }
+}
+
+impl<'a, K: Ord, V, A: Allocator> OccupiedEntry<'a, K, V, A> {
+    pub fn key(&self) -> &K { unsafe { &*keys(self.node).add(self.index) } }
+    pub fn get(&self) -> &V { unsafe { &*vals(self.node).add(self.index) } }
+    pub fn getMut(&mut self) -> &mut V { unsafe { &mut *vals(self.node).add(self.index) } }
+    pub fn intoMut(self) -> &'a mut V { unsafe { &mut *vals(self.node).add(self.index) } }
+
+    /// Replace the value, returning the old one.
+    pub fn insert(&mut self, v: V) -> V { mem::replace(self.getMut(), v) }
+
+    pub fn removeEntry(self) -> (K, V) {
+        // the removal moves the entry around, so look it up through a bitwise copy of the key,
+        // only ever compared and never dropped
+        let k = ManuallyDrop::new(unsafe { keys(self.node).add(self.index).read() });
+        self.map.removeEntry(&*k).unwrap()
+    }
+
+    pub fn remove(self) -> V { self.removeEntry().1 }
+}
+
+impl<'a, K: Ord, V, A: Allocator> VacantEntry<'a, K, V, A> {
+    pub fn key(&self) -> &K { &self.key }
+    pub fn intoKey(self) -> K { self.key }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn insert(self, v: V) -> &'a mut V { unsafe { &mut *self.map.insertEntry(self.key, v).0 } }
+}
+
+/// Ordered set on top of `BTreeMap`.
+pub struct BTreeSet<K, A: Allocator = LibcAllocator> {
+    map     : BTreeMap<K, (), A>,
+}
+
+impl<K: Ord> BTreeSet<K> {
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+}
+
+impl<K: Ord, A: Allocator + Default> Default for BTreeSet<K, A> {
+    fn default() -> Self { Self::newIn(A::default()) }
+}
+
+impl<K: Ord, A: Allocator> BTreeSet<K, A> {
+    pub fn newIn(alloc: A) -> Self { Self { map: BTreeMap::newIn(alloc) } }
+
+    pub fn allocator(&self) -> &A { self.map.allocator() }
+
+    pub fn len(&self) -> usize { self.map.len() }
+    pub fn isEmpty(&self) -> bool { self.map.isEmpty() }
+    pub fn clear(&mut self) { self.map.clear() }
+
+    /// Insert `k`, returns false if it was already there (the set keeps the old key).
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn insert(&mut self, k: K) -> bool { self.map.insert(k, ()).is_none() }
+
+    pub fn contains<Q: ?Sized + Ord>(&self, k: &Q) -> bool where K: Borrow<Q> { self.map.containsKey(k) }
+    pub fn get<Q: ?Sized + Ord>(&self, k: &Q) -> Option<&K> where K: Borrow<Q> { self.map.getKeyValue(k).map(|(k, _)| k) }
+
+    /// Remove `k`, returns false if it wasn't there.
+    pub fn remove<Q: ?Sized + Ord>(&mut self, k: &Q) -> bool where K: Borrow<Q> { self.map.removeEntry(k).is_some() }
+    pub fn take<Q: ?Sized + Ord>(&mut self, k: &Q) -> Option<K> where K: Borrow<Q> { self.map.removeEntry(k).map(|(k, _)| k) }
+
+    pub fn first(&self) -> Option<&K> { self.map.first().map(|(k, _)| k) }
+    pub fn last(&self) -> Option<&K> { self.map.last().map(|(k, _)| k) }
+    pub fn popFirst(&mut self) -> Option<K> { self.map.popFirst().map(|(k, _)| k) }
+    pub fn popLast(&mut self) -> Option<K> { self.map.popLast().map(|(k, _)| k) }
+
+    pub fn retain<F: FnMut(&K) -> bool>(&mut self, mut f: F) { self.map.retain(|k, _| f(k)) }
+
+    pub fn iter(&self) -> Keys<'_, K, ()> { self.map.keys() }
+
+    /// Iterate over the keys in `range`, in increasing order.
+    pub fn range<Q: ?Sized + Ord, R: ops::RangeBounds<Q>>(&self, range: R) -> SetRange<'_, K> where K: Borrow<Q> {
+        SetRange { range: self.map.range(range) }
+    }
+
+    pub fn isSubset<B: Allocator>(&self, other: &BTreeSet<K, B>) -> bool {
+        self.len() <= other.len() && self.iter().all(|k| other.contains(k))
+    }
+
+    pub fn isDisjoint<B: Allocator>(&self, other: &BTreeSet<K, B>) -> bool {
+        self.iter().all(|k| !other.contains(k))
+    }
+}
+
+impl<K: Ord + Clone, A: Allocator + Clone> Clone for BTreeSet<K, A> {
+    fn clone(&self) -> Self { Self { map: self.map.clone() } }
+}
+
+/// Iterator over the keys of a `BTreeSet` in a range.
+pub struct SetRange<'a, K> {
+    range   : Range<'a, K, ()>,
+}
+
+impl<'a, K> Iterator for SetRange<'a, K> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> { self.range.next().map(|(k, _)| k) }
+}
+
+impl<'a, K> DoubleEndedIterator for SetRange<'a, K> {
+    fn next_back(&mut self) -> Option<&'a K> { self.range.next_back().map(|(k, _)| k) }
+}
+
+/// Owning iterator for `BTreeSet`.
+pub struct SetIntoIter<K, A: Allocator = LibcAllocator> {
+    iter    : IntoIter<K, (), A>,
+}
+
+impl<K, A: Allocator> Iterator for SetIntoIter<K, A> {
+    type Item = K;
+    fn next(&mut self) -> Option<K> { self.iter.next().map(|(k, _)| k) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+impl<K, A: Allocator> DoubleEndedIterator for SetIntoIter<K, A> {
+    fn next_back(&mut self) -> Option<K> { self.iter.next_back().map(|(k, _)| k) }
+}
+
+impl<K, A: Allocator> IntoIterator for BTreeSet<K, A> {
+    type Item = K;
+    type IntoIter = SetIntoIter<K, A>;
+    fn into_iter(self) -> SetIntoIter<K, A> { SetIntoIter { iter: self.map.into_iter() } }
+}
+
+impl<'a, K, A: Allocator> IntoIterator for &'a BTreeSet<K, A> {
+    type Item = &'a K;
+    type IntoIter = Keys<'a, K, ()>;
+    fn into_iter(self) -> Keys<'a, K, ()> { self.map.keys() }
+}
+
+impl<K: Ord, A: Allocator> iter::Extend<K> for BTreeSet<K, A> {
+    fn extend<I: IntoIterator<Item = K>>(&mut self, it: I) {
+        for k in it {
+            self.insert(k);
+        }
+    }
+}
+
+impl<K: Ord, A: Allocator + Default> iter::FromIterator<K> for BTreeSet<K, A> {
+    fn from_iter<I: IntoIterator<Item = K>>(it: I) -> Self {
+        let mut s = Self::newIn(A::default());
+        s.extend(it);
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec::*;
+    use crate::string::*;
+    use crate::sort::*;
+    use crate::testutil::*;
+
+    // check the node sizes, the key order and that all the leaves are at the same depth
+    fn checkNode<K: Ord, V>(n: *mut Node<K, V>, isRoot: bool, lo: Option<&K>, hi: Option<&K>) -> (usize, usize) {
+        unsafe {
+            let len = (*n).len;
+            assert!(len <= CAPACITY && (isRoot || len >= MIN_DEGREE - 1) && len > 0);
+            let ks = slice::from_raw_parts(keys(n), len);
+            assert!(ks.windows(2).all(|w| w[0] < w[1]));
+            assert!(lo.is_none_or(|l| *l < ks[0]) && hi.is_none_or(|h| ks[len - 1] < *h));
+            if (*n).leaf { return (1, len) }
+
+            let mut depth = None;
+            let mut count = len;
+            for i in 0..=len {
+                let l = if i == 0 { lo } else { Some(&ks[i - 1]) };
+                let h = if i == len { hi } else { Some(&ks[i]) };
+                let (d, c) = checkNode(edge(n, i), false, l, h);
+                assert!(depth.is_none_or(|x| x == d));
+                depth = Some(d);
+                count += c;
+            }
+            (depth.unwrap() + 1, count)
+        }
+    }
+
+    fn check<K: Ord, V>(m: &BTreeMap<K, V>) {
+        if m.root.is_null() {
+            assert!(m.len() == 0);
+        } else {
+            assert!(checkNode(m.root, true, None, None).1 == m.len());
+        }
+    }
+
+    #[test]
+    fn testRandomOps() {
+        let mut rng = Lcg(23);
+        let mut m = BTreeMap::new();
+        let mut reference : Vec<u32> = Vec::new();
+        for round in 0..4000 {
+            let k = rng.next() % 500;
+            let present = reference.contains(&k);
+            if rng.next() % 3 == 1 {
+                assert!(m.remove(&k) == if present { Some(k * 2) } else { None });
+                reference.retain(|x| *x != k);
+            } else {
+                assert!(m.insert(k, k * 2) == if present { Some(k * 2) } else { None });
+                if !present { reference.pushBack(k) }
+            }
+            if round % 200 == 0 { check(&m) }
+        }
+        check(&m);
+        reference.sortUnstable();
+        assert!(m.len() == reference.len());
+        assert!(m.keys().cloned().collect::<Vec<u32>>().asArray() == reference.asArray());
+        assert!(m.keys().rev().cloned().eq(reference.iter().rev().cloned()));
+        for k in reference.iter() {
+            assert!(m[k] == k * 2 && m.containsKey(k));
+        }
+
+        // drain from both ends
+        let mut i = 0;
+        let mut j = reference.len();
+        while !m.isEmpty() {
+            if m.len() % 2 == 0 {
+                assert!(m.popFirst().unwrap().0 == reference[i]);
+                i += 1;
+            } else {
+                j -= 1;
+                assert!(m.popLast().unwrap().0 == reference[j]);
+            }
+            if m.len() % 50 == 0 { check(&m) }
+        }
+        assert!(i == j && m.popFirst().is_none() && m.first().is_none());
+    }
+
+    #[test]
+    fn testRange() {
+        let m : BTreeMap<i32, i32> = (0..1000).map(|i| (i * 2, i)).collect();
+        check(&m);
+        assert!(m.first() == Some((&0, &0)) && m.last() == Some((&1998, &999)));
+
+        let r : Vec<i32> = m.range(10..20).map(|(k, _)| *k).collect();
+        assert!(r.asArray() == [10, 12, 14, 16, 18]);
+        let r : Vec<i32> = m.range(11..=20).map(|(k, _)| *k).collect();
+        assert!(r.asArray() == [12, 14, 16, 18, 20]);
+        let r : Vec<i32> = m.range((ops::Bound::Excluded(10), ops::Bound::Excluded(16))).rev().map(|(k, _)| *k).collect();
+        assert!(r.asArray() == [14, 12]);
+        assert!(m.range(..).count() == 1000 && m.range(1990..).count() == 5 && m.range(..=4).count() == 3);
+        assert!(m.range(11..12).next().is_none() && m.range(5000..).next().is_none() && m.range(..0).next().is_none());
+        assert!(m.range((ops::Bound::Included(20), ops::Bound::Excluded(10))).next().is_none());
+
+        // meeting in the middle from both ends
+        let mut r = m.range(100..=110);
+        assert!(r.next() == Some((&100, &50)) && r.next_back() == Some((&110, &55)));
+        let mut n = 0;
+        while (if n % 2 == 0 { r.next() } else { r.next_back() }).is_some() { n += 1 }
+        assert!(n == 4);
+
+        let mut m = m;
+        for (_, v) in m.rangeMut(0..10) {
+            *v = -1;
+        }
+        assert!(m.values().filter(|v| **v == -1).count() == 5);
+        for v in m.valuesMut() {
+            *v += 1;
+        }
+        assert!(m[&0] == 0 && m[&10] == 6);
+    }
+
+    #[test]
+    fn testEntry() {
+        let mut m : BTreeMap<String, i32> = BTreeMap::new();
+        for w in ["b", "a", "b", "c", "b", "a"] {
+            *m.entry(String::from(w)).orInsert(0) += 1;
+        }
+        assert!(m["a"] == 2 && m["b"] == 3 && m["c"] == 1);
+
+        m.entry(String::from("a")).andModify(|v| *v *= 10).orDefault();
+        m.entry(String::from("d")).andModify(|v| *v *= 10).orInsertWith(|| 7);
+        assert!(m["a"] == 20 && m["d"] == 7);
+
+        match m.entry(String::from("b")) {
+            Entry::Occupied(mut e) => {
+                assert!(*e.key() == "b" && *e.get() == 3 && e.insert(4) == 3);
+                assert!(e.remove() == 4);
+            }
+            Entry::Vacant(_) => panic!("b should be in the map"),
+        }
+        match m.entry(String::from("e")) {
+            Entry::Vacant(e) => assert!(e.key() == &"e"),
+            Entry::Occupied(_) => panic!("e shouldn't be in the map"),
+        }
+        assert!(m.len() == 3 && m.get("b").is_none() && m.getKeyValue("d").unwrap().0 == &"d");
+    }
+
+    #[test]
+    fn testDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let mut m = BTreeMap::new();
+            for i in 0..300 {
+                let mut s = String::new();
+                fmt::Write::write_fmt(&mut s, format_args!("{:04}", i)).unwrap();
+                m.insert(s, Vec::<i32>::new());
+            }
+            for i in (0..300).step_by(7) {
+                let mut s = String::new();
+                fmt::Write::write_fmt(&mut s, format_args!("{:04}", i)).unwrap();
+                assert!(m.remove(s.toStr()).is_some());
+            }
+            check(&m);
+            m.retain(|k, _| k.asArray()[3] != b'1');
+            check(&m);
+            assert!(m.keys().all(|k| k.asArray()[3] != b'1'));
+
+            let c = m.clone();
+            let mut it = m.into_iter();
+            assert!(it.next().unwrap().0 == "0002" && it.next_back().unwrap().0 == "0299");
+            drop(it);
+            assert!(c.len() > 200);
+        });
+    }
+
+    #[test]
+    fn testRetain() {
+        let mut rng = Lcg(5);
+        let mut m : BTreeMap<u32, u32> = (0..2000).map(|k| (k, k)).collect();
+        let mut seen = 0;
+        m.retain(|k, v| { assert!(*k == seen); seen += 1; *v += 1; rng.next() & 3 == 0 });
+        check(&m);
+        assert!(seen == 2000 && m.len() > 300 && m.len() < 700);
+        assert!(m.iter().all(|(k, v)| *v == k + 1));
+        m.retain(|_, _| false);
+        assert!(m.isEmpty() && m.root.is_null());
+    }
+
+    #[test]
+    fn testRetainPanic() {
+        extern crate std;
+        crate::mem::assertNoLeaks(|| {
+            let mut m : BTreeMap<i32, Vec<i32>> = (0..100).map(|k| (k, Vec::new())).collect();
+            let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                m.retain(|k, _| { if *k == 50 { panic!("retain") } k % 2 == 0 });
+            }));
+            assert!(r.is_err());
+            // the odd keys before the panic are removed, the rest is untouched
+            check(&m);
+            assert!(m.len() == 75 && m.containsKey(&50) && m.containsKey(&51) && !m.containsKey(&49));
+        });
+    }
+
+    #[test]
+    fn testSet() {
+        let mut s : BTreeSet<i32> = [5, 1, 9, 3].iter().cloned().collect();
+        assert!(s.insert(7) && !s.insert(7) && s.len() == 5);
+        assert!(s.contains(&9) && !s.contains(&2) && s.get(&3) == Some(&3));
+        assert!(s.first() == Some(&1) && s.last() == Some(&9));
+        assert!(s.iter().cloned().collect::<Vec<i32>>().asArray() == [1, 3, 5, 7, 9]);
+        assert!(s.range(2..8).cloned().collect::<Vec<i32>>().asArray() == [3, 5, 7]);
+        assert!(s.remove(&5) && !s.remove(&5) && s.take(&7) == Some(7));
+
+        let t : BTreeSet<i32> = (0..10).collect();
+        assert!(s.isSubset(&t) && !t.isSubset(&s) && !s.isDisjoint(&t));
+        s.retain(|k| *k > 1);
+        assert!(s.popFirst() == Some(3) && s.popLast() == Some(9) && s.isEmpty());
+        assert!(t.into_iter().rev().take(2).sum::<i32>() == 17);
+    }
+}
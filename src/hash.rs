@@ -1,13 +1,216 @@
+/// Streaming hash state, fed by `Hash::hash`.
+pub trait Hasher {
+    fn writeBytes(&mut self, bytes: &[u8]);
+    fn writeU8(&mut self, v: u8) { self.writeBytes(&[v]) }
+    fn writeU64(&mut self, v: u64) { self.writeBytes(&v.to_le_bytes()) }
+    fn finish(&self) -> u64;
+}
+
+/// Builds a fresh `Hasher` for every key hashed by a `HashMap`.
+pub trait BuildHasher {
+    type Hasher: Hasher;
+    fn buildHasher(&self) -> Self::Hasher;
+
+    fn hashOne<T: ?Sized + Hash>(&self, v: &T) -> u64 {
+        let mut h = self.buildHasher();
+        v.hash(&mut h);
+        h.finish()
+    }
+}
+
 pub trait Hash {
-    fn hash(&self) -> usize;
+    fn hash<H: Hasher>(&self, state: &mut H);
+
+    /// Feed all of `data`, types with a byte representation can do it in one write.
+    fn hashSlice<H: Hasher>(data: &[Self], state: &mut H) where Self: Sized {
+        for d in data {
+            d.hash(state);
+        }
+    }
+}
+
+impl Hash for u8 {
+    fn hash<H: Hasher>(&self, state: &mut H) { state.writeU8(*self) }
+    fn hashSlice<H: Hasher>(data: &[u8], state: &mut H) { state.writeBytes(data) }
+}
+
+impl Hash for i8 {
+    fn hash<H: Hasher>(&self, state: &mut H) { state.writeU8(*self as u8) }
 }
 
-impl Hash for &[u8] {
-    fn hash(&self) -> usize {
-        murmurHash64A(self, 0xcae4f57) as usize
+macro_rules! hashAsU64 {
+    ($($t:ty)*) => {$(
+        impl Hash for $t {
+            fn hash<H: Hasher>(&self, state: &mut H) { state.writeU64(*self as u64) }
+        }
+    )*}
+}
+
+hashAsU64! { u16 u32 u64 usize i16 i32 i64 isize char }
+
+impl Hash for u128 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.writeU64(*self as u64);
+        state.writeU64((*self >> 64) as u64);
+    }
+}
+
+impl Hash for i128 {
+    fn hash<H: Hasher>(&self, state: &mut H) { (*self as u128).hash(state) }
+}
+
+impl Hash for bool {
+    fn hash<H: Hasher>(&self, state: &mut H) { state.writeU8(*self as u8) }
+}
+
+// the length goes first so that `([1], [2, 3])` and `([1, 2], [3])` hash differently
+impl<T: Hash> Hash for [T] {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.writeU64(self.len() as u64);
+        T::hashSlice(self, state)
     }
 }
 
+impl<T: Hash, const N: usize> Hash for [T; N] {
+    fn hash<H: Hasher>(&self, state: &mut H) { self[..].hash(state) }
+}
+
+// `String` hashes the same, so `String` keys can be looked up by `&str`
+impl Hash for str {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.writeBytes(self.as_bytes());
+        state.writeU8(0xff);
+    }
+}
+
+impl<T: ?Sized + Hash> Hash for &T {
+    fn hash<H: Hasher>(&self, state: &mut H) { (**self).hash(state) }
+}
+
+impl<T: Hash> Hash for Option<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            None => state.writeU8(0),
+            Some(v) => {
+                state.writeU8(1);
+                v.hash(state);
+            }
+        }
+    }
+}
+
+impl Hash for () {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+macro_rules! hashTuple {
+    ($($t:ident $i:tt),+) => {
+        impl<$($t: Hash),+> Hash for ($($t,)+) {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                $(self.$i.hash(state);)+
+            }
+        }
+    }
+}
+
+hashTuple! { T0 0 }
+hashTuple! { T0 0, T1 1 }
+hashTuple! { T0 0, T1 1, T2 2 }
+hashTuple! { T0 0, T1 1, T2 2, T3 3 }
+hashTuple! { T0 0, T1 1, T2 2, T3 3, T4 4 }
+hashTuple! { T0 0, T1 1, T2 2, T3 3, T4 4, T5 5 }
+
+const MURMUR_M: u64 = 0xc6a4a7935bd1e995;
+const MURMUR_R: u32 = 47;
+
+#[inline]
+fn murmurMix(h: u64, k: u64) -> u64 {
+    let mut k = k.wrapping_mul(MURMUR_M);
+    k ^= k >> MURMUR_R;
+    k = k.wrapping_mul(MURMUR_M);
+    (h ^ k).wrapping_mul(MURMUR_M)
+}
+
+/// Streaming version of `murmurHash64A`: the words are mixed as they come and the length at the end.
+pub struct MurmurHasher {
+    h       : u64,
+    tail    : u64,
+    tailLen : usize,
+    len     : u64,
+}
+
+impl MurmurHasher {
+    pub fn new(seed: u64) -> Self { Self { h: seed, tail: 0, tailLen: 0, len: 0 } }
+}
+
+impl Hasher for MurmurHasher {
+    fn writeBytes(&mut self, bytes: &[u8]) {
+        self.len = self.len.wrapping_add(bytes.len() as u64);
+        let mut bytes = bytes;
+
+        // top up the pending word first
+        while self.tailLen != 0 && !bytes.is_empty() {
+            self.tail |= (bytes[0] as u64) << (8 * self.tailLen);
+            self.tailLen += 1;
+            bytes = &bytes[1..];
+            if self.tailLen == 8 {
+                self.h = murmurMix(self.h, self.tail);
+                self.tail = 0;
+                self.tailLen = 0;
+            }
+        }
+
+        let mut words = bytes.chunks_exact(8);
+        for w in &mut words {
+            self.h = murmurMix(self.h, u64::from_le_bytes([w[0], w[1], w[2], w[3], w[4], w[5], w[6], w[7]]));
+        }
+        for b in words.remainder() {
+            self.tail |= (*b as u64) << (8 * self.tailLen);
+            self.tailLen += 1;
+        }
+    }
+
+    fn writeU64(&mut self, v: u64) {
+        if self.tailLen == 0 {
+            self.len = self.len.wrapping_add(8);
+            self.h = murmurMix(self.h, v);
+        } else {
+            self.writeBytes(&v.to_le_bytes())
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        let mut h = self.h;
+        if self.tailLen != 0 {
+            h = (h ^ self.tail).wrapping_mul(MURMUR_M);
+        }
+        h ^= self.len.wrapping_mul(MURMUR_M);
+        h ^= h >> MURMUR_R;
+        h = h.wrapping_mul(MURMUR_M);
+        h ^= h >> MURMUR_R;
+        h
+    }
+}
+
+/// The default `BuildHasher` of `HashMap`, a `MurmurHasher` with a fixed seed.
+#[derive(Clone, Copy)]
+pub struct BuildMurmurHasher {
+    seed    : u64,
+}
+
+impl BuildMurmurHasher {
+    pub fn withSeed(seed: u64) -> Self { Self { seed } }
+}
+
+impl Default for BuildMurmurHasher {
+    fn default() -> Self { Self::withSeed(0xcae4f57) }
+}
+
+impl BuildHasher for BuildMurmurHasher {
+    type Hasher = MurmurHasher;
+    fn buildHasher(&self) -> MurmurHasher { MurmurHasher::new(self.seed) }
+}
+
 // from: https://github.com/antirez/redis/blob/unstable/src/hyperloglog.c
 // Copyright 2014 (c) Salvatore Sanfilippo <antirez at gmail dot com> - 3-Clause BSD license
 /* Our hash function is MurmurHash2, 64 bit version.
@@ -23,7 +226,7 @@ pub fn murmurHash64A (key: &[u8], seed: u64) -> u64 {
     let end = key.len() - len;
 
     while i < end {
-        let mut k = key[i + 0] as u64;
+        let mut k = key[i] as u64;
         k |= (key[i + 1] as u64) << 8;
         k |= (key[i + 2] as u64) << 16;
         k |= (key[i + 3] as u64) << 24;
@@ -55,3 +258,115 @@ pub fn murmurHash64A (key: &[u8], seed: u64) -> u64 {
     h ^= h >> r;
     h
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec::*;
+    use crate::string::*;
+    use crate::mem::*;
+    use crate::hashmap::*;
+
+    fn hashOf<T: ?Sized + Hash>(v: &T) -> u64 { BuildMurmurHasher::default().hashOne(v) }
+
+    #[test]
+    fn testStreaming() {
+        let bytes : [u8; 37] = core::array::from_fn(|i| (i * 7) as u8);
+        let whole = {
+            let mut h = MurmurHasher::new(1);
+            h.writeBytes(&bytes);
+            h.finish()
+        };
+        // the result doesn't depend on how the bytes are split
+        for split in 0..bytes.len() {
+            let mut h = MurmurHasher::new(1);
+            h.writeBytes(&bytes[..split]);
+            h.writeBytes(&bytes[split..]);
+            assert!(h.finish() == whole);
+        }
+        let mut h = MurmurHasher::new(1);
+        for b in bytes.iter() { h.writeU8(*b) }
+        assert!(h.finish() == whole);
+
+        let mut h = MurmurHasher::new(2);
+        h.writeBytes(&bytes);
+        assert!(h.finish() != whole);
+    }
+
+    #[test]
+    fn testBorrowedForms() {
+        assert!(hashOf(&String::from("hello")) == hashOf("hello"));
+        let mut v = Vec::new();
+        v.extendFromSlice(b"hello");
+        assert!(hashOf(&v) == hashOf(&b"hello"[..]) && hashOf(&v) == hashOf(b"hello"));
+        assert!(hashOf(&v) != hashOf("hello"));
+        assert!(hashOf(&Box::new(5u32)) == hashOf(&5u32));
+        assert!(hashOf(&Some(0u8)) != hashOf(&None::<u8>));
+
+        // nested slices don't run into each other
+        assert!(hashOf(&(&[1u8][..], &[2u8, 3][..])) != hashOf(&(&[1u8, 2][..], &[3u8][..])));
+        assert!(hashOf(&("ab", "c")) != hashOf(&("a", "bc")));
+    }
+
+    #[test]
+    fn testIntegerSpread() {
+        // identity hashing would put all of these in the same bucket of a 1024 slot table
+        let mut buckets = Vec::new();
+        for _ in 0..1024 { buckets.pushBack(false) }
+        for i in 0..1024i32 {
+            buckets[(hashOf(&(i * 1024)) & 1023) as usize] = true;
+        }
+        assert!(buckets.iter().filter(|b| **b).count() > 512);
+    }
+
+    #[test]
+    fn testCompositeKeys() {
+        crate::mem::assertNoLeaks(|| {
+            let mut hm = HashMap::<(u32, String), usize>::new();
+            for i in 0..200u32 {
+                hm.set((i % 10, crate::format!("{}", i / 10)), i as usize);
+            }
+            assert!(hm.count() == 200);
+            assert!(hm.get(&(3, String::from("7"))) == Some(&73));
+            assert!(hm.get(&(3, String::from("70"))).is_none());
+
+            let mut hm = HashMap::<[u8; 4], Option<char>>::new();
+            hm.set(*b"abcd", Some('x'));
+            hm.set(*b"abce", None);
+            assert!(hm.get(b"abcd") == Some(&Some('x')) && hm.get(b"abce") == Some(&None));
+        });
+    }
+
+    // FNV-1a, to check a map works with another hasher
+    struct Fnv(u64);
+
+    impl Hasher for Fnv {
+        fn writeBytes(&mut self, bytes: &[u8]) {
+            for b in bytes {
+                self.0 = (self.0 ^ *b as u64).wrapping_mul(0x100000001b3);
+            }
+        }
+        fn finish(&self) -> u64 { self.0 }
+    }
+
+    #[derive(Clone, Default)]
+    struct BuildFnv;
+
+    impl BuildHasher for BuildFnv {
+        type Hasher = Fnv;
+        fn buildHasher(&self) -> Fnv { Fnv(0xcbf29ce484222325) }
+    }
+
+    #[test]
+    fn testBuildHasher() {
+        let mut hm = HashMap::withHasher(BuildFnv);
+        for i in 0..500u64 { hm.set(i, i * 3) }
+        for i in 0..500u64 { assert!(hm.get(&i) == Some(&(i * 3))) }
+        let c = hm.clone();
+        assert!(c.count() == 500 && c.get(&499) == Some(&1497));
+
+        let s : HashSet<&str, LibcAllocator, BuildFnv> = ["a", "b", "a"].iter().copied().collect();
+        let t = HashSet::withHasher(BuildFnv);
+        assert!(s.len() == 2 && s.contains("a") && s.isDisjoint(&t));
+    }
+}
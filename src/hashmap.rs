@@ -29,40 +29,65 @@
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 //
 use core::*;
+use core::borrow::Borrow;
 use crate::mem::*;
 use crate::hash::*;
 
 
-struct KeyValue<K : Hash + PartialEq, V> {
+struct KeyValue<K, V> {
     hash    : usize,
     key     : K,
     value   : V,
 }
 
-impl<K: Hash + PartialEq, V> KeyValue<K, V> {
+impl<K, V> KeyValue<K, V> {
     pub fn isEmpty(&self) -> bool { self.hash == 0 }
 }
 
-pub struct HashMap<K: Hash + PartialEq, V> {
+pub struct HashMap<K: Hash + PartialEq, V, A: Allocator = LibcAllocator, S: BuildHasher = BuildMurmurHasher> {
     table   : Unique<KeyValue<K, V>>,
     capacity: usize,
     count   : usize,
+    alloc   : A,
+    hasher  : S,
 }
 
 impl<K: Hash + PartialEq, V> HashMap<K, V> {
-    pub fn new() -> Self {
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+}
+
+impl<K: Hash + PartialEq, V, A: Allocator + Default, S: BuildHasher + Default> Default for HashMap<K, V, A, S> {
+    fn default() -> Self { Self::withHasherIn(A::default(), S::default()) }
+}
+
+impl<K: Hash + PartialEq, V, S: BuildHasher> HashMap<K, V, LibcAllocator, S> {
+    pub fn withHasher(hasher: S) -> Self { Self::withHasherIn(LibcAllocator, hasher) }
+}
+
+impl<K: Hash + PartialEq, V, A: Allocator> HashMap<K, V, A> {
+    pub fn newIn(alloc: A) -> Self { Self::withHasherIn(alloc, BuildMurmurHasher::default()) }
+}
+
+impl<K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> HashMap<K, V, A, S> {
+    pub fn withHasherIn(alloc: A, hasher: S) -> Self {
         Self {
             table   : Unique::new(ptr::null_mut()),
             count   : 0,
             capacity: 0,
+            alloc,
+            hasher,
         }
     }
 
     pub fn count(&self) -> usize { self.count }
 
+    pub fn allocator(&self) -> &A { &self.alloc }
+    pub fn hasher(&self) -> &S { &self.hasher }
+
+    // owned keys and their borrowed form must hash the same (`String` and `str`, `Vec<u8>` and `[u8]`)
     #[inline]
-    fn hash(k: &K) -> usize {
-        match k.hash() {
+    fn hash<Q: ?Sized + Hash>(&self, k: &Q) -> usize {
+        match self.hasher.hashOne(k) as usize {
             0 => 1,
             h => h,
         }
@@ -75,86 +100,120 @@ impl<K: Hash + PartialEq, V> HashMap<K, V> {
         ret
     }
 
-    fn uncheckedSet(&mut self, k: K, v: V) {
-        let hash    = Self::hash(&k);
+    // index of the slot holding `k`, or of the empty slot it goes in (the table can't be full)
+    fn probe(&self, hash: usize, k: &K) -> Result<usize, usize> {
         let mut index   = (hash & (self.capacity - 1)) as isize;
-        let entries = unsafe { core::slice::from_raw_parts_mut(self.table.getMutPtr(), self.capacity) };
+        let entries = unsafe { core::slice::from_raw_parts(self.table.getPtr(), self.capacity) };
 
         for _ in 0..self.capacity {
-            let mut e = &mut entries[index as usize];
+            let e = &entries[index as usize];
             if e.isEmpty() {
-                e.key   = k;
-                e.value = v;
-                e.hash  = hash;
-                self.count += 1;
-                return;
+                return Err(index as usize);
             }
 
-            if hash == e.hash && k == e.key {
-                e.value = v;
-                return;
+            if hash == e.hash && *k == e.key {
+                return Ok(index as usize);
             }
 
             index = self.next(index);
         }
-        panic!("uncheckedSet shouldn't reach this point");
+        panic!("probe shouldn't reach this point");
     }
 
-    fn newWithCap(cap: usize) -> Self {
-        Self {
-            table   : Unique::new(unsafe { allocRaw(cap * ::core::mem::size_of::<KeyValue<K, V>>()) as *mut KeyValue<K, V> }),
-            count   : 0,
-            capacity: cap,
+    // write a new entry in the empty slot at `index`
+    fn fill(&mut self, index: usize, hash: usize, k: K, v: V) -> &mut V {
+        let e = unsafe { &mut *self.table.getMutPtr().add(index) };
+        unsafe {
+            ptr::write(&mut e.key, k);
+            ptr::write(&mut e.value, v);
         }
+        e.hash  = hash;
+        self.count += 1;
+        &mut e.value
     }
 
-    fn grow(&mut self, newCap: usize) {
-        let oldEntries  = unsafe { core::slice::from_raw_parts(self.table.getPtr(), self.capacity) };
-        let mut newHM   = HashMap::<K, V>::newWithCap(newCap);
-        for o in oldEntries {
-            if !o.isEmpty() {
-                unsafe {
-                newHM.uncheckedSet(::core::ptr::read_unaligned(&o.key),
-                                   ::core::ptr::read_unaligned(&o.value));
+    fn uncheckedSet(&mut self, k: K, v: V) {
+        let hash    = self.hash(&k);
+        match self.probe(hash, &k) {
+            Ok(index) => unsafe { (*self.table.getMutPtr().add(index)).value = v },
+            Err(index) => { self.fill(index, hash, k, v); }
+        }
+    }
+
+    fn tableSize(cap: usize) -> usize { cap * ::core::mem::size_of::<KeyValue<K, V>>() }
+
+    fn tryGrow(&mut self, newCap: usize) -> Result<(), AllocError> {
+        let oldTable    = self.table.getMutPtr();
+        let oldCap      = self.capacity;
+
+        let align       = ::core::mem::align_of::<KeyValue<K, V>>();
+        let size        = newCap.checked_mul(::core::mem::size_of::<KeyValue<K, V>>()).ok_or(AllocError { size: usize::MAX, align })?;
+        let newTable    = unsafe { self.alloc.alloc(size, align) };
+        if newTable.is_null() { return Err(AllocError { size, align }) }
+
+        // the empty slot marker is hash == 0, so the table has to start zeroed
+        unsafe { ptr::write_bytes(newTable, 0, size) };
+
+        self.table      = Unique::new(newTable as *mut KeyValue<K, V>);
+        self.capacity   = newCap;
+        self.count      = 0;
+
+        if oldCap > 0 {
+            let oldEntries  = unsafe { core::slice::from_raw_parts(oldTable, oldCap) };
+            for o in oldEntries {
+                if !o.isEmpty() {
+                    unsafe {
+                    self.uncheckedSet(::core::ptr::read(&o.key),
+                                      ::core::ptr::read(&o.value));
+                    }
                 }
             }
+            unsafe { self.alloc.free(oldTable as *mut u8, Self::tableSize(oldCap), align) };
         }
-
-        self.count  = 0;
-        self.capacity = 0;
-        unsafe { free(self.table.getMutPtr()) };
-        *self = newHM;
+        Ok(())
     }
 
     pub fn set(&mut self, k: K, v: V) {
-        if 4 * self.count >= 3 * self.capacity {
-            self.grow(if self.capacity == 0 { 4 } else { self.capacity * 2 });
-        }
-        self.uncheckedSet(k, v)
+        if let Err(e) = self.trySet(k, v) { handleAllocError(e) }
     }
 
-    pub fn exist(&self, k: K) -> bool {
-        let hash = Self::hash(&k);
-        let mut index   = (hash & (self.capacity - 1)) as isize;
-        let entries = unsafe { core::slice::from_raw_parts(self.table.getPtr(), self.capacity) };
-
-        for _ in 0..self.capacity {
-            let e = &entries[index as usize];
-            if e.isEmpty() {
-                return false;
-            }
+    /// Same as `set`, but returns an error instead of calling the out of memory handler
+    /// (`k` and `v` are dropped in that case).
+    pub fn trySet(&mut self, k: K, v: V) -> Result<(), AllocError> {
+        self.tryReserveSlot()?;
+        self.uncheckedSet(k, v);
+        Ok(())
+    }
 
-            if hash == e.hash && k == e.key {
-                return true;
-            }
+    // grow if one more entry would go past the 3/4 load factor
+    fn tryReserveSlot(&mut self) -> Result<(), AllocError> {
+        if 4 * self.count >= 3 * self.capacity {
+            self.tryGrow(if self.capacity == 0 { 4 } else { self.capacity * 2 })?;
+        }
+        Ok(())
+    }
 
-            index = self.next(index);
+    /// The entry for `k`, to read, update or fill it with a single lookup.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn entry(&mut self, k: K) -> Entry<'_, K, V, A, S> {
+        let hash = self.hash(&k);
+        let mut slot = if self.capacity == 0 { Err(0) } else { self.probe(hash, &k) };
+        if slot.is_err() {
+            // only a vacant entry needs room, and growing moves its slot
+            let capacity = self.capacity;
+            if let Err(e) = self.tryReserveSlot() { handleAllocError(e) }
+            if self.capacity != capacity { slot = self.probe(hash, &k) }
+        }
+        match slot {
+            Ok(index) => Entry::Occupied(OccupiedEntry { map: self, index }),
+            Err(index) => Entry::Vacant(VacantEntry { map: self, key: k, hash, index }),
         }
-        false
     }
 
-    pub fn get(&self, k: K) -> Option<&V> {
-        let hash = Self::hash(&k);
+    fn find<Q: ?Sized + Hash + PartialEq>(&self, k: &Q) -> Option<usize> where K: Borrow<Q> {
+        if self.capacity == 0 { return None }
+
+        let hash = self.hash(k);
         let mut index   = (hash & (self.capacity - 1)) as isize;
         let entries = unsafe { core::slice::from_raw_parts(self.table.getPtr(), self.capacity) };
 
@@ -164,8 +223,8 @@ impl<K: Hash + PartialEq, V> HashMap<K, V> {
                 return None;
             }
 
-            if hash == e.hash && k == e.key {
-                return Some(&e.value);
+            if hash == e.hash && *k == *e.key.borrow() {
+                return Some(index as usize);
             }
 
             index = self.next(index);
@@ -173,24 +232,44 @@ impl<K: Hash + PartialEq, V> HashMap<K, V> {
         None
     }
 
-    pub fn remove(&mut self, k: K) {
-        let hash = Self::hash(&k);
-        let mut index   = (hash & (self.capacity - 1)) as isize;
+    pub fn exist<Q: ?Sized + Hash + PartialEq>(&self, k: &Q) -> bool where K: Borrow<Q> {
+        self.find(k).is_some()
+    }
+
+    pub fn get<Q: ?Sized + Hash + PartialEq>(&self, k: &Q) -> Option<&V> where K: Borrow<Q> {
+        self.getKeyValue(k).map(|(_, v)| v)
+    }
+
+    pub fn getMut<Q: ?Sized + Hash + PartialEq>(&mut self, k: &Q) -> Option<&mut V> where K: Borrow<Q> {
+        let index = self.find(k)?;
         let entries = unsafe { core::slice::from_raw_parts_mut(self.table.getMutPtr(), self.capacity) };
+        Some(&mut entries[index].value)
+    }
 
-        for _ in 0..self.capacity {
-            let e = &entries[index as usize];
-            if e.isEmpty() {
-                return;
-            }
+    pub fn getKeyValue<Q: ?Sized + Hash + PartialEq>(&self, k: &Q) -> Option<(&K, &V)> where K: Borrow<Q> {
+        let index = self.find(k)?;
+        let entries = unsafe { core::slice::from_raw_parts(self.table.getPtr(), self.capacity) };
+        Some((&entries[index].key, &entries[index].value))
+    }
 
-            if hash == e.hash && k == e.key {
-                self.count -= 1;
-                break;
-            }
+    /// Remove the entry for `k`, returning its value.
+    pub fn remove<Q: ?Sized + Hash + PartialEq>(&mut self, k: &Q) -> Option<V> where K: Borrow<Q> {
+        self.removeEntry(k).map(|(_, v)| v)
+    }
 
-            index = self.next(index);
-        }
+    /// Remove the entry for `k`, returning the stored key and value.
+    pub fn removeEntry<Q: ?Sized + Hash + PartialEq>(&mut self, k: &Q) -> Option<(K, V)> where K: Borrow<Q> {
+        let index = self.find(k)?;
+        Some(self.removeAt(index))
+    }
+
+    // take the entry at `index` out and shift the following entries of its probe run back
+    fn removeAt(&mut self, index: usize) -> (K, V) {
+        let mut index = index as isize;
+        let entries = unsafe { core::slice::from_raw_parts_mut(self.table.getMutPtr(), self.capacity) };
+        let removed = unsafe { ::core::ptr::read(&entries[index as usize]) };
+        entries[index as usize].hash = 0;
+        self.count -= 1;
 
         loop {
             let emptyIndex = index;
@@ -199,27 +278,85 @@ impl<K: Hash + PartialEq, V> HashMap<K, V> {
                 index = self.next(index);
                 let s = &entries[index as usize];
                 if s.isEmpty() {
-                    entries[emptyIndex as usize].hash = 0;
-                    unsafe { ::core::ptr::read_unaligned(&entries[emptyIndex as usize]) };  // drop it!
-                    return;
+                    return (removed.key, removed.value);
                 }
 
                 originalIndex   = (s.hash & (self.capacity - 1)) as isize;
 
-                if ! ((index <= originalIndex && originalIndex < emptyIndex)
-                    || (originalIndex < emptyIndex && emptyIndex < index)
-                    || (emptyIndex < index && index <= originalIndex)) {
+                let stays = (originalIndex < emptyIndex && (index <= originalIndex || emptyIndex < index))
+                    || (emptyIndex < index && index <= originalIndex);
+                if !stays {
                     break;
                 }
             }
 
-            entries[emptyIndex as usize] = unsafe { ::core::ptr::read_unaligned(&entries[index as usize]) };
+            unsafe { ::core::ptr::copy_nonoverlapping(&entries[index as usize], &mut entries[emptyIndex as usize], 1) };
             entries[index as usize].hash = 0;
         }
     }
+
+    fn entries(&self) -> &[KeyValue<K, V>] {
+        if self.capacity == 0 { return &[] }
+        unsafe { core::slice::from_raw_parts(self.table.getPtr(), self.capacity) }
+    }
+
+    fn entriesMut(&mut self) -> &mut [KeyValue<K, V>] {
+        if self.capacity == 0 { return &mut [] }
+        unsafe { core::slice::from_raw_parts_mut(self.table.getMutPtr(), self.capacity) }
+    }
+
+    /// Entries in table order, which is unspecified.
+    pub fn iter(&self) -> Iter<'_, K, V> { Iter { entries: self.entries().iter(), remaining: self.count } }
+    pub fn iterMut(&mut self) -> IterMut<'_, K, V> {
+        let remaining = self.count;
+        IterMut { entries: self.entriesMut().iter_mut(), remaining }
+    }
+
+    pub fn keys(&self) -> Keys<'_, K, V> { Keys { iter: self.iter() } }
+    pub fn values(&self) -> Values<'_, K, V> { Values { iter: self.iter() } }
+    pub fn valuesMut(&mut self) -> ValuesMut<'_, K, V> { ValuesMut { iter: self.iterMut() } }
+
+    /// Move every entry out, the map keeps its capacity. Entries not consumed are dropped with the iterator.
+    pub fn drain(&mut self) -> MapDrain<'_, K, V, A, S> {
+        // detach the table up front, so the map stays empty and consistent even if the iterator is leaked
+        let table       = mem::replace(&mut self.table, Unique::new(ptr::null_mut()));
+        let capacity    = mem::replace(&mut self.capacity, 0);
+        let remaining   = mem::replace(&mut self.count, 0);
+        MapDrain { map: self, table, capacity, remaining, index: 0 }
+    }
+
+    /// Drop every entry, keeping the capacity.
+    pub fn clear(&mut self) { self.drain().for_each(drop) }
+
+    /// Keep only the entries for which `f` returns true.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        if self.count == 0 { return }
+
+        // removing shifts entries from further down their probe run back into the hole, so walk in probe
+        // order starting after an empty slot: whatever moves into the current slot hasn't been visited yet
+        let start = self.entries().iter().position(|e| e.isEmpty()).unwrap();
+        let mut index = start as isize;
+        for _ in 0..self.capacity {
+            index = self.next(index);
+            loop {
+                let e = &mut self.entriesMut()[index as usize];
+                if e.isEmpty() || f(&e.key, &mut e.value) { break }
+                self.removeAt(index as usize);
+            }
+        }
+    }
+
+    // move the entry at `index` out without fixing the probe runs, only valid when all of them go (`MapIntoIter`)
+    fn take(&mut self, index: usize) -> (K, V) {
+        let e = &mut self.entriesMut()[index];
+        e.hash = 0;
+        let kv = unsafe { (ptr::read(&e.key), ptr::read(&e.value)) };
+        self.count -= 1;
+        kv
+    }
 }
 
-impl<K : Hash + PartialEq, V> Drop for HashMap<K, V> {
+impl<K : Hash + PartialEq, V, A: Allocator, S: BuildHasher> Drop for HashMap<K, V, A, S> {
     fn drop(&mut self) {
             if self.capacity > 0 {
             let arr      = unsafe { core::slice::from_raw_parts_mut(self.table.getMutPtr(), self.capacity) };
@@ -229,21 +366,442 @@ impl<K : Hash + PartialEq, V> Drop for HashMap<K, V> {
                     unsafe { ptr::drop_in_place(&kv.value as *const V as *mut V) };
                 }
             }
-            unsafe { free(self.table.getMutPtr()) }
+            unsafe { self.alloc.free(self.table.getMutPtr() as *mut u8, Self::tableSize(self.capacity), ::core::mem::align_of::<KeyValue<K, V>>()) }
+        }
+    }
+}
+
+impl<K: Hash + PartialEq + Clone, V: Clone, A: Allocator + Clone, S: BuildHasher + Clone> Clone for HashMap<K, V, A, S> {
+    fn clone(&self) -> Self {
+        let mut c = Self::withHasherIn(self.alloc.clone(), self.hasher.clone());
+        if self.capacity > 0 {
+            if let Err(e) = c.tryGrow(self.capacity) { handleAllocError(e) }
+            let entries = unsafe { core::slice::from_raw_parts(self.table.getPtr(), self.capacity) };
+            for e in entries {
+                if !e.isEmpty() {
+                    c.uncheckedSet(e.key.clone(), e.value.clone());
+                }
+            }
+        }
+        c
+    }
+}
+
+/// Iterator over the entries of a `HashMap`.
+pub struct Iter<'a, K, V> {
+    entries     : slice::Iter<'a, KeyValue<K, V>>,
+    remaining   : usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    fn next(&mut self) -> Option<(&'a K, &'a V)> {
+        let e = self.entries.find(|e| !e.isEmpty())?;
+        self.remaining -= 1;
+        Some((&e.key, &e.value))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.remaining, Some(self.remaining)) }
+}
+
+impl<'a, K, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+/// Iterator over the entries of a `HashMap`, with mutable values.
+pub struct IterMut<'a, K, V> {
+    entries     : slice::IterMut<'a, KeyValue<K, V>>,
+    remaining   : usize,
+}
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    fn next(&mut self) -> Option<(&'a K, &'a mut V)> {
+        let e = self.entries.find(|e| !e.isEmpty())?;
+        self.remaining -= 1;
+        Some((&e.key, &mut e.value))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.remaining, Some(self.remaining)) }
+}
+
+impl<'a, K, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+pub struct Keys<'a, K, V> {
+    iter    : Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> { self.iter.next().map(|(k, _)| k) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+pub struct Values<'a, K, V> {
+    iter    : Iter<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+    fn next(&mut self) -> Option<&'a V> { self.iter.next().map(|(_, v)| v) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+pub struct ValuesMut<'a, K, V> {
+    iter    : IterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+    fn next(&mut self) -> Option<&'a mut V> { self.iter.next().map(|(_, v)| v) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+/// Draining iterator for `HashMap`, see `HashMap::drain`.
+pub struct MapDrain<'a, K: Hash + PartialEq, V, A: Allocator = LibcAllocator, S: BuildHasher = BuildMurmurHasher> {
+    map         : &'a mut HashMap<K, V, A, S>,
+    table       : Unique<KeyValue<K, V>>,
+    capacity    : usize,
+    remaining   : usize,
+    index       : usize,
+}
+
+impl<'a, K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> Iterator for MapDrain<'a, K, V, A, S> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<(K, V)> {
+        while self.index < self.capacity {
+            let e = unsafe { &mut *self.table.getMutPtr().add(self.index) };
+            self.index += 1;
+            if !e.isEmpty() {
+                e.hash = 0;
+                self.remaining -= 1;
+                return Some(unsafe { (ptr::read(&e.key), ptr::read(&e.value)) })
+            }
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.remaining, Some(self.remaining)) }
+}
+
+impl<'a, K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> ExactSizeIterator for MapDrain<'a, K, V, A, S> {}
+
+impl<'a, K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> Drop for MapDrain<'a, K, V, A, S> {
+    // drop what is left, then give the now empty table back to the map
+    fn drop(&mut self) {
+        self.for_each(drop);
+        self.map.table      = mem::replace(&mut self.table, Unique::new(ptr::null_mut()));
+        self.map.capacity   = self.capacity;
+    }
+}
+
+/// Owning iterator for `HashMap`, the entries not consumed are dropped with it.
+pub struct MapIntoIter<K: Hash + PartialEq, V, A: Allocator = LibcAllocator, S: BuildHasher = BuildMurmurHasher> {
+    map     : HashMap<K, V, A, S>,
+    index   : usize,
+}
+
+impl<K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> Iterator for MapIntoIter<K, V, A, S> {
+    type Item = (K, V);
+    fn next(&mut self) -> Option<(K, V)> {
+        while self.index < self.map.capacity {
+            let index = self.index;
+            self.index += 1;
+            if !self.map.entries()[index].isEmpty() { return Some(self.map.take(index)) }
+        }
+        None
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.map.count, Some(self.map.count)) }
+}
+
+impl<K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> ExactSizeIterator for MapIntoIter<K, V, A, S> {}
+
+impl<K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> IntoIterator for HashMap<K, V, A, S> {
+    type Item = (K, V);
+    type IntoIter = MapIntoIter<K, V, A, S>;
+    fn into_iter(self) -> MapIntoIter<K, V, A, S> { MapIntoIter { map: self, index: 0 } }
+}
+
+impl<'a, K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> IntoIterator for &'a HashMap<K, V, A, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+    fn into_iter(self) -> Iter<'a, K, V> { self.iter() }
+}
+
+impl<'a, K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> IntoIterator for &'a mut HashMap<K, V, A, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+    fn into_iter(self) -> IterMut<'a, K, V> { self.iterMut() }
+}
+
+/// A `HashMap` entry, see `HashMap::entry`.
+pub enum Entry<'a, K: Hash + PartialEq, V, A: Allocator = LibcAllocator, S: BuildHasher = BuildMurmurHasher> {
+    Occupied(OccupiedEntry<'a, K, V, A, S>),
+    Vacant(VacantEntry<'a, K, V, A, S>),
+}
+
+pub struct OccupiedEntry<'a, K: Hash + PartialEq, V, A: Allocator = LibcAllocator, S: BuildHasher = BuildMurmurHasher> {
+    map     : &'a mut HashMap<K, V, A, S>,
+    index   : usize,
+}
+
+pub struct VacantEntry<'a, K: Hash + PartialEq, V, A: Allocator = LibcAllocator, S: BuildHasher = BuildMurmurHasher> {
+    map     : &'a mut HashMap<K, V, A, S>,
+    key     : K,
+    hash    : usize,
+    index   : usize,
+}
+
+impl<'a, K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> Entry<'a, K, V, A, S> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(e) => e.key(),
+            Entry::Vacant(e) => e.key(),
+        }
+    }
+
+    /// The value, inserting `v` first if the entry is vacant.
+    pub fn orInsert(self, v: V) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.intoMut(),
+            Entry::Vacant(e) => e.insert(v),
+        }
+    }
+
+    pub fn orInsertWith<F: FnOnce() -> V>(self, f: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(e) => e.intoMut(),
+            Entry::Vacant(e) => e.insert(f()),
+        }
+    }
+
+    pub fn orDefault(self) -> &'a mut V where V: Default { self.orInsertWith(V::default) }
+
+    /// Update the value in place if the entry is occupied.
+    pub fn andModify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut e) => {
+                f(e.getMut());
+                Entry::Occupied(e)
+            }
+            Entry::Vacant(e) => Entry::Vacant(e),
+        }
+    }
+}
+
+impl<'a, K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> OccupiedEntry<'a, K, V, A, S> {
+    fn slot(&self) -> *mut KeyValue<K, V> { unsafe { (self.map.table.getPtr() as *mut KeyValue<K, V>).add(self.index) } }
+
+    pub fn key(&self) -> &K { unsafe { &(*self.slot()).key } }
+    pub fn get(&self) -> &V { unsafe { &(*self.slot()).value } }
+    pub fn getMut(&mut self) -> &mut V { unsafe { &mut (*self.slot()).value } }
+    pub fn intoMut(self) -> &'a mut V { unsafe { &mut (*self.slot()).value } }
+
+    /// Replace the value, returning the old one.
+    pub fn insert(&mut self, v: V) -> V { mem::replace(self.getMut(), v) }
+
+    pub fn removeEntry(self) -> (K, V) { self.map.removeAt(self.index) }
+    pub fn remove(self) -> V { self.removeEntry().1 }
+}
+
+impl<'a, K: Hash + PartialEq, V, A: Allocator, S: BuildHasher> VacantEntry<'a, K, V, A, S> {
+    pub fn key(&self) -> &K { &self.key }
+    pub fn intoKey(self) -> K { self.key }
+
+    /// Fill the slot found by `HashMap::entry`, the table already has room for it.
+    pub fn insert(self, v: V) -> &'a mut V { self.map.fill(self.index, self.hash, self.key, v) }
+}
+
+/// Unordered set on top of `HashMap`.
+pub struct HashSet<K: Hash + PartialEq, A: Allocator = LibcAllocator, S: BuildHasher = BuildMurmurHasher> {
+    map     : HashMap<K, (), A, S>,
+}
+
+impl<K: Hash + PartialEq> HashSet<K> {
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+}
+
+impl<K: Hash + PartialEq, A: Allocator + Default, S: BuildHasher + Default> Default for HashSet<K, A, S> {
+    fn default() -> Self { Self::withHasherIn(A::default(), S::default()) }
+}
+
+impl<K: Hash + PartialEq, S: BuildHasher> HashSet<K, LibcAllocator, S> {
+    pub fn withHasher(hasher: S) -> Self { Self::withHasherIn(LibcAllocator, hasher) }
+}
+
+impl<K: Hash + PartialEq, A: Allocator> HashSet<K, A> {
+    pub fn newIn(alloc: A) -> Self { Self { map: HashMap::newIn(alloc) } }
+}
+
+impl<K: Hash + PartialEq, A: Allocator, S: BuildHasher> HashSet<K, A, S> {
+    pub fn withHasherIn(alloc: A, hasher: S) -> Self { Self { map: HashMap::withHasherIn(alloc, hasher) } }
+
+    pub fn allocator(&self) -> &A { self.map.allocator() }
+    pub fn hasher(&self) -> &S { self.map.hasher() }
+
+    pub fn len(&self) -> usize { self.map.count() }
+    pub fn isEmpty(&self) -> bool { self.map.count() == 0 }
+    pub fn clear(&mut self) { self.map.clear() }
+
+    /// Insert `k`, returns false if it was already there (the set keeps the old key).
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn insert(&mut self, k: K) -> bool {
+        match self.map.entry(k) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(e) => { e.insert(()); true }
+        }
+    }
+
+    pub fn contains<Q: ?Sized + Hash + PartialEq>(&self, k: &Q) -> bool where K: Borrow<Q> { self.map.exist(k) }
+    pub fn get<Q: ?Sized + Hash + PartialEq>(&self, k: &Q) -> Option<&K> where K: Borrow<Q> { self.map.getKeyValue(k).map(|(k, _)| k) }
+
+    /// Remove `k`, returns false if it wasn't there.
+    pub fn remove<Q: ?Sized + Hash + PartialEq>(&mut self, k: &Q) -> bool where K: Borrow<Q> { self.map.removeEntry(k).is_some() }
+    pub fn take<Q: ?Sized + Hash + PartialEq>(&mut self, k: &Q) -> Option<K> where K: Borrow<Q> { self.map.removeEntry(k).map(|(k, _)| k) }
+
+    pub fn retain<F: FnMut(&K) -> bool>(&mut self, mut f: F) { self.map.retain(|k, _| f(k)) }
+
+    pub fn iter(&self) -> Keys<'_, K, ()> { self.map.keys() }
+    pub fn drain(&mut self) -> SetDrain<'_, K, A, S> { SetDrain { drain: self.map.drain() } }
+
+    /// Keys in `self` or `other`, each once.
+    pub fn union<'a, B: Allocator>(&'a self, other: &'a HashSet<K, B, S>) -> Union<'a, K, A, S> {
+        Union { iter: self.iter().chain(other.difference(self)) }
+    }
+
+    /// Keys in both `self` and `other`.
+    pub fn intersection<'a, B: Allocator>(&'a self, other: &'a HashSet<K, B, S>) -> Intersection<'a, K, B, S> {
+        Intersection { iter: self.iter(), other }
+    }
+
+    /// Keys in `self` but not in `other`.
+    pub fn difference<'a, B: Allocator>(&'a self, other: &'a HashSet<K, B, S>) -> Difference<'a, K, B, S> {
+        Difference { iter: self.iter(), other }
+    }
+
+    /// Keys in exactly one of `self` and `other`.
+    pub fn symmetricDifference<'a, B: Allocator>(&'a self, other: &'a HashSet<K, B, S>) -> SymmetricDifference<'a, K, A, B, S> {
+        SymmetricDifference { iter: self.difference(other).chain(other.difference(self)) }
+    }
+
+    pub fn isSubset<B: Allocator>(&self, other: &HashSet<K, B, S>) -> bool {
+        self.len() <= other.len() && self.iter().all(|k| other.contains(k))
+    }
+
+    pub fn isSuperset<B: Allocator>(&self, other: &HashSet<K, B, S>) -> bool { other.isSubset(self) }
+
+    pub fn isDisjoint<B: Allocator>(&self, other: &HashSet<K, B, S>) -> bool {
+        if self.len() <= other.len() {
+            self.iter().all(|k| !other.contains(k))
+        } else {
+            other.iter().all(|k| !self.contains(k))
+        }
+    }
+}
+
+impl<K: Hash + PartialEq + Clone, A: Allocator + Clone, S: BuildHasher + Clone> Clone for HashSet<K, A, S> {
+    fn clone(&self) -> Self { Self { map: self.map.clone() } }
+}
+
+pub struct Union<'a, K: Hash + PartialEq, A: Allocator, S: BuildHasher> {
+    iter    : iter::Chain<Keys<'a, K, ()>, Difference<'a, K, A, S>>,
+}
+
+impl<'a, K: Hash + PartialEq, A: Allocator, S: BuildHasher> Iterator for Union<'a, K, A, S> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> { self.iter.next() }
+}
+
+pub struct Intersection<'a, K: Hash + PartialEq, B: Allocator, S: BuildHasher> {
+    iter    : Keys<'a, K, ()>,
+    other   : &'a HashSet<K, B, S>,
+}
+
+impl<'a, K: Hash + PartialEq, B: Allocator, S: BuildHasher> Iterator for Intersection<'a, K, B, S> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> {
+        let other = self.other;
+        self.iter.find(|k| other.contains(*k))
+    }
+}
+
+pub struct Difference<'a, K: Hash + PartialEq, B: Allocator, S: BuildHasher> {
+    iter    : Keys<'a, K, ()>,
+    other   : &'a HashSet<K, B, S>,
+}
+
+impl<'a, K: Hash + PartialEq, B: Allocator, S: BuildHasher> Iterator for Difference<'a, K, B, S> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> {
+        let other = self.other;
+        self.iter.find(|k| !other.contains(*k))
+    }
+}
+
+pub struct SymmetricDifference<'a, K: Hash + PartialEq, A: Allocator, B: Allocator, S: BuildHasher> {
+    iter    : iter::Chain<Difference<'a, K, B, S>, Difference<'a, K, A, S>>,
+}
+
+impl<'a, K: Hash + PartialEq, A: Allocator, B: Allocator, S: BuildHasher> Iterator for SymmetricDifference<'a, K, A, B, S> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<&'a K> { self.iter.next() }
+}
+
+/// Draining iterator for `HashSet`, see `HashMap::drain`.
+pub struct SetDrain<'a, K: Hash + PartialEq, A: Allocator = LibcAllocator, S: BuildHasher = BuildMurmurHasher> {
+    drain   : MapDrain<'a, K, (), A, S>,
+}
+
+impl<'a, K: Hash + PartialEq, A: Allocator, S: BuildHasher> Iterator for SetDrain<'a, K, A, S> {
+    type Item = K;
+    fn next(&mut self) -> Option<K> { self.drain.next().map(|(k, _)| k) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.drain.size_hint() }
+}
+
+/// Owning iterator for `HashSet`.
+pub struct SetIntoIter<K: Hash + PartialEq, A: Allocator = LibcAllocator, S: BuildHasher = BuildMurmurHasher> {
+    iter    : MapIntoIter<K, (), A, S>,
+}
+
+impl<K: Hash + PartialEq, A: Allocator, S: BuildHasher> Iterator for SetIntoIter<K, A, S> {
+    type Item = K;
+    fn next(&mut self) -> Option<K> { self.iter.next().map(|(k, _)| k) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.iter.size_hint() }
+}
+
+impl<K: Hash + PartialEq, A: Allocator, S: BuildHasher> IntoIterator for HashSet<K, A, S> {
+    type Item = K;
+    type IntoIter = SetIntoIter<K, A, S>;
+    fn into_iter(self) -> SetIntoIter<K, A, S> { SetIntoIter { iter: self.map.into_iter() } }
+}
+
+impl<'a, K: Hash + PartialEq, A: Allocator, S: BuildHasher> IntoIterator for &'a HashSet<K, A, S> {
+    type Item = &'a K;
+    type IntoIter = Keys<'a, K, ()>;
+    fn into_iter(self) -> Keys<'a, K, ()> { self.map.keys() }
+}
+
+impl<K: Hash + PartialEq, A: Allocator, S: BuildHasher> iter::Extend<K> for HashSet<K, A, S> {
+    fn extend<I: IntoIterator<Item = K>>(&mut self, it: I) {
+        for k in it {
+            self.insert(k);
         }
     }
 }
 
-impl Hash for i32 {
-    fn hash(&self) -> usize { *self as usize }
+impl<K: Hash + PartialEq, A: Allocator + Default, S: BuildHasher + Default> iter::FromIterator<K> for HashSet<K, A, S> {
+    fn from_iter<I: IntoIterator<Item = K>>(it: I) -> Self {
+        let mut s = Self::withHasherIn(A::default(), S::default());
+        s.extend(it);
+        s
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
     use crate::vec::*;
+    use crate::string::*;
+    use crate::sort::Sort;
+    use crate::testutil::*;
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn testInsert() {
         let mut hm = HashMap::<i32, i32>::new();
         for i in 0..100 {
@@ -251,7 +809,7 @@ mod test {
         }
 
         for i in 0..100 {
-            let ret = hm.get(i);
+            let ret = hm.get(&i);
             assert!(ret.is_some());
             match ret {
                 Some(o) => assert!(*o == i * 2),
@@ -261,6 +819,7 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::assertions_on_constants, clippy::bool_comparison)]
     fn testRemove() {
         let mut hm = HashMap::<i32, i32>::new();
         for i in 0..100 {
@@ -268,12 +827,12 @@ mod test {
         }
 
         for i in 45..55 {
-            hm.remove(i);
-            assert!(hm.exist(i) == false);
+            hm.remove(&i);
+            assert!(hm.exist(&i) == false);
         }
 
         for i in 0..45 {
-            let ret = hm.get(i);
+            let ret = hm.get(&i);
             assert!(ret.is_some());
             match ret {
                 Some(o) => assert!(*o == i * 2),
@@ -282,7 +841,7 @@ mod test {
         }
 
         for i in 55..100 {
-            let ret = hm.get(i);
+            let ret = hm.get(&i);
             assert!(ret.is_some());
             match ret {
                 Some(o) => assert!(*o == i * 2),
@@ -291,83 +850,407 @@ mod test {
         }
 
         for i in 45..55 {
-            assert!(hm.exist(i) == false);
+            assert!(hm.exist(&i) == false);
         }
 
         assert!(hm.count() == 90);
     }
 
     #[test]
+    #[allow(clippy::assertions_on_constants)]
     fn testVecInsert() {
-        let mut hm = HashMap::<i32, Vec<i32>>::new();
-        for i in 0..100 {
-            let mut v = Vec::new();
-            for j in 0..i * 2 {
-                v.pushBack(j);
+        crate::mem::assertNoLeaks(|| {
+            let mut hm = HashMap::<i32, Vec<i32>>::new();
+            for i in 0..100 {
+                let mut v = Vec::new();
+                for j in 0..i * 2 {
+                    v.pushBack(j);
+                }
+                hm.set(i, v);
             }
-            hm.set(i, v);
-        }
 
-        for i in 0..100 {
-            let ret = hm.get(i);
-            assert!(ret.is_some());
-            match ret {
-                Some(o) => {
-                    for j in 0..i*2 {
-                        assert!((*o)[j as usize] == j)
+            for i in 0..100 {
+                let ret = hm.get(&i);
+                assert!(ret.is_some());
+                match ret {
+                    Some(o) => {
+                        for j in 0..i*2 {
+                            assert!((*o)[j as usize] == j)
+                        }
                     }
+                    None => assert!(false)
                 }
-                None => assert!(false)
             }
-        }
+        });
     }
 
     #[test]
+    #[allow(clippy::assertions_on_constants, clippy::bool_comparison)]
     fn testVecRemove() {
-        let mut hm = HashMap::<i32, Vec<i32>>::new();
-        for i in 0..100 {
-            let mut v = Vec::new();
-            for j in 0..i * 2 {
-                v.pushBack(j);
+        crate::mem::assertNoLeaks(|| {
+            let mut hm = HashMap::<i32, Vec<i32>>::new();
+            for i in 0..100 {
+                let mut v = Vec::new();
+                for j in 0..i * 2 {
+                    v.pushBack(j);
+                }
+                hm.set(i, v);
             }
-            hm.set(i, v);
-        }
 
-        for i in 45..55 {
-            hm.remove(i);
-            assert!(hm.exist(i) == false);
-        }
+            for i in 45..55 {
+                hm.remove(&i);
+                assert!(hm.exist(&i) == false);
+            }
 
-        for i in 0..45 {
-            let ret = hm.get(i);
-            assert!(ret.is_some());
-            match ret {
-                Some(o) => {
-                    for j in 0..i*2 {
-                        assert!((*o)[j as usize] == j)
-                    }
-                },
-                None => assert!(false)
+            for i in 0..45 {
+                let ret = hm.get(&i);
+                assert!(ret.is_some());
+                match ret {
+                    Some(o) => {
+                        for j in 0..i*2 {
+                            assert!((*o)[j as usize] == j)
+                        }
+                    },
+                    None => assert!(false)
+                }
             }
-        }
 
-        for i in 55..100 {
-            let ret = hm.get(i);
-            assert!(ret.is_some());
-            match ret {
-                Some(o) => {
-                    for j in 0..i*2 {
-                        assert!((*o)[j as usize] == j)
-                    }
-                },
-                None => assert!(false)
+            for i in 55..100 {
+                let ret = hm.get(&i);
+                assert!(ret.is_some());
+                match ret {
+                    Some(o) => {
+                        for j in 0..i*2 {
+                            assert!((*o)[j as usize] == j)
+                        }
+                    },
+                    None => assert!(false)
+                }
+            }
+
+            for i in 45..55 {
+                assert!(hm.exist(&i) == false);
+            }
+
+            assert!(hm.count() == 90);
+        });
+    }
+
+    #[test]
+    #[allow(clippy::bool_comparison)]
+    fn testEmpty() {
+        let hm = HashMap::<i32, i32>::new();
+        assert!(hm.get(&1).is_none());
+        assert!(hm.exist(&1) == false);
+    }
+
+    #[test]
+    fn testCollisionRemove() {
+        crate::mem::assertNoLeaks(|| {
+            // keys are multiples of the capacity so they all probe from the same slot
+            let mut hm = HashMap::<i32, Vec<i32>>::new();
+            for i in 0..20 {
+                let mut v = Vec::new();
+                v.pushBack(i);
+                hm.set(i * 1024, v);
             }
+
+            for i in 5..10 {
+                hm.remove(&(i * 1024));
+            }
+
+            for i in 0..20 {
+                match hm.get(&(i * 1024)) {
+                    Some(v) => { assert!(!(5..10).contains(&i)); assert!(v[0] == i) },
+                    None => assert!((5..10).contains(&i)),
+                }
+            }
+            assert!(hm.count() == 15);
+        });
+    }
+
+    #[test]
+    fn testBorrowedLookup() {
+        crate::mem::assertNoLeaks(|| {
+            let mut hm = HashMap::<String, Vec<u8>>::new();
+            for i in 0..50 {
+                let mut v = Vec::new();
+                v.pushBack(i as u8);
+                hm.set(crate::format!("key{}", i), v);
+            }
+            assert!(hm.exist("key7") && !hm.exist("key50"));
+            assert!(hm.get("key7").unwrap()[0] == 7);
+            hm.getMut("key8").unwrap().pushBack(1);
+            assert!(hm.get("key8").unwrap().len() == 2);
+
+            let (k, v) = hm.getKeyValue("key9").unwrap();
+            assert!(*k == "key9" && v[0] == 9);
+
+            let (k, v) = hm.removeEntry("key10").unwrap();
+            assert!(k == "key10" && v[0] == 10);
+            assert!(hm.remove("key11").unwrap()[0] == 11);
+            assert!(hm.remove("key11").is_none() && hm.removeEntry("key10").is_none());
+            assert!(hm.count() == 48);
+
+            // `Vec<u8>` keys looked up by byte slices
+            let mut bytes = HashMap::<Vec<u8>, i32>::new();
+            for i in 0..20u8 {
+                let mut k = Vec::new();
+                k.extendFromSlice(&[i, i + 1]);
+                bytes.set(k, i as i32);
+            }
+            assert!(bytes.get(&[3u8, 4][..]) == Some(&3) && bytes.get(&[3u8, 5][..]).is_none());
+            assert!(bytes.remove(&[19u8, 20][..]) == Some(19) && bytes.count() == 19);
+        });
+    }
+
+    #[test]
+    fn testEntry() {
+        crate::mem::assertNoLeaks(|| {
+            let mut m = HashMap::<String, i32>::new();
+            for w in ["b", "a", "b", "c", "b", "a"] {
+                *m.entry(String::from(w)).orInsert(0) += 1;
+            }
+            assert!(m.get("a") == Some(&2) && m.get("b") == Some(&3) && m.get("c") == Some(&1));
+
+            m.entry(String::from("a")).andModify(|v| *v *= 10).orDefault();
+            m.entry(String::from("d")).andModify(|v| *v *= 10).orInsertWith(|| 7);
+            assert!(m.get("a") == Some(&20) && m.get("d") == Some(&7));
+
+            match m.entry(String::from("b")) {
+                Entry::Occupied(mut e) => {
+                    assert!(*e.key() == "b" && *e.get() == 3 && e.insert(4) == 3);
+                    assert!(e.remove() == 4);
+                }
+                Entry::Vacant(_) => panic!("b should be in the map"),
+            }
+            match m.entry(String::from("e")) {
+                Entry::Vacant(e) => assert!(e.key() == &"e"),
+                Entry::Occupied(_) => panic!("e shouldn't be in the map"),
+            }
+            assert!(m.count() == 3 && m.get("b").is_none());
+
+            // multimap, vacant entries filled across table growth
+            let mut mm = HashMap::<i32, Vec<i32>>::new();
+            for i in 0..1000 {
+                mm.entry(i % 37).orInsertWith(Vec::new).pushBack(i);
+            }
+            assert!(mm.count() == 37);
+            for k in 0..37 {
+                let v = mm.get(&k).unwrap();
+                assert!(v.len() == if k < 1000 % 37 { 28 } else { 27 });
+                assert!(v.iter().all(|x| x % 37 == k));
+            }
+            for k in 0..37 {
+                if let Entry::Occupied(e) = mm.entry(k) {
+                    if k & 1 == 0 { assert!(e.removeEntry().0 == k) }
+                }
+            }
+            for k in 0..37 {
+                assert!(mm.exist(&k) == (k & 1 == 1));
+            }
+        });
+    }
+
+    #[test]
+    fn testEntryNoGrow() {
+        // 3 entries in 4 slots is the load limit, only a new key makes the table grow
+        let mut m = HashMap::<i32, i32>::new();
+        for i in 0..3 { m.set(i, i) }
+        assert!(m.capacity == 4);
+        *m.entry(1).orInsert(0) += 10;
+        assert!(m.capacity == 4 && m.get(&1) == Some(&11));
+        m.entry(3).orInsert(3);
+        assert!(m.capacity == 8 && m.count() == 4);
+    }
+
+    #[test]
+    fn testIter() {
+        let mut hm = HashMap::<i32, i32>::new();
+        for i in 0..1000 { hm.set(i, i * 2) }
+
+        assert!(hm.iter().len() == 1000 && hm.keys().sum::<i32>() == 999 * 500);
+        assert!(hm.iter().all(|(k, v)| *v == k * 2));
+        for (_, v) in hm.iterMut() { *v += 1 }
+        for v in hm.valuesMut() { *v -= 2 }
+        assert!((&hm).into_iter().all(|(k, v)| *v == k * 2 - 1));
+        assert!(hm.values().map(|v| *v as i64).sum::<i64>() == 999 * 1000 - 1000);
+
+        let mut seen = Vec::new();
+        for _ in 0..1000 { seen.pushBack(false) }
+        for k in hm.keys() {
+            assert!(!seen[*k as usize]);
+            seen[*k as usize] = true;
         }
+        assert!(seen.iter().all(|b| *b));
 
-        for i in 45..55 {
-            assert!(hm.exist(i) == false);
+        let empty = HashMap::<i32, i32>::new();
+        assert!(empty.iter().next().is_none() && empty.keys().count() == 0);
+    }
+
+    // every key hashes to 0, the empty slot marker
+    struct ZeroHasher;
+
+    impl Hasher for ZeroHasher {
+        fn writeBytes(&mut self, _bytes: &[u8]) {}
+        fn finish(&self) -> u64 { 0 }
+    }
+
+    #[derive(Clone, Default)]
+    struct BuildZero;
+
+    impl BuildHasher for BuildZero {
+        type Hasher = ZeroHasher;
+        fn buildHasher(&self) -> ZeroHasher { ZeroHasher }
+    }
+
+    #[test]
+    fn testZeroHash() {
+        let mut hm = HashMap::withHasher(BuildZero);
+        assert!(hm.hash(&7) == 1);
+        for i in 0..100 { hm.set(i, i + 1) }
+        assert!(hm.count() == 100 && hm.iter().count() == 100);
+        for i in 0..100 { assert!(hm.get(&i) == Some(&(i + 1))) }
+        for i in 0..50 { assert!(hm.remove(&(i * 2)) == Some(i * 2 + 1)) }
+        for i in 0..100 { assert!(hm.exist(&i) == (i & 1 == 1)) }
+        hm.set(0, 10);
+        assert!(hm.get(&0) == Some(&10) && hm.count() == 51);
+    }
+
+    #[test]
+    fn testRetain() {
+        let mut rng = Lcg(0x12345);
+
+        for _ in 0..20 {
+            let mut hm = HashMap::<i32, i32>::new();
+            let mut keys = Vec::new();
+            for _ in 0..(rng.next() % 500) {
+                let k = (rng.next() % 2000) as i32 * 64;   // lots of collisions
+                if !hm.exist(&k) { keys.pushBack(k) }
+                hm.set(k, k);
+            }
+            let m = rng.next() & 3;
+            let keep = |k: &i32| (*k / 64) as u32 & 3 != m;
+            let mut visited = 0;
+            hm.retain(|k, v| {
+                visited += 1;
+                *v += 1;
+                keep(k)
+            });
+            assert!(visited == keys.len());
+            for k in keys.iter() {
+                if keep(k) {
+                    assert!(hm.get(k) == Some(&(k + 1)));
+                } else {
+                    assert!(!hm.exist(k));
+                }
+            }
+            assert!(hm.count() == keys.iter().filter(|k| keep(k)).count());
         }
+    }
 
-        assert!(hm.count() == 90);
+    #[test]
+    fn testDrain() {
+        crate::mem::assertNoLeaks(|| {
+            let mut hm = HashMap::<i32, Vec<i32>>::new();
+            for i in 0..100 {
+                let mut v = Vec::new();
+                v.pushBack(i);
+                hm.set(i, v);
+            }
+            let cap = hm.capacity;
+            let mut sum = 0;
+            {
+                let mut d = hm.drain();
+                assert!(d.len() == 100);
+                for _ in 0..10 {
+                    let (k, v) = d.next().unwrap();
+                    assert!(v[0] == k);
+                    sum += k;
+                }
+                assert!(d.len() == 90);
+            }
+            assert!(sum < 100 * 99 / 2 && hm.count() == 0 && hm.capacity == cap && hm.iter().next().is_none());
+
+            for i in 0..50 { hm.set(i, Vec::new()) }
+            assert!(hm.count() == 50 && hm.exist(&49));
+            hm.clear();
+            assert!(hm.count() == 0 && !hm.exist(&49));
+
+            for i in 0..50 { hm.set(i, Vec::new()) }
+            let mut it = hm.clone().into_iter();
+            let (k, _) = it.next().unwrap();
+            assert!(it.len() == 49 && hm.exist(&k));
+            drop(it);
+
+            let mut keys = Vec::new();
+            for (k, _) in hm { keys.pushBack(k) }
+            keys.sortUnstable();
+            assert!(keys.len() == 50 && keys.iter().enumerate().all(|(i, k)| i as i32 == *k));
+        });
+    }
+
+    #[test]
+    fn testDrainLeaked() {
+        // a leaked drain leaves the map empty, later inserts can't find stale keys
+        let mut hm = HashMap::<i32, i32>::new();
+        for i in 0..100 { hm.set(i, i) }
+        let mut d = hm.drain();
+        for _ in 0..10 { assert!(d.next().is_some()) }
+        core::mem::forget(d);
+        assert!(hm.count() == 0 && hm.iter().next().is_none() && !hm.exist(&50));
+        for i in 0..100 { hm.set(i, -i) }
+        assert!(hm.count() == 100 && hm.iter().all(|(k, v)| *v == -*k));
+    }
+
+    fn sorted<'a, I: Iterator<Item = &'a i32>>(it: I) -> Vec<i32> {
+        let mut v = Vec::new();
+        for k in it { v.pushBack(*k) }
+        v.sortUnstable();
+        v
+    }
+
+    #[test]
+    fn testSet() {
+        let a : HashSet<i32> = (0..20).filter(|i| i & 1 == 0).collect();  // evens
+        let mut b = HashSet::new();
+        b.extend((0..20).filter(|i| i % 3 == 0));                        // multiples of 3
+        assert!(a.len() == 10 && b.len() == 7 && a.contains(&4) && !a.contains(&3));
+
+        assert!(sorted(a.union(&b)).asArray() == [0, 2, 3, 4, 6, 8, 9, 10, 12, 14, 15, 16, 18]);
+        assert!(sorted(a.intersection(&b)).asArray() == [0, 6, 12, 18]);
+        assert!(sorted(a.difference(&b)).asArray() == [2, 4, 8, 10, 14, 16]);
+        assert!(sorted(a.symmetricDifference(&b)).asArray() == [2, 3, 4, 8, 9, 10, 14, 15, 16]);
+
+        let c : HashSet<i32> = a.intersection(&b).copied().collect();
+        assert!(c.isSubset(&a) && c.isSubset(&b) && !a.isSubset(&c) && a.isSuperset(&c));
+        assert!(!a.isDisjoint(&b));
+        let odd : HashSet<i32> = (0..20).filter(|i| i & 1 == 1).collect();
+        assert!(a.isDisjoint(&odd) && odd.isDisjoint(&a));
+
+        let mut d = a.clone();
+        assert!(!d.insert(4) && d.insert(5) && d.remove(&5) && !d.remove(&5) && d.take(&4) == Some(4));
+        d.retain(|k| *k < 10);
+        assert!(sorted(d.iter()).asArray() == [0, 2, 6, 8]);
+        assert!(sorted(d.drain().collect::<Vec<i32>>().iter()).asArray() == [0, 2, 6, 8] && d.isEmpty());
+
+        let mut total = 0;
+        for k in a { total += k }
+        assert!(total == 90);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn testSetBorrowed() {
+        crate::mem::assertNoLeaks(|| {
+            let mut s = HashSet::<String>::new();
+            for w in ["red", "green", "blue", "green"] {
+                s.insert(String::from(w));
+            }
+            assert!(s.len() == 3 && s.contains("green") && !s.contains("yellow"));
+            assert!(*s.get("blue").unwrap() == "blue");
+            assert!(s.remove("red") && !s.contains("red"));
+            let t : HashSet<String> = ["blue", "pink"].iter().map(|w| String::from(w)).collect();
+            assert!(s.union(&t).count() == 3 && *s.intersection(&t).next().unwrap() == "blue");
+        });
+    }
+}
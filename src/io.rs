@@ -14,7 +14,8 @@ extern "C"
     pub static mut stderr: *mut libc::FILE;
 }
 
-pub fn fprintStrings<'a>(f: *mut libc::FILE, arr: &[&'a str]) {
+#[allow(clippy::not_unsafe_ptr_arg_deref)]   // `f` is one of the libc streams
+pub fn fprintStrings(f: *mut libc::FILE, arr: &[&str]) {
     for s in arr {
         unsafe { libc::fprintf(f,"%.*s\0".as_bytes().as_ptr() as *const i8, s.len(), s.as_bytes().as_ptr())};
     }
@@ -76,10 +77,12 @@ pub struct FileWriter {
     file    : *mut ::libc::FILE,
 }
 
+#[allow(clippy::result_unit_err)]
 impl FileWriter {
+    #[allow(clippy::cmp_null, clippy::ptr_eq)]
     pub fn create(fname: &str) -> Result<Self, ()> {
         let mut sname = String::from(fname);
-        sname.add('\0' as u8);
+        sname.add(b'\0');
         let f = unsafe { ::libc::fopen(sname.toStr().as_bytes().as_ptr() as *const i8, "wb\0".as_bytes().as_ptr() as *const i8) };
         if f as * const _ == ::core::ptr::null() {
             Result::Err(())
@@ -142,7 +145,9 @@ pub struct FileReader {
     file    : *mut ::libc::FILE,
 }
 
+#[allow(clippy::result_unit_err)]
 impl FileReader {
+    #[allow(clippy::cmp_null, clippy::ptr_eq)]
     pub fn open(fname: &str) -> Result<Self, ()> {
         let mut sname = String::from(fname);
         sname.add(b'\0');
@@ -208,6 +213,7 @@ impl StreamReader for FileReader {
         self.read(buff)
     }
 
+    #[allow(clippy::needless_bool)]
     fn isEOF(&self) -> bool {
         if unsafe { ::libc::feof(self.file) } != 0 {
             true
@@ -217,6 +223,7 @@ impl StreamReader for FileReader {
     }
 }
 
+#[allow(clippy::result_unit_err)]
 pub trait StreamSeek : Stream {
     fn seek(&mut self, cursor: usize) -> Result<usize, ()>;
 }
@@ -224,7 +231,9 @@ pub trait StreamSeek : Stream {
 ////////////////////////////////////////////////////////////////////////////////
 pub struct File {}
 
+#[allow(clippy::result_unit_err)]
 impl File {
+    #[allow(clippy::match_like_matches_macro, clippy::redundant_pattern_matching)]
     pub fn exist(fname: &str) -> bool {
         let f = FileReader::open(fname);
         match f {
@@ -263,7 +272,7 @@ impl File {
             ::libc::tmpnam(s);
             let len = ::libc::strlen(s);
             let slice = ::core::slice::from_raw_parts(s as *const u8, len);
-            let st = String::from(&::core::str::from_utf8(slice).unwrap());
+            let st = String::from(::core::str::from_utf8(slice).unwrap());
             crate::mem::free(s);
             st
         }
@@ -283,6 +292,7 @@ pub mod console {
 mod tests {
     use super::*;
     #[test]
+    #[allow(clippy::needless_as_bytes)]
     fn testCreateReadRemoveFile() {
         let name = File::tmpname();
         {
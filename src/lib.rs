@@ -1,18 +1,41 @@
 #![allow(non_snake_case, non_camel_case_types)]
+// the containers spell it `isEmpty`, which clippy doesn't recognise
+#![allow(clippy::len_without_is_empty)]
 #![no_std]
 
 pub mod ctypes;
 pub mod mem;
 pub mod io;
 pub mod vec;
+pub mod sort;
+pub mod bitvec;
+pub mod vecdeque;
+pub mod linkedlist;
+pub mod arrayvec;
+pub mod smallvec;
+pub mod binaryheap;
 pub mod string;
 pub mod hash;
 pub mod hashmap;
+pub mod slotmap;
+pub mod btree;
 pub mod stream;
 
+#[cfg(test)]
+mod testutil;
+
 pub use string::*;
 pub use vec::*;
+pub use sort::Sort;
+pub use bitvec::{BitVec, BitSet};
+pub use vecdeque::VecDeque;
+pub use linkedlist::LinkedList;
+pub use arrayvec::ArrayVec;
+pub use smallvec::SmallVec;
+pub use binaryheap::{BinaryHeap, MinHeap};
 pub use hashmap::*;
+pub use slotmap::SlotMap;
+pub use btree::{BTreeMap, BTreeSet};
 pub use ctypes::*;
 pub use mem::*;
 
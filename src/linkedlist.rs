@@ -0,0 +1,528 @@
+use core::*;
+use core::marker::PhantomData;
+use crate::mem::*;
+
+struct Node<T> {
+    next    : *mut Node<T>,
+    prev    : *mut Node<T>,
+    value   : T,
+}
+
+/// Doubly linked list, every node is allocated separately through the list's allocator.
+pub struct LinkedList<T, A: Allocator = LibcAllocator> {
+    head    : *mut Node<T>,
+    tail    : *mut Node<T>,
+    count   : usize,
+    alloc   : A,
+}
+
+unsafe impl<T: Send, A: Allocator + Send> Send for LinkedList<T, A> {}
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for LinkedList<T, A> {}
+
+impl<T> LinkedList<T> {
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+}
+
+impl<T, A: Allocator + Default> Default for LinkedList<T, A> {
+    fn default() -> Self { Self::newIn(A::default()) }
+}
+
+impl<T, A: Allocator> LinkedList<T, A> {
+    pub fn newIn(alloc: A) -> Self {
+        Self { head: ptr::null_mut(), tail: ptr::null_mut(), count: 0, alloc }
+    }
+
+    pub fn allocator(&self) -> &A { &self.alloc }
+
+    pub fn len(&self) -> usize { self.count }
+    pub fn isEmpty(&self) -> bool { self.count == 0 }
+
+    // link a new node holding `v` between `prev` and `next` (null at the ends)
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    fn insertBetween(&mut self, prev: *mut Node<T>, next: *mut Node<T>, v: T) -> *mut Node<T> {
+        let size = mem::size_of::<Node<T>>();
+        let align = mem::align_of::<Node<T>>();
+        let node = unsafe { self.alloc.alloc(size, align) } as *mut Node<T>;
+        if node.is_null() { handleAllocError(AllocError { size, align }) }
+        unsafe {
+            node.write(Node { next, prev, value: v });
+            if prev.is_null() { self.head = node } else { (*prev).next = node }
+            if next.is_null() { self.tail = node } else { (*next).prev = node }
+        }
+        self.count += 1;
+        node
+    }
+
+    // unlink `node` and free it, returning its value
+    unsafe fn unlink(&mut self, node: *mut Node<T>) -> T {
+        let Node { next, prev, value } = node.read();
+        if prev.is_null() { self.head = next } else { (*prev).next = next }
+        if next.is_null() { self.tail = prev } else { (*next).prev = prev }
+        self.count -= 1;
+        self.alloc.free(node as *mut u8, mem::size_of::<Node<T>>(), mem::align_of::<Node<T>>());
+        value
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn pushFront(&mut self, v: T) { self.insertBetween(ptr::null_mut(), self.head, v); }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn pushBack(&mut self, v: T) { self.insertBetween(self.tail, ptr::null_mut(), v); }
+
+    pub fn popFront(&mut self) -> Option<T> {
+        if self.head.is_null() { None } else { Some(unsafe { self.unlink(self.head) }) }
+    }
+
+    pub fn popBack(&mut self) -> Option<T> {
+        if self.tail.is_null() { None } else { Some(unsafe { self.unlink(self.tail) }) }
+    }
+
+    pub fn front(&self) -> Option<&T> { unsafe { self.head.as_ref().map(|n| &n.value) } }
+    pub fn back(&self) -> Option<&T> { unsafe { self.tail.as_ref().map(|n| &n.value) } }
+    pub fn frontMut(&mut self) -> Option<&mut T> { unsafe { self.head.as_mut().map(|n| &mut n.value) } }
+    pub fn backMut(&mut self) -> Option<&mut T> { unsafe { self.tail.as_mut().map(|n| &mut n.value) } }
+
+    pub fn clear(&mut self) {
+        while self.popFront().is_some() {}
+    }
+
+    pub fn contains(&self, v: &T) -> bool where T: PartialEq { self.iter().any(|e| e == v) }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { head: self.head, tail: self.tail, count: self.count, marker: PhantomData }
+    }
+
+    pub fn iterMut(&mut self) -> IterMut<'_, T> {
+        IterMut { head: self.head, tail: self.tail, count: self.count, marker: PhantomData }
+    }
+
+    /// Move all the elements of `other` to the end of this list.
+    /// The nodes are relinked in O(1) when both lists share their allocator (see `Allocator::isSame`),
+    /// otherwise the elements are moved one by one to nodes from this list's allocator.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn append(&mut self, other: &mut Self) {
+        if other.head.is_null() { return }
+        if !self.alloc.isSame(&other.alloc) {
+            while let Some(v) = other.popFront() {
+                self.pushBack(v);
+            }
+            return
+        }
+
+        if self.tail.is_null() {
+            self.head = other.head;
+        } else {
+            unsafe {
+                (*self.tail).next = other.head;
+                (*other.head).prev = self.tail;
+            }
+        }
+        self.tail = other.tail;
+        self.count += other.count;
+        other.head = ptr::null_mut();
+        other.tail = ptr::null_mut();
+        other.count = 0;
+    }
+
+    /// Split the list at `at`, returning the nodes from `at` onwards.
+    pub fn splitOff(&mut self, at: usize) -> Self where A: Clone {
+        assert!(at <= self.count, "splitOff index {} out of bounds (len {})", at, self.count);
+        let mut other = Self::newIn(self.alloc.clone());
+        if at == self.count { return other }
+        if at == 0 {
+            mem::swap(self, &mut other);
+            return other
+        }
+
+        // walk from the closest end to the last node kept
+        let last = unsafe {
+            if at - 1 < self.count / 2 {
+                let mut n = self.head;
+                for _ in 0..at - 1 { n = (*n).next }
+                n
+            } else {
+                let mut n = self.tail;
+                for _ in 0..self.count - at { n = (*n).prev }
+                n
+            }
+        };
+        unsafe {
+            other.head = (*last).next;
+            (*other.head).prev = ptr::null_mut();
+            (*last).next = ptr::null_mut();
+        }
+        other.tail = self.tail;
+        other.count = self.count - at;
+        self.tail = last;
+        self.count = at;
+        other
+    }
+
+    /// Cursor on the first element (or on the ghost position if empty).
+    pub fn cursorFront(&mut self) -> CursorMut<'_, T, A> {
+        let index = if self.head.is_null() { None } else { Some(0) };
+        CursorMut { current: self.head, index, list: self }
+    }
+
+    /// Cursor on the last element (or on the ghost position if empty).
+    pub fn cursorBack(&mut self) -> CursorMut<'_, T, A> {
+        let index = if self.tail.is_null() { None } else { Some(self.count - 1) };
+        CursorMut { current: self.tail, index, list: self }
+    }
+}
+
+impl<T, A: Allocator> Drop for LinkedList<T, A> {
+    fn drop(&mut self) { self.clear() }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for LinkedList<T, A> {
+    fn clone(&self) -> Self {
+        let mut l = Self::newIn(self.alloc.clone());
+        for v in self.iter() {
+            l.pushBack(v.clone());
+        }
+        l
+    }
+}
+
+impl<T, A: Allocator> iter::Extend<T> for LinkedList<T, A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        for v in it {
+            self.pushBack(v);
+        }
+    }
+}
+
+impl<T, A: Allocator + Default> iter::FromIterator<T> for LinkedList<T, A> {
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Self {
+        let mut l = Self::newIn(A::default());
+        l.extend(it);
+        l
+    }
+}
+
+/// Iterator over `LinkedList` references, in both directions.
+pub struct Iter<'a, T> {
+    head    : *mut Node<T>,
+    tail    : *mut Node<T>,
+    count   : usize,
+    marker  : PhantomData<&'a Node<T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> {
+        if self.count == 0 { return None }
+        self.count -= 1;
+        let n = unsafe { &*self.head };
+        self.head = n.next;
+        Some(&n.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.count, Some(self.count)) }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        if self.count == 0 { return None }
+        self.count -= 1;
+        let n = unsafe { &*self.tail };
+        self.tail = n.prev;
+        Some(&n.value)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for Iter<'a, T> {}
+
+/// Iterator over `LinkedList` mutable references, in both directions.
+pub struct IterMut<'a, T> {
+    head    : *mut Node<T>,
+    tail    : *mut Node<T>,
+    count   : usize,
+    marker  : PhantomData<&'a mut Node<T>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<&'a mut T> {
+        if self.count == 0 { return None }
+        self.count -= 1;
+        let n = unsafe { &mut *self.head };
+        self.head = n.next;
+        Some(&mut n.value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.count, Some(self.count)) }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut T> {
+        if self.count == 0 { return None }
+        self.count -= 1;
+        let n = unsafe { &mut *self.tail };
+        self.tail = n.prev;
+        Some(&mut n.value)
+    }
+}
+
+impl<'a, T> ExactSizeIterator for IterMut<'a, T> {}
+
+/// Owning iterator for `LinkedList`, the elements not consumed are dropped with it.
+pub struct IntoIter<T, A: Allocator = LibcAllocator> {
+    list    : LinkedList<T, A>,
+}
+
+impl<T, A: Allocator> Iterator for IntoIter<T, A> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> { self.list.popFront() }
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.list.len(), Some(self.list.len())) }
+}
+
+impl<T, A: Allocator> DoubleEndedIterator for IntoIter<T, A> {
+    fn next_back(&mut self) -> Option<T> { self.list.popBack() }
+}
+
+impl<T, A: Allocator> ExactSizeIterator for IntoIter<T, A> {}
+
+impl<T, A: Allocator> IntoIterator for LinkedList<T, A> {
+    type Item = T;
+    type IntoIter = IntoIter<T, A>;
+    fn into_iter(self) -> IntoIter<T, A> { IntoIter { list: self } }
+}
+
+impl<'a, T, A: Allocator> IntoIterator for &'a LinkedList<T, A> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> { self.iter() }
+}
+
+impl<'a, T, A: Allocator> IntoIterator for &'a mut LinkedList<T, A> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+    fn into_iter(self) -> IterMut<'a, T> { self.iterMut() }
+}
+
+/// Mutable cursor over a `LinkedList`. Past the ends the cursor sits on a "ghost" position
+/// between the tail and the head, where `current` is `None`.
+pub struct CursorMut<'a, T, A: Allocator = LibcAllocator> {
+    current : *mut Node<T>,
+    index   : Option<usize>,
+    list    : &'a mut LinkedList<T, A>,
+}
+
+impl<'a, T, A: Allocator> CursorMut<'a, T, A> {
+    /// Index of the current element, `None` on the ghost position.
+    pub fn index(&self) -> Option<usize> { self.index }
+
+    pub fn current(&mut self) -> Option<&mut T> { unsafe { self.current.as_mut().map(|n| &mut n.value) } }
+
+    pub fn peekNext(&mut self) -> Option<&mut T> {
+        let next = if self.current.is_null() { self.list.head } else { unsafe { (*self.current).next } };
+        unsafe { next.as_mut().map(|n| &mut n.value) }
+    }
+
+    pub fn peekPrev(&mut self) -> Option<&mut T> {
+        let prev = if self.current.is_null() { self.list.tail } else { unsafe { (*self.current).prev } };
+        unsafe { prev.as_mut().map(|n| &mut n.value) }
+    }
+
+    /// Move to the next element, from the tail to the ghost position and from there to the head.
+    pub fn moveNext(&mut self) {
+        if self.current.is_null() {
+            self.current = self.list.head;
+            self.index = if self.current.is_null() { None } else { Some(0) };
+        } else {
+            self.current = unsafe { (*self.current).next };
+            self.index = if self.current.is_null() { None } else { self.index.map(|i| i + 1) };
+        }
+    }
+
+    /// Move to the previous element, from the head to the ghost position and from there to the tail.
+    pub fn movePrev(&mut self) {
+        if self.current.is_null() {
+            self.current = self.list.tail;
+            self.index = if self.current.is_null() { None } else { Some(self.list.count - 1) };
+        } else {
+            self.current = unsafe { (*self.current).prev };
+            self.index = if self.current.is_null() { None } else { self.index.map(|i| i - 1) };
+        }
+    }
+
+    /// Insert `v` after the current element (at the front on the ghost position).
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn insertAfter(&mut self, v: T) {
+        if self.current.is_null() {
+            self.list.pushFront(v);
+        } else {
+            let next = unsafe { (*self.current).next };
+            self.list.insertBetween(self.current, next, v);
+        }
+    }
+
+    /// Insert `v` before the current element (at the back on the ghost position).
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn insertBefore(&mut self, v: T) {
+        if self.current.is_null() {
+            self.list.pushBack(v);
+        } else {
+            let prev = unsafe { (*self.current).prev };
+            self.list.insertBetween(prev, self.current, v);
+            self.index = self.index.map(|i| i + 1);
+        }
+    }
+
+    /// Remove the current element and move to the next one.
+    pub fn removeCurrent(&mut self) -> Option<T> {
+        if self.current.is_null() { return None }
+        let node = self.current;
+        self.current = unsafe { (*node).next };
+        if self.current.is_null() { self.index = None }
+        Some(unsafe { self.list.unlink(node) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec::*;
+    use core::cell::Cell;
+    use crate::testutil::*;
+
+    fn values(l: &LinkedList<Vec<i32>>) -> Vec<i32> { l.iter().map(|v| v[0]).collect() }
+
+    #[test]
+    fn testPushPop() {
+        crate::mem::assertNoLeaks(|| {
+            let mut l = LinkedList::new();
+            for i in 0..10 {
+                l.pushBack(item(i));
+                l.pushFront(item(-i));
+            }
+            assert!(l.len() == 20 && l.front().unwrap()[0] == -9 && l.back().unwrap()[0] == 9);
+            assert!(l.popFront().unwrap()[0] == -9 && l.popBack().unwrap()[0] == 9);
+            l.frontMut().unwrap()[0] = 100;
+            assert!(l.iter().next_back().unwrap()[0] == 8 && l.iter().next().unwrap()[0] == 100);
+            for v in l.iterMut().rev().take(2) {
+                v[0] += 1;
+            }
+            assert!(l.back().unwrap()[0] == 9);
+            let ints : LinkedList<i32> = (0..5).collect();
+            assert!(ints.contains(&3) && !ints.contains(&7));
+
+            // dropping a partly consumed iterator and a non empty list
+            let mut it = l.clone().into_iter();
+            assert!(it.next().unwrap()[0] == 100 && it.next_back().unwrap()[0] == 9);
+            drop(it);
+            l.popBack();
+        });
+    }
+
+    #[test]
+    fn testAppendSplitOff() {
+        crate::mem::assertNoLeaks(|| {
+            let mut a : LinkedList<Vec<i32>> = (0..5).map(item).collect();
+            let mut b : LinkedList<Vec<i32>> = (5..10).map(item).collect();
+            a.append(&mut b);
+            assert!(a.len() == 10 && b.isEmpty());
+            assert!(values(&a).asArray() == [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+
+            let mut c = a.splitOff(7);
+            let d = a.splitOff(2);
+            assert!(values(&a).asArray() == [0, 1] && values(&d).asArray() == [2, 3, 4, 5, 6]);
+            assert!(values(&c).asArray() == [7, 8, 9]);
+            assert!(c.splitOff(3).isEmpty());
+            let e = c.splitOff(0);
+            assert!(c.isEmpty() && e.len() == 3 && e.front().unwrap()[0] == 7);
+
+            b.append(&mut a);
+            b.pushBack(item(10));
+            assert!(values(&b).asArray() == [0, 1, 10] && a.isEmpty());
+        });
+    }
+
+    // counts the blocks it has out
+    struct CountingAllocator {
+        live    : Cell<usize>,
+    }
+
+    impl Allocator for CountingAllocator {
+        unsafe fn alloc(&self, size: usize, align: usize) -> *mut u8 {
+            self.live.set(self.live.get() + 1);
+            LibcAllocator.alloc(size, align)
+        }
+
+        unsafe fn free(&self, ptr: *mut u8, size: usize, align: usize) {
+            self.live.set(self.live.get() - 1);
+            LibcAllocator.free(ptr, size, align)
+        }
+    }
+
+    #[test]
+    fn testAppendAllocators() {
+        let (ca, cb) = (CountingAllocator { live: Cell::new(0) }, CountingAllocator { live: Cell::new(0) });
+        let mut a = LinkedList::newIn(&ca);
+        let mut b = LinkedList::newIn(&cb);
+        for i in 0..5 {
+            a.pushBack(i);
+            b.pushBack(i + 5);
+        }
+        a.append(&mut b);
+        // the nodes of `b` went back to its allocator, `a` allocated its own
+        assert!(ca.live.get() == 10 && cb.live.get() == 0 && b.isEmpty());
+        assert!(a.iter().copied().eq(0..10));
+
+        // same allocator, the nodes are relinked
+        let mut c = LinkedList::newIn(&ca);
+        for i in 10..15 { c.pushBack(i) }
+        assert!(ca.live.get() == 15);
+        a.append(&mut c);
+        assert!(ca.live.get() == 15 && c.isEmpty() && a.iter().copied().eq(0..15));
+        drop(a);
+        assert!(ca.live.get() == 0);
+    }
+
+    #[test]
+    fn testCursor() {
+        crate::mem::assertNoLeaks(|| {
+            let mut l : LinkedList<Vec<i32>> = (0..6).map(item).collect();
+            {
+                let mut c = l.cursorFront();
+                assert!(c.index() == Some(0) && c.current().unwrap()[0] == 0);
+                c.moveNext();
+                c.moveNext();
+                assert!(c.removeCurrent().unwrap()[0] == 2);
+                assert!(c.index() == Some(2) && c.current().unwrap()[0] == 3);
+                c.insertBefore(item(20));
+                c.insertAfter(item(30));
+                assert!(c.index() == Some(3) && c.peekPrev().unwrap()[0] == 20 && c.peekNext().unwrap()[0] == 30);
+            }
+            assert!(values(&l).asArray() == [0, 1, 20, 3, 30, 4, 5]);
+            {
+                // through the ghost position at both ends
+                let mut c = l.cursorBack();
+                assert!(c.index() == Some(6));
+                assert!(c.removeCurrent().unwrap()[0] == 5);
+                assert!(c.index().is_none() && c.current().is_none() && c.removeCurrent().is_none());
+                assert!(c.peekNext().unwrap()[0] == 0 && c.peekPrev().unwrap()[0] == 4);
+                c.insertAfter(item(-1));
+                c.insertBefore(item(40));
+                c.movePrev();
+                assert!(c.index() == Some(7) && c.current().unwrap()[0] == 40);
+                c.moveNext();
+                c.moveNext();
+                assert!(c.index() == Some(0) && c.current().unwrap()[0] == -1);
+                c.movePrev();
+                assert!(c.index().is_none());
+            }
+            assert!(values(&l).asArray() == [-1, 0, 1, 20, 3, 30, 4, 40]);
+
+            let mut e : LinkedList<Vec<i32>> = LinkedList::new();
+            let mut c = e.cursorFront();
+            assert!(c.index().is_none());
+            c.moveNext();
+            assert!(c.current().is_none());
+            c.insertBefore(item(1));
+            assert!(e.len() == 1);
+        });
+    }
+}
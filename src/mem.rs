@@ -1,51 +1,275 @@
-pub unsafe fn allocRaw(size: usize) -> *mut u8 {
-    //let addr = libc::memalign(core::mem::size_of::<usize>(), size) as *mut u8;
-    let addr = libc::calloc(core::mem::size_of::<usize>(), size) as *mut u8;
-    //libc::memset(addr as *mut libc::c_void, 0, size);
-    addr
+pub mod rc;
+pub mod arc;
+pub mod arena;
+pub mod pool;
+#[cfg(feature = "debug-alloc")]
+pub mod debug;
+
+pub use rc::Rc;
+pub use arc::Arc;
+pub use arena::Arena;
+pub use pool::Pool;
+
+/// Alignment guaranteed by `allocRaw` (and the libc `malloc` family).
+pub const MIN_ALIGN: usize = 2 * core::mem::size_of::<usize>();
+
+// libc primitives, wrapped by `debug` when the "debug-alloc" feature is on
+#[cfg_attr(feature = "debug-alloc", allow(dead_code))]
+mod sys {
+    use super::MIN_ALIGN;
+
+    /// Zeroed block of `size` bytes aligned to `MIN_ALIGN`, or null on failure.
+    ///
+    /// # Safety
+    /// The block must be released with `freeRaw` (or resized with `reallocRaw`).
+    pub unsafe fn allocRaw(size: usize) -> *mut u8 {
+        //let addr = libc::memalign(core::mem::size_of::<usize>(), size) as *mut u8;
+        let addr = libc::calloc(1, size) as *mut u8;
+        //libc::memset(addr as *mut libc::c_void, 0, size);
+        addr
+    }
+
+    /// Same as `allocRaw` (zeroed memory) but the returned address is a multiple of `align`,
+    /// which must be a power of 2.
+    ///
+    /// # Safety
+    /// The block must be released with `freeRaw` (or resized with `reallocRawAligned` and the same `align`).
+    pub unsafe fn allocRawAligned(size: usize, align: usize) -> *mut u8 {
+        if align <= MIN_ALIGN { return allocRaw(size) }
+
+        let mut addr = core::ptr::null_mut();
+        if libc::posix_memalign(&mut addr, align, size) != 0 { return core::ptr::null_mut() }
+        libc::memset(addr, 0, size);
+        addr as *mut u8
+    }
+
+    /// # Safety
+    /// `arr` must be null or a live block from `allocRaw`/`reallocRaw`, it is invalid afterwards
+    /// unless null is returned.
+    pub unsafe fn reallocRaw(arr: *mut u8, size: usize) -> *mut u8 {
+        libc::realloc(arr as *mut libc::c_void, size) as *mut u8
+    }
+
+    /// Resize a block returned by `allocRawAligned`. The system `realloc` only keeps
+    /// `MIN_ALIGN`, so over-aligned blocks are moved to a fresh aligned block instead.
+    ///
+    /// # Safety
+    /// `arr` must be a live block of `oldSize` bytes allocated with `align`, it is invalid
+    /// afterwards unless null is returned.
+    pub unsafe fn reallocRawAligned(arr: *mut u8, oldSize: usize, newSize: usize, align: usize) -> *mut u8 {
+        if align <= MIN_ALIGN { return reallocRaw(arr, newSize) }
+
+        let addr = allocRawAligned(newSize, align);
+        if !addr.is_null() {
+            core::ptr::copy_nonoverlapping(arr, addr, core::cmp::min(oldSize, newSize));
+            freeRaw(arr);
+        }
+        addr
+    }
+
+    /// Frees blocks from `allocRaw`, `allocRawAligned` and their realloc counterparts.
+    ///
+    /// # Safety
+    /// `arr` must be null or a live block from one of those functions, and isn't usable afterwards.
+    pub unsafe fn freeRaw(arr: *mut u8) {
+        libc::free(arr as *mut libc::c_void);
+    }
 }
 
-pub unsafe fn freeRaw(arr: *mut u8) {
-    libc::free(arr as *mut libc::c_void);
+#[cfg(not(feature = "debug-alloc"))]
+pub use sys::{allocRaw, allocRawAligned, reallocRaw, reallocRawAligned, freeRaw};
+#[cfg(feature = "debug-alloc")]
+pub use debug::{allocRaw, allocRawAligned, reallocRaw, reallocRawAligned, freeRaw};
+
+/// Run `f` and, with the "debug-alloc" feature, panic if it left allocations
+/// (made from the calling thread) behind. Without the feature this only runs `f`.
+pub fn assertNoLeaks<F: FnOnce()>(f: F) {
+    #[cfg(feature = "debug-alloc")]
+    debug::assertNoLeaks(f);
+    #[cfg(not(feature = "debug-alloc"))]
+    f();
 }
 
+/// Zeroed, uninitialised storage for a `T`, or null on failure.
+///
+/// # Safety
+/// The result must be released with `free`.
+#[cfg_attr(feature = "debug-alloc", track_caller)]
 pub unsafe fn alloc<T>() -> *mut T {
-    allocRaw(core::mem::size_of::<T>()) as *mut T
+    allocRawAligned(core::mem::size_of::<T>(), core::mem::align_of::<T>()) as *mut T
 }
 
+/// Release storage from `alloc` without dropping its content.
+///
+/// # Safety
+/// `t` must come from `alloc::<T>` (or be null) and isn't usable afterwards.
+#[cfg_attr(feature = "debug-alloc", track_caller)]
 pub unsafe fn free<T>(t: *mut T) {
     freeRaw(t as *mut u8)
 }
 
 // TODO: change this to const generics when they become stable and return a slice
+/// Zeroed, uninitialised storage for `count` values of `T`, or null on failure.
+///
+/// # Safety
+/// The result must be released with `freeArray` once every element is initialised, or with `free`.
+#[cfg_attr(feature = "debug-alloc", track_caller)]
 pub unsafe fn allocArray<T>(count: usize) -> *mut T {
-    allocRaw(core::mem::size_of::<T>() * count) as *mut T
+    allocRawAligned(core::mem::size_of::<T>() * count, core::mem::align_of::<T>()) as *mut T
 }
 
 // TODO: change this to slice once const generics stable
+/// Drop the `count` elements of `ptr` and release it.
+///
+/// # Safety
+/// `ptr` must come from `allocArray::<T>(count)` with all `count` elements initialised.
 pub unsafe fn freeArray<T>(ptr: *mut T, count: usize) {
     let arr      = core::slice::from_raw_parts_mut(ptr, count); // this will keep a pointer (will not free it)
-    for i in 0..count {
-        ::core::ptr::drop_in_place(&arr[i] as *const T as *mut T);
-    }
+    ::core::ptr::drop_in_place(arr);
     free(ptr);
 }
 
+////////////////////////////////////////////////////////////////////////////////
+/// Returned by the `try*` container functions when the allocator runs out of memory
+/// (or when the requested size doesn't fit in `usize`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AllocError {
+    pub size    : usize,
+    pub align   : usize,
+}
+
+impl core::fmt::Display for AllocError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "memory allocation of {} bytes (align {}) failed", self.size, self.align)
+    }
+}
+
+/// Called by the infallible container functions when an allocation fails.
+pub type OomHandler = fn(AllocError) -> !;
+
+static OOM_HANDLER : core::sync::atomic::AtomicUsize = core::sync::atomic::AtomicUsize::new(0);
+
+fn defaultOomHandler(err: AllocError) -> ! {
+    panic!("{}", err)
+}
+
+/// Replace the out of memory handler (the default one panics), returning the previous one.
+pub fn setOomHandler(handler: OomHandler) -> OomHandler {
+    match OOM_HANDLER.swap(handler as usize, core::sync::atomic::Ordering::SeqCst) {
+        0 => defaultOomHandler,
+        h => unsafe { core::mem::transmute::<usize, OomHandler>(h) },
+    }
+}
+
+/// Invoke the current out of memory handler.
+pub fn handleAllocError(err: AllocError) -> ! {
+    match OOM_HANDLER.load(core::sync::atomic::Ordering::SeqCst) {
+        0 => defaultOomHandler(err),
+        h => {
+            let handler : OomHandler = unsafe { core::mem::transmute(h) };
+            handler(err)
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Memory source used by the containers (`Box`, `Vec`, `String`, `HashMap`).
+///
+/// `size` and `align` passed to `realloc`/`free` are always the ones the block
+/// was allocated (or last reallocated) with. Returned memory is not required
+/// to be zeroed, and a null pointer signals an allocation failure.
+///
+/// Every block passed to `realloc`/`free` must have been returned by the same allocator
+/// instance (or a copy of it that shares its state) and not freed yet.
+pub trait Allocator {
+    /// # Safety
+    /// `align` must be a power of 2, the block must be freed through this allocator.
+    unsafe fn alloc(&self, size: usize, align: usize) -> *mut u8;
+
+    /// Resize `ptr` to `newSize` bytes, keeping the first `min(oldSize, newSize)` bytes.
+    /// The default implementation allocates a new block, copies and frees the old one.
+    ///
+    /// # Safety
+    /// See the trait: `ptr` must be a live block of `oldSize` bytes allocated with `align`.
+    unsafe fn realloc(&self, ptr: *mut u8, oldSize: usize, newSize: usize, align: usize) -> *mut u8 {
+        let newPtr = self.alloc(newSize, align);
+        if !newPtr.is_null() {
+            ::core::ptr::copy_nonoverlapping(ptr, newPtr, ::core::cmp::min(oldSize, newSize));
+            self.free(ptr, oldSize, align);
+        }
+        newPtr
+    }
+
+    /// # Safety
+    /// See the trait: `ptr` must be a live block of `size` bytes allocated with `align`.
+    unsafe fn free(&self, ptr: *mut u8, size: usize, align: usize);
+
+    /// Can the blocks of `other` be freed through `self`? Containers use it to move whole
+    /// allocations between them. Zero sized allocators are stateless, so by default any two
+    /// of them are interchangeable and any two others are not.
+    fn isSame(&self, other: &Self) -> bool where Self: Sized {
+        let _ = other;
+        ::core::mem::size_of::<Self>() == 0
+    }
+}
+
+/// The default allocator: forwards to `allocRawAligned`, `reallocRawAligned` and `freeRaw` (libc).
+#[derive(Clone, Copy, Default)]
+pub struct LibcAllocator;
+
+impl Allocator for LibcAllocator {
+    #[inline]
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    unsafe fn alloc(&self, size: usize, align: usize) -> *mut u8 { allocRawAligned(size, align) }
+
+    #[inline]
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    unsafe fn realloc(&self, ptr: *mut u8, oldSize: usize, newSize: usize, align: usize) -> *mut u8 { reallocRawAligned(ptr, oldSize, newSize, align) }
+
+    #[inline]
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    unsafe fn free(&self, ptr: *mut u8, _size: usize, _align: usize) { freeRaw(ptr) }
+}
+
+impl<A: Allocator + ?Sized> Allocator for &A {
+    #[inline]
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    unsafe fn alloc(&self, size: usize, align: usize) -> *mut u8 { (**self).alloc(size, align) }
+
+    #[inline]
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    unsafe fn realloc(&self, ptr: *mut u8, oldSize: usize, newSize: usize, align: usize) -> *mut u8 { (**self).realloc(ptr, oldSize, newSize, align) }
+
+    #[inline]
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    unsafe fn free(&self, ptr: *mut u8, size: usize, align: usize) { (**self).free(ptr, size, align) }
+
+    #[inline]
+    fn isSame(&self, other: &Self) -> bool { ::core::ptr::eq(*self, *other) || ::core::mem::size_of_val(*self) == 0 }
+}
+
 #[repr(C)]
 pub struct Unique<T: ?Sized> {
     ptr         : *mut T,
     _marker     : ::core::marker::PhantomData<T>,
 }
 
-impl<T> Unique<T> {
-    pub fn new(ptr: *mut T) -> Self { Self { ptr : ptr, _marker: ::core::marker::PhantomData } }
+unsafe impl<T: Send + ?Sized> Send for Unique<T> {}
+unsafe impl<T: Sync + ?Sized> Sync for Unique<T> {}
+
+impl<T: ?Sized> Unique<T> {
+    pub fn new(ptr: *mut T) -> Self { Self { ptr, _marker: ::core::marker::PhantomData } }
     pub fn getMutPtr(&mut self) -> *mut T { self.ptr }
     pub fn getPtr(&self) -> *const T { self.ptr }
 }
 
+/// Owning pointer to a `T` allocated from `A`. `T` can be unsized: `Box<[T]>` comes from
+/// `Vec::intoBoxedSlice`, `Box<str>` from `String::intoBoxedStr` and `Box<dyn Trait>`
+/// from `unsizeBox!`.
 #[repr(C)]
-pub struct Box<T>{
-    uptr: Unique<T>
+pub struct Box<T: ?Sized, A: Allocator = LibcAllocator> {
+    uptr    : Unique<T>,
+    alloc   : A,
 }
 
 impl<T> Box<T> {
@@ -54,49 +278,133 @@ impl<T> Box<T> {
     /// # Examples
     ///
     /// ```
-    /// let five = Box::new(5);
+    /// let five = alt_std::Box::new(5);
     /// ```
     #[inline(always)]
-    pub fn new(x: T) -> Box<T> {
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn new(x: T) -> Box<T> { Self::newIn(x, LibcAllocator) }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn tryNew(x: T) -> Result<Box<T>, AllocError> { Self::tryNewIn(x, LibcAllocator) }
+}
+
+impl<T: ?Sized> Box<T> {
+    pub fn fromRaw(raw: *mut T) -> Self { Self::fromRawIn(raw, LibcAllocator) }
+}
+
+impl<T, A: Allocator> Box<T, A> {
+    /// Allocates memory from `alloc` and then places `x` into it.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn newIn(x: T, alloc: A) -> Box<T, A> {
+        match Self::tryNewIn(x, alloc) {
+            Ok(b) => b,
+            Err(e) => handleAllocError(e),
+        }
+    }
+
+    /// Same as `newIn`, but returns an error instead of calling the out of memory handler.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn tryNewIn(x: T, alloc: A) -> Result<Box<T, A>, AllocError> {
         unsafe {
-            let addr = alloc::<T>();
-            *addr = x;
-            Self { uptr: Unique::new(addr) }
+            let size = ::core::mem::size_of::<T>();
+            let align = ::core::mem::align_of::<T>();
+            let addr =
+                if size == 0 { ::core::ptr::NonNull::dangling().as_ptr() }
+                else { alloc.alloc(size, align) as *mut T };
+            if addr.is_null() { return Err(AllocError { size, align }) }
+            addr.write(x);
+            Ok(Self { uptr: Unique::new(addr), alloc })
         }
     }
 
+    pub fn unbox(self) -> T {
+        unsafe {
+            let (ptr, alloc) = self.intoRawWithAllocator();
+            let v = ptr.read();
+            freeBoxed(&alloc, ptr);
+            v
+        }
+    }
+}
+
+// zero sized values are never allocated (see `tryNewIn`), so they are never freed either
+unsafe fn freeBoxed<T: ?Sized, A: Allocator>(alloc: &A, ptr: *mut T) {
+    let size = ::core::mem::size_of_val(&*ptr);
+    if size != 0 {
+        alloc.free(ptr as *mut u8, size, ::core::mem::align_of_val(&*ptr));
+    }
+}
+
+impl<T: ?Sized, A: Allocator> Box<T, A> {
+    /// Takes ownership of `raw`, which must have been allocated by `alloc`.
+    pub fn fromRawIn(raw: *mut T, alloc: A) -> Self {
+        Self { uptr: Unique::new(raw), alloc }
+    }
+
+    pub fn allocator(&self) -> &A { &self.alloc }
+
     pub fn asRef(&self) -> &T { unsafe { &(*self.uptr.getPtr()) } }
-    pub fn asMut(&mut self) -> &T { unsafe { &mut (*self.uptr.getMutPtr()) } }
+    pub fn asMut(&mut self) -> &mut T { unsafe { &mut (*self.uptr.getMutPtr()) } }
     pub fn intoRaw(self) -> *mut T {
         let m = ::core::mem::ManuallyDrop::new(self);
         m.uptr.ptr
     }
 
-    pub fn fromRaw(raw: *mut T) -> Self {
-        Self { uptr: Unique::new(raw) }
+    pub fn intoRawWithAllocator(self) -> (*mut T, A) {
+        let m = ::core::mem::ManuallyDrop::new(self);
+        (m.uptr.ptr, unsafe { ::core::ptr::read(&m.alloc) })
     }
+}
 
-    pub fn unbox(self) -> T {
-        unsafe {
-            let ptr = self.uptr.ptr;
-            let v = self.intoRaw().read();
-            free(ptr);
-            v
-        }
+impl<T, A: Allocator> Box<[T], A> {
+    /// Turn the boxed slice back into a `Vec` (without copying).
+    pub fn intoVec(self) -> crate::vec::Vec<T, A> {
+        let (ptr, alloc) = self.intoRawWithAllocator();
+        let len = unsafe { (&*ptr).len() };
+        unsafe { crate::vec::Vec::fromRawPartsIn(ptr as *mut T, len, len, alloc) }
     }
 }
 
-impl<T> Drop for Box<T> {
+impl<T: ?Sized, A: Allocator> ::core::ops::Deref for Box<T, A> {
+    type Target = T;
+    fn deref(&self) -> &T { self.asRef() }
+}
+
+impl<T: ?Sized, A: Allocator> ::core::ops::DerefMut for Box<T, A> {
+    fn deref_mut(&mut self) -> &mut T { self.asMut() }
+}
+
+impl<T: ?Sized + crate::hash::Hash, A: Allocator> crate::hash::Hash for Box<T, A> {
+    fn hash<H: crate::hash::Hasher>(&self, state: &mut H) { self.asRef().hash(state) }
+}
+
+impl<T: ?Sized, A: Allocator> Drop for Box<T, A> {
     fn drop(&mut self) {
         unsafe {
             let addr = self.uptr.getMutPtr();
             ::core::ptr::drop_in_place(addr);
-            free(addr);
+            freeBoxed(&self.alloc, addr);
         }
     }
 }
 
-
+/// Convert a `Box<T, A>` into a `Box<U, A>` where `*mut T` coerces to `*mut U`
+/// (`Box<dyn Trait>` from `Box<Concrete>`, `Box<[T]>` from `Box<[T; N]>`).
+///
+/// # Examples
+///
+/// ```
+/// use alt_std::*;
+/// let b : Box<dyn core::fmt::Display> = unsizeBox!(Box::new(5), dyn core::fmt::Display);
+/// ```
+#[macro_export]
+macro_rules! unsizeBox {
+    ($b:expr, $t:ty) => {{
+        let (ptr, alloc) = $crate::mem::Box::intoRawWithAllocator($b);
+        let ptr : *mut $t = ptr;
+        $crate::mem::Box::fromRawIn(ptr, alloc)
+    }};
+}
 
 #[cfg(test)]
 mod tests {
@@ -104,56 +412,351 @@ mod tests {
 
     #[test]
     fn testDrop() {
-        let _b0 = Box::new(1234);
-        let _b1 = Box::new(1234345);
-        let mut v = crate::vec::Vec::new();
-        for i in 0..100 {
-            v.pushBack(i);
-        }
-        let _bv = Box::new(v);
+        crate::mem::assertNoLeaks(|| {
+            let _b0 = Box::new(1234);
+            let _b1 = Box::new(1234345);
+            let mut v = crate::vec::Vec::new();
+            for i in 0..100 {
+                v.pushBack(i);
+            }
+            let _bv = Box::new(v);
+        });
     }
 
     #[test]
     fn testDropVecVec() {
-        let _b0 = Box::new(1234);
-        let _b1 = Box::new(1234345);
-        let mut v = crate::vec::Vec::new();
-        for _ in 0..100 {
-            let mut vj = crate::vec::Vec::new();
-            for j in 0..100 {
-                vj.pushBack(j);
+        crate::mem::assertNoLeaks(|| {
+            let _b0 = Box::new(1234);
+            let _b1 = Box::new(1234345);
+            let mut v = crate::vec::Vec::new();
+            for _ in 0..100 {
+                let mut vj = crate::vec::Vec::new();
+                for j in 0..100 {
+                    vj.pushBack(j);
+                }
+                v.pushBack(vj);
             }
-            v.pushBack(vj);
-        }
-        let _bv = Box::new(v);
+            let _bv = Box::new(v);
+        });
     }
 
     #[test]
     fn testBoxUnbox() {
-        let b = Box::new(1234);
-        let _v = b.unbox();
+        crate::mem::assertNoLeaks(|| {
+            let b = Box::new(1234);
+            let _v = b.unbox();
+        });
     }
 
     #[test]
     fn testBoxUnboxVecVec() {
-        let _b0 = Box::new(1234);
-        let _b1 = Box::new(1234345);
+        crate::mem::assertNoLeaks(|| {
+            let _b0 = Box::new(1234);
+            let _b1 = Box::new(1234345);
+            let mut v = crate::vec::Vec::new();
+            for _ in 0..100 {
+                let mut vj = crate::vec::Vec::new();
+                for j in 0..100 {
+                    vj.pushBack(j);
+                }
+                v.pushBack(vj);
+            }
+            let v2 = Box::new(v);
+            let _v3 = v2.unbox();
+        });
+    }
+
+    #[test]
+    fn testBoxFromToRaw() {
+        crate::mem::assertNoLeaks(|| {
+            let b = Box::new(1234);
+            let r = b.intoRaw();
+            let _b = Box::fromRaw(r);
+        });
+    }
+
+    struct CountingAllocator {
+        allocs  : ::core::cell::Cell<usize>,
+        frees   : ::core::cell::Cell<usize>,
+    }
+
+    impl CountingAllocator {
+        fn new() -> Self { Self { allocs: ::core::cell::Cell::new(0), frees: ::core::cell::Cell::new(0) } }
+    }
+
+    impl Allocator for CountingAllocator {
+        unsafe fn alloc(&self, size: usize, align: usize) -> *mut u8 {
+            self.allocs.set(self.allocs.get() + 1);
+            LibcAllocator.alloc(size, align)
+        }
+
+        unsafe fn free(&self, ptr: *mut u8, size: usize, align: usize) {
+            self.frees.set(self.frees.get() + 1);
+            LibcAllocator.free(ptr, size, align)
+        }
+    }
+
+    #[test]
+    fn testCustomAllocator() {
+        let a = CountingAllocator::new();
+        {
+            let b = Box::newIn(1234, &a);
+            assert!(*b.asRef() == 1234);
+            let mut v = crate::vec::Vec::newIn(&a);
+            for i in 0..100 {
+                v.pushBack(i);
+            }
+            let mut s = crate::string::String::newIn(&a);
+            s.add(b'a');
+            let mut hm = crate::hashmap::HashMap::newIn(&a);
+            for i in 0..100 {
+                hm.set(i, i);
+            }
+            assert!(b.unbox() == 1234);
+        }
+        assert!(a.allocs.get() > 0);
+        assert!(a.allocs.get() == a.frees.get());
+    }
+
+    #[repr(align(16))]
+    #[derive(Clone, Copy, PartialEq)]
+    struct Align16(u8);
+    #[repr(align(32))]
+    #[derive(Clone, Copy, PartialEq)]
+    struct Align32(u8);
+    #[repr(align(64))]
+    #[derive(Clone, Copy, PartialEq)]
+    struct Align64(u8);
+    #[repr(align(4096))]
+    #[derive(Clone, Copy, PartialEq)]
+    struct Align4096(u8);
+
+    impl crate::hash::Hash for Align64 {
+        fn hash<H: crate::hash::Hasher>(&self, state: &mut H) { state.writeU8(self.0) }
+    }
+
+    fn isAligned<T>(p: *const T) -> bool { (p as usize) & (::core::mem::align_of::<T>() - 1) == 0 }
+
+    fn checkAlignment<T: Copy + PartialEq>(t: T) {
+        unsafe {
+            let p = alloc::<T>();
+            assert!(isAligned(p));
+            free(p);
+
+            let p = allocArray::<T>(7);
+            assert!(isAligned(p));
+            free(p);
+        }
+
+        let b = Box::new(t);
+        assert!(isAligned(b.asRef()));
+
         let mut v = crate::vec::Vec::new();
         for _ in 0..100 {
-            let mut vj = crate::vec::Vec::new();
-            for j in 0..100 {
-                vj.pushBack(j);
-            }
-            v.pushBack(vj);
+            v.pushBack(t);
+            assert!(isAligned(&v[0]));
+            assert!(isAligned(v.last().unwrap()));
+        }
+        let v2 = v.clone();
+        assert!(isAligned(&v2[0]));
+        for i in 0..100 {
+            assert!(v2[i] == t);
         }
-        let v2 = Box::new(v);
-        let _v3 = v2.unbox();
     }
 
     #[test]
-    fn testBoxFromToRaw() {
-        let b = Box::new(1234);
-        let r = b.intoRaw();
-        let _b = Box::fromRaw(r);
+    fn testAlignedAlloc() {
+        checkAlignment(Align16(16));
+        checkAlignment(Align32(32));
+        checkAlignment(Align64(64));
+        checkAlignment(Align4096(1));
+    }
+
+    #[test]
+    fn testAlignedRealloc() {
+        unsafe {
+            let p = allocRawAligned(100, 4096);
+            assert!(p as usize & 4095 == 0);
+            for i in 0..100 { *p.add(i) = i as u8 }
+            let p = reallocRawAligned(p, 100, 10000, 4096);
+            assert!(p as usize & 4095 == 0);
+            for i in 0..100 { assert!(*p.add(i) == i as u8) }
+            freeRaw(p);
+        }
+    }
+
+    #[test]
+    fn testAlignedHashMap() {
+        let mut hm = crate::hashmap::HashMap::<Align64, Align4096>::new();
+        for i in 0..100 {
+            hm.set(Align64(i), Align4096(i));
+        }
+        for i in 0..100 {
+            let v = hm.get(&Align64(i)).unwrap();
+            assert!(isAligned(v));
+            assert!(v.0 == i);
+        }
+    }
+
+    // fails every allocation once `budget` bytes were handed out
+    struct LimitAllocator {
+        budget  : ::core::cell::Cell<usize>,
+    }
+
+    impl Allocator for LimitAllocator {
+        unsafe fn alloc(&self, size: usize, align: usize) -> *mut u8 {
+            if size > self.budget.get() { return ::core::ptr::null_mut() }
+            self.budget.set(self.budget.get() - size);
+            LibcAllocator.alloc(size, align)
+        }
+
+        unsafe fn free(&self, ptr: *mut u8, size: usize, align: usize) {
+            LibcAllocator.free(ptr, size, align)
+        }
+    }
+
+    #[test]
+    fn testTryAlloc() {
+        let a = LimitAllocator { budget: ::core::cell::Cell::new(1024) };
+
+        assert!(Box::tryNewIn([0u8; 2048], &a).is_err());
+        let b = Box::tryNewIn([1u8; 512], &a).unwrap();
+        assert!(b.asRef()[511] == 1);
+
+        let mut v = crate::vec::Vec::<u8, _>::newIn(&a);
+        assert!(v.tryReserve(1024).is_err());
+        for i in 0..256 {
+            assert!(v.tryPushBack(i as u8).is_ok());
+        }
+        assert!(v.tryPushBack(0).is_err());
+        assert!(v.len() == 256);
+        assert!(v[255] == 255);
+
+        a.budget.set(0);
+        let mut s = crate::string::String::newIn(&a);
+        assert!(s.tryAppend("hello").is_err());
+        assert!(s.len() == 0);
+
+        let mut hm = crate::hashmap::HashMap::<i32, i32, _>::newIn(&a);
+        assert!(hm.trySet(1, 1).is_err());
+        assert!(hm.count() == 0);
+        assert!(hm.get(&1).is_none());
+    }
+
+    #[test]
+    fn testTryReserveOverflow() {
+        let mut v = crate::vec::Vec::<u64>::new();
+        assert!(v.tryReserve(usize::MAX).is_err());
+        assert!(v.tryReserveExact(usize::MAX / 4).is_err());
+        v.pushBack(1);
+        assert!(v[0] == 1);
+    }
+
+    fn customOomHandler(_err: AllocError) -> ! {
+        panic!("custom oom handler")
+    }
+
+    // the handler is process wide, put the previous one back even when the test panics
+    struct RestoreOomHandler(OomHandler);
+
+    impl Drop for RestoreOomHandler {
+        fn drop(&mut self) { setOomHandler(self.0); }
+    }
+
+    #[test]
+    #[should_panic(expected = "custom oom handler")]
+    fn testOomHandler() {
+        let _restore = RestoreOomHandler(setOomHandler(customOomHandler));
+        let a = LimitAllocator { budget: ::core::cell::Cell::new(0) };
+        let _b = Box::newIn(1234, &a);
+    }
+
+    trait Shape {
+        fn area(&self) -> usize;
+    }
+
+    struct Square(usize, crate::vec::Vec<usize>);
+    struct Rect(usize, usize);
+
+    impl Shape for Square {
+        fn area(&self) -> usize { self.0 * self.1[0] }
+    }
+
+    impl Shape for Rect {
+        fn area(&self) -> usize { self.0 * self.1 }
+    }
+
+    #[test]
+    fn testBoxDyn() {
+        crate::mem::assertNoLeaks(|| {
+            let mut shapes = crate::vec::Vec::<Box<dyn Shape>>::new();
+            for i in 0..100 {
+                let mut v = crate::vec::Vec::new();
+                v.pushBack(i);
+                shapes.pushBack(unsizeBox!(Box::new(Square(i, v)), dyn Shape));
+                shapes.pushBack(unsizeBox!(Box::new(Rect(i, 2)), dyn Shape));
+            }
+
+            for i in 0..100 {
+                assert!(shapes[i * 2].area() == i * i);
+                assert!(shapes[i * 2 + 1].area() == i * 2);
+            }
+        });
+    }
+
+    #[test]
+    fn testBoxDynStream() {
+        use crate::stream::*;
+        let mut r : Box<dyn StreamReader> = unsizeBox!(Box::new(MemoryStreamReader::from("hello".as_bytes())), dyn StreamReader);
+        let mut buff = [0u8; 5];
+        assert!(r.read(&mut buff) == Ok(5));
+        assert!(r.isEOF());
+        assert!(&buff == b"hello");
+    }
+
+    #[test]
+    fn testBoxSlice() {
+        crate::mem::assertNoLeaks(|| {
+            let mut v = crate::vec::Vec::new();
+            for i in 0..100 {
+                let mut vi = crate::vec::Vec::new();
+                vi.pushBack(i);
+                v.pushBack(vi);
+            }
+
+            let mut b = v.intoBoxedSlice();
+            assert!(b.len() == 100);
+            b[10].pushBack(11);
+            assert!(b[10][1] == 11);
+
+            let v = b.intoVec();
+            assert!(v.len() == 100 && v.capacity() == 100);
+            assert!(v[99][0] == 99);
+
+            let empty = crate::vec::Vec::<crate::vec::Vec<i32>>::new().intoBoxedSlice();
+            assert!(empty.is_empty());
+
+            let arr : Box<[i32]> = unsizeBox!(Box::new([1, 2, 3]), [i32]);
+            assert!(arr.len() == 3 && arr[2] == 3);
+        });
+    }
+
+    #[test]
+    fn testBoxStr() {
+        crate::mem::assertNoLeaks(|| {
+            let mut s = crate::string::String::from("hello");
+            s.add(b'!');
+            let b = s.intoBoxedStr();
+            assert!(&*b == "hello!");
+            let b = crate::string::String::new().intoBoxedStr();
+            assert!(b.is_empty());
+        });
+    }
+
+    #[test]
+    fn testBoxZeroSized() {
+        let b = Box::new(());
+        b.unbox();
+        let _b : Box<[u8]> = unsizeBox!(Box::new([0u8; 0]), [u8]);
     }
 }
@@ -0,0 +1,333 @@
+//
+// Atomic reference counting (Arc/Weak)
+//
+use core::sync::atomic::{AtomicUsize, Ordering, fence};
+use core::ptr;
+use crate::mem::*;
+
+// weak == LOCKED while `isUnique` checks the strong count
+const LOCKED : usize = usize::MAX;
+
+struct ArcInner<T> {
+    strong  : AtomicUsize,
+    weak    : AtomicUsize,  // weak references + 1 held collectively by the strong ones
+    value   : T,
+}
+
+/// Thread safe shared ownership of a `T` allocated from `A`.
+pub struct Arc<T, A: Allocator + Clone = LibcAllocator> {
+    ptr     : *mut ArcInner<T>,
+    alloc   : A,
+}
+
+/// Non owning reference to an `Arc` allocation, see `Arc::downgrade` and `Weak::upgrade`.
+pub struct Weak<T, A: Allocator + Clone = LibcAllocator> {
+    ptr     : *mut ArcInner<T>,
+    alloc   : A,
+}
+
+unsafe impl<T: Send + Sync, A: Allocator + Clone + Send + Sync> Send for Arc<T, A> {}
+unsafe impl<T: Send + Sync, A: Allocator + Clone + Send + Sync> Sync for Arc<T, A> {}
+unsafe impl<T: Send + Sync, A: Allocator + Clone + Send + Sync> Send for Weak<T, A> {}
+unsafe impl<T: Send + Sync, A: Allocator + Clone + Send + Sync> Sync for Weak<T, A> {}
+
+unsafe fn freeArcInner<T, A: Allocator>(alloc: &A, ptr: *mut ArcInner<T>) {
+    alloc.free(ptr as *mut u8, core::mem::size_of::<ArcInner<T>>(), core::mem::align_of::<ArcInner<T>>())
+}
+
+impl<T> Arc<T> {
+    pub fn new(value: T) -> Self { Self::newIn(value, LibcAllocator) }
+}
+
+impl<T, A: Allocator + Clone> Arc<T, A> {
+    pub fn newIn(value: T, alloc: A) -> Self {
+        let size    = core::mem::size_of::<ArcInner<T>>();
+        let align   = core::mem::align_of::<ArcInner<T>>();
+        let ptr     = unsafe { alloc.alloc(size, align) as *mut ArcInner<T> };
+        if ptr.is_null() { handleAllocError(AllocError { size, align }) }
+        unsafe { ptr.write(ArcInner { strong: AtomicUsize::new(1), weak: AtomicUsize::new(1), value }) };
+        Self { ptr, alloc }
+    }
+
+    #[inline]
+    fn inner(&self) -> &ArcInner<T> { unsafe { &*self.ptr } }
+
+    pub fn strongCount(this: &Self) -> usize { this.inner().strong.load(Ordering::SeqCst) }
+    pub fn weakCount(this: &Self) -> usize {
+        match this.inner().weak.load(Ordering::SeqCst) {
+            LOCKED  => 0,
+            w       => w - 1,
+        }
+    }
+
+    /// true if both point to the same allocation
+    pub fn ptrEq(this: &Self, other: &Self) -> bool { this.ptr == other.ptr }
+
+    pub fn downgrade(this: &Self) -> Weak<T, A> {
+        let weak = &this.inner().weak;
+        let mut cur = weak.load(Ordering::Relaxed);
+        loop {
+            // spin while `isUnique` holds the weak count
+            if cur == LOCKED {
+                core::hint::spin_loop();
+                cur = weak.load(Ordering::Relaxed);
+                continue;
+            }
+
+            match weak.compare_exchange_weak(cur, cur + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_)   => return Weak { ptr: this.ptr, alloc: this.alloc.clone() },
+                Err(old)=> cur = old,
+            }
+        }
+    }
+
+    // true if there is no other Arc or Weak to this allocation
+    fn isUnique(&mut self) -> bool {
+        // lock the weak count so no Weak can be created (from another Arc) while checking the strong one
+        if self.inner().weak.compare_exchange(1, LOCKED, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+            let unique = self.inner().strong.load(Ordering::Acquire) == 1;
+            self.inner().weak.store(1, Ordering::Release);
+            unique
+        } else {
+            false
+        }
+    }
+
+    /// Mutable access to the value, only if there are no other `Arc` or `Weak` to it.
+    pub fn getMut(this: &mut Self) -> Option<&mut T> {
+        if this.isUnique() {
+            Some(unsafe { &mut (*this.ptr).value })
+        } else {
+            None
+        }
+    }
+
+    /// Copy on write: mutable access to the value, cloning it into a new allocation first
+    /// if it is shared with other `Arc`. If only `Weak` are left, the value is moved to a
+    /// new allocation instead and they can't be upgraded anymore.
+    pub fn makeMut(this: &mut Self) -> &mut T where T: Clone {
+        // taking the strong count to 0 keeps the Weak from upgrading while we look at them
+        if this.inner().strong.compare_exchange(1, 0, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            *this = Arc::newIn(this.inner().value.clone(), this.alloc.clone());
+        } else if this.inner().weak.load(Ordering::Relaxed) != 1 {
+            unsafe {
+                let value   = ptr::read(&this.inner().value);
+                let alloc   = this.alloc.clone();
+                // the old allocation is left to the Weak, with the reference the strong ones held
+                let old     = core::mem::ManuallyDrop::new(core::mem::replace(this, Arc::newIn(value, alloc)));
+                drop(Weak { ptr: old.ptr, alloc: ptr::read(&old.alloc) });
+            }
+        } else {
+            this.inner().strong.store(1, Ordering::Release);
+        }
+        unsafe { &mut (*this.ptr).value }
+    }
+
+    /// Returns the value if `this` is the only strong reference, otherwise gives `this` back.
+    pub fn tryUnwrap(this: Self) -> Result<T, Self> {
+        if this.inner().strong.compare_exchange(1, 0, Ordering::Relaxed, Ordering::Relaxed).is_err() {
+            return Err(this);
+        }
+        fence(Ordering::Acquire);
+
+        unsafe {
+            let this    = core::mem::ManuallyDrop::new(this);
+            let value   = ptr::read(&this.inner().value);
+            let alloc   = ptr::read(&this.alloc);
+
+            // release the weak reference held by the strong ones
+            if this.inner().weak.fetch_sub(1, Ordering::Release) == 1 {
+                fence(Ordering::Acquire);
+                freeArcInner(&alloc, this.ptr);
+            }
+            Ok(value)
+        }
+    }
+}
+
+impl<T, A: Allocator + Clone> Clone for Arc<T, A> {
+    fn clone(&self) -> Self {
+        self.inner().strong.fetch_add(1, Ordering::Relaxed);
+        Self { ptr: self.ptr, alloc: self.alloc.clone() }
+    }
+}
+
+impl<T, A: Allocator + Clone> core::ops::Deref for Arc<T, A> {
+    type Target = T;
+    fn deref(&self) -> &T { &self.inner().value }
+}
+
+impl<T, A: Allocator + Clone> Drop for Arc<T, A> {
+    fn drop(&mut self) {
+        if self.inner().strong.fetch_sub(1, Ordering::Release) != 1 { return }
+
+        fence(Ordering::Acquire);
+        unsafe {
+            ptr::drop_in_place(&mut (*self.ptr).value);
+
+            if self.inner().weak.fetch_sub(1, Ordering::Release) == 1 {
+                fence(Ordering::Acquire);
+                freeArcInner(&self.alloc, self.ptr);
+            }
+        }
+    }
+}
+
+impl<T, A: Allocator + Clone> Weak<T, A> {
+    /// Get a strong reference back, or `None` if the value was already dropped.
+    pub fn upgrade(&self) -> Option<Arc<T, A>> {
+        let strong = unsafe { &(*self.ptr).strong };
+        let mut cur = strong.load(Ordering::Relaxed);
+        loop {
+            if cur == 0 { return None }
+
+            match strong.compare_exchange_weak(cur, cur + 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_)   => return Some(Arc { ptr: self.ptr, alloc: self.alloc.clone() }),
+                Err(old)=> cur = old,
+            }
+        }
+    }
+
+    pub fn strongCount(&self) -> usize { unsafe { (*self.ptr).strong.load(Ordering::SeqCst) } }
+
+    pub fn ptrEq(&self, other: &Self) -> bool { self.ptr == other.ptr }
+}
+
+impl<T, A: Allocator + Clone> Clone for Weak<T, A> {
+    fn clone(&self) -> Self {
+        unsafe { (*self.ptr).weak.fetch_add(1, Ordering::Relaxed) };
+        Self { ptr: self.ptr, alloc: self.alloc.clone() }
+    }
+}
+
+impl<T, A: Allocator + Clone> Drop for Weak<T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            if (*self.ptr).weak.fetch_sub(1, Ordering::Release) == 1 {
+                fence(Ordering::Acquire);
+                freeArcInner(&self.alloc, self.ptr);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::vec::*;
+    use crate::string::*;
+    use crate::hashmap::*;
+
+    fn table() -> HashMap<String, Vec<u8>> {
+        let mut hm = HashMap::new();
+        for i in 0..100 {
+            let mut v = Vec::new();
+            for j in 0..i {
+                v.pushBack(j as u8);
+            }
+            hm.set(crate::format!("key{}", i), v);
+        }
+        hm
+    }
+
+    #[test]
+    fn testSharedTable() {
+        let t = Arc::new(table());
+        let mut threads = std::vec::Vec::new();
+        for _ in 0..8 {
+            let t = t.clone();
+            threads.push(std::thread::spawn(move || {
+                for i in 0..100 {
+                    let v = t.get(&crate::format!("key{}", i)).unwrap();
+                    assert!(v.len() == i);
+                }
+            }));
+        }
+
+        for th in threads {
+            th.join().unwrap();
+        }
+        assert!(Arc::strongCount(&t) == 1);
+    }
+
+    #[test]
+    fn testCounts() {
+        let mut threads = std::vec::Vec::new();
+        let a = Arc::new(1234);
+        let w = Arc::downgrade(&a);
+        for _ in 0..8 {
+            let w = w.clone();
+            threads.push(std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    let a = w.upgrade().unwrap();
+                    let _w = Arc::downgrade(&a);
+                    assert!(*a == 1234);
+                }
+            }));
+        }
+
+        for th in threads {
+            th.join().unwrap();
+        }
+        assert!(Arc::strongCount(&a) == 1);
+        assert!(Arc::weakCount(&a) == 1);
+        drop(a);
+        assert!(w.upgrade().is_none());
+    }
+
+    #[test]
+    fn testMakeMut() {
+        let mut snapshot = Arc::new(table());
+        let old = snapshot.clone();
+
+        // shared: makeMut copies the table
+        Arc::makeMut(&mut snapshot).set(String::from("new"), Vec::new());
+        assert!(!Arc::ptrEq(&snapshot, &old));
+        assert!(snapshot.exist("new"));
+        assert!(!old.exist("new"));
+        assert!(snapshot.count() == 101 && old.count() == 100);
+
+        // unique: makeMut updates in place
+        let before = &*snapshot as *const HashMap<String, Vec<u8>>;
+        Arc::makeMut(&mut snapshot).remove("new");
+        assert!(core::ptr::eq(&*snapshot, before));
+        assert!(snapshot.count() == 100);
+
+        // a weak reference prevents in place updates too, the table moves without a copy
+        let w = Arc::downgrade(&snapshot);
+        assert!(Arc::getMut(&mut snapshot).is_none());
+        Arc::makeMut(&mut snapshot);
+        assert!(w.upgrade().is_none() && w.strongCount() == 0);
+        assert!(snapshot.count() == 100 && Arc::weakCount(&snapshot) == 0);
+    }
+
+    #[test]
+    fn testMakeMutWeak() {
+        struct NoClone(Vec<i32>);
+        impl Clone for NoClone {
+            fn clone(&self) -> Self { panic!("makeMut shouldn't clone") }
+        }
+
+        crate::mem::assertNoLeaks(|| {
+            let mut a = Arc::new(NoClone(Vec::new()));
+            let w = Arc::downgrade(&a);
+            let w2 = w.clone();
+            Arc::makeMut(&mut a).0.pushBack(1);
+            assert!(w.upgrade().is_none() && a.0[0] == 1);
+            drop(w);
+            drop(a);
+            drop(w2);
+        });
+    }
+
+    #[test]
+    fn testTryUnwrap() {
+        let a = Arc::new(String::from("hello"));
+        let b = a.clone();
+        let a = Arc::tryUnwrap(a).err().unwrap();
+        drop(b);
+        assert!(Arc::tryUnwrap(a).ok().unwrap() == "hello");
+    }
+}
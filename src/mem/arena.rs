@@ -0,0 +1,250 @@
+//
+// Bump allocator: every allocation is carved out of big chunks that are only
+// released all at once (`reset` or drop)
+//
+use core::cell::Cell;
+use core::ptr;
+use crate::mem::*;
+use crate::vec::Vec;
+use crate::string::String;
+
+#[repr(C)]
+struct Chunk {
+    next    : *mut Chunk,   // previous (older) chunk
+    size    : usize,        // data size, the data follows the header
+}
+
+const HEADER_SIZE   : usize = core::mem::size_of::<Chunk>();
+const DEFAULT_CHUNK : usize = 4096 - HEADER_SIZE;
+
+/// Bump allocator, use `&Arena` as the allocator of the containers
+/// (`newVec`, `newString`, `newBox`) so they can't outlive it.
+///
+/// `free` only gives memory back when it is the last allocation, everything
+/// else is released by `reset` or when the arena is dropped.
+pub struct Arena {
+    chunks      : Cell<*mut Chunk>,
+    cursor      : Cell<usize>,
+    end         : Cell<usize>,
+    chunkSize   : Cell<usize>,
+    last        : Cell<usize>,  // address of the last allocation
+}
+
+impl Arena {
+    pub fn new() -> Self { Self::withChunkSize(DEFAULT_CHUNK) }
+
+    /// `chunkSize` is the size of the first chunk, the following ones double in size.
+    pub fn withChunkSize(chunkSize: usize) -> Self {
+        Self {
+            chunks      : Cell::new(ptr::null_mut()),
+            cursor      : Cell::new(0),
+            end         : Cell::new(0),
+            chunkSize   : Cell::new(usize::max(chunkSize, 16)),
+            last        : Cell::new(0),
+        }
+    }
+
+    pub fn newVec<T>(&self) -> Vec<T, &Self> { Vec::newIn(self) }
+    pub fn newString(&self) -> String<&Self> { String::newIn(self) }
+    pub fn newBox<T>(&self, x: T) -> Box<T, &Self> { Box::newIn(x, self) }
+
+    /// Number of backing chunks
+    pub fn chunkCount(&self) -> usize {
+        let mut count = 0;
+        let mut c = self.chunks.get();
+        while !c.is_null() {
+            count += 1;
+            c = unsafe { (*c).next };
+        }
+        count
+    }
+
+    #[inline]
+    fn alignUp(addr: usize, align: usize) -> Option<usize> {
+        Some(addr.checked_add(align - 1)? & !(align - 1))
+    }
+
+    fn newChunk(&self, size: usize, align: usize) -> bool {
+        let dataSize = match size.checked_add(align) {
+            Some(s) => usize::max(s, self.chunkSize.get()),
+            None => return false,
+        };
+        let chunk = unsafe { allocRaw(HEADER_SIZE + dataSize) as *mut Chunk };
+        if chunk.is_null() { return false }
+
+        unsafe {
+            (*chunk).next = self.chunks.get();
+            (*chunk).size = dataSize;
+        }
+        self.chunks.set(chunk);
+        self.cursor.set(chunk as usize + HEADER_SIZE);
+        self.end.set(chunk as usize + HEADER_SIZE + dataSize);
+        self.chunkSize.set(self.chunkSize.get().saturating_mul(2));
+        true
+    }
+
+    fn bump(&self, size: usize, align: usize) -> Option<*mut u8> {
+        let start = Self::alignUp(self.cursor.get(), align)?;
+        let end = start.checked_add(size)?;
+        if self.chunks.get().is_null() || end > self.end.get() { return None }
+        self.cursor.set(end);
+        self.last.set(start);
+        Some(start as *mut u8)
+    }
+
+    /// Release all the allocations. The most recent chunk is kept for reuse.
+    pub fn reset(&mut self) {
+        let head = self.chunks.get();
+        if head.is_null() { return }
+
+        unsafe {
+            let mut c = (*head).next;
+            while !c.is_null() {
+                let next = (*c).next;
+                freeRaw(c as *mut u8);
+                c = next;
+            }
+            (*head).next = ptr::null_mut();
+        }
+        self.cursor.set(head as usize + HEADER_SIZE);
+        self.last.set(0);
+    }
+}
+
+impl Default for Arena {
+    fn default() -> Self { Self::new() }
+}
+
+impl Allocator for Arena {
+    unsafe fn alloc(&self, size: usize, align: usize) -> *mut u8 {
+        match self.bump(size, align) {
+            Some(p) => p,
+            None => {
+                if !self.newChunk(size, align) { return ptr::null_mut() }
+                self.bump(size, align).unwrap_or(ptr::null_mut())
+            }
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, oldSize: usize, newSize: usize, align: usize) -> *mut u8 {
+        // the last allocation can grow/shrink in place
+        if ptr as usize == self.last.get() && ptr as usize + newSize <= self.end.get() {
+            self.cursor.set(ptr as usize + newSize);
+            return ptr;
+        }
+
+        let newPtr = self.alloc(newSize, align);
+        if !newPtr.is_null() {
+            ptr::copy_nonoverlapping(ptr, newPtr, core::cmp::min(oldSize, newSize));
+        }
+        newPtr
+    }
+
+    unsafe fn free(&self, ptr: *mut u8, size: usize, _align: usize) {
+        // only the last allocation can be given back
+        if ptr as usize == self.last.get() && ptr as usize + size == self.cursor.get() {
+            self.cursor.set(ptr as usize);
+            self.last.set(0);
+        }
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        let mut c = self.chunks.get();
+        while !c.is_null() {
+            unsafe {
+                let next = (*c).next;
+                freeRaw(c as *mut u8);
+                c = next;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string::Append;
+
+    #[test]
+    fn testArenaContainers() {
+        let arena = Arena::new();
+        let mut strings = arena.newVec();
+        for i in 0..1000 {
+            let mut s = arena.newString();
+            s.append("item");
+            core::fmt::Write::write_fmt(&mut s, format_args!("{}", i)).unwrap();
+            strings.pushBack(s);
+        }
+
+        let mut v = arena.newVec();
+        for i in 0..10000 {
+            v.pushBack(i);
+        }
+        let b = arena.newBox(v);
+
+        for i in 0..1000 {
+            assert!(strings[i] == crate::format!("item{}", i));
+        }
+        for i in 0..10000 {
+            assert!(b[i] == i);
+        }
+        assert!(arena.chunkCount() > 1);
+    }
+
+    #[test]
+    fn testArenaDrop() {
+        // destructors still run for the values stored in the arena containers
+        let arena = Arena::new();
+        let rc = crate::mem::Rc::new(0);
+        {
+            let mut v = arena.newVec();
+            for _ in 0..100 {
+                v.pushBack(rc.clone());
+            }
+            let _b = arena.newBox(rc.clone());
+            assert!(crate::mem::Rc::strongCount(&rc) == 102);
+        }
+        assert!(crate::mem::Rc::strongCount(&rc) == 1);
+    }
+
+    #[test]
+    fn testArenaReset() {
+        let mut arena = Arena::withChunkSize(64);
+        for _ in 0..100 {
+            unsafe { arena.alloc(48, 8) };
+        }
+        assert!(arena.chunkCount() > 1);
+
+        arena.reset();
+        assert!(arena.chunkCount() == 1);
+        // the kept chunk is the largest one, so this fits without a new chunk
+        for _ in 0..8 {
+            assert!(!unsafe { arena.alloc(48, 8) }.is_null());
+        }
+        assert!(arena.chunkCount() == 1);
+    }
+
+    #[test]
+    fn testArenaAlignment() {
+        let arena = Arena::withChunkSize(100);
+        for align in [1, 2, 8, 64, 4096].iter() {
+            let p = unsafe { arena.alloc(3, *align) };
+            assert!(p as usize & (align - 1) == 0);
+        }
+    }
+
+    #[test]
+    fn testArenaLastAllocation() {
+        let arena = Arena::withChunkSize(1024);
+        unsafe {
+            let a = arena.alloc(16, 8);
+            let b = arena.realloc(a, 16, 64, 8);
+            assert!(a == b);    // grown in place
+            arena.free(b, 64, 8);
+            let c = arena.alloc(16, 8);
+            assert!(a == c);    // given back
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//
+// Leak detecting/statistics allocator ("debug-alloc" feature)
+//
+// Every block gets a header (size, call site, thread, serial) and is linked in
+// a global list of live allocations. Freed blocks are held back in a small
+// quarantine before going back to the system, so a double free of a recent
+// block is reported instead of freeing whatever reused the address. Live
+// headers carry a canary derived from their address, which is how a free
+// tells them apart from foreign pointers without walking the list.
+//
+use core::fmt::Write;
+use core::panic::Location;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+use super::{sys, MIN_ALIGN};
+
+const QUARANTINE_SIZE : usize = 64;
+
+#[repr(C)]
+struct Header {
+    prev        : *mut Header,
+    next        : *mut Header,
+    size        : usize,
+    offset      : usize,    // from the start of the system block to the user pointer
+    canary      : usize,    // `canary(p)` while the block is live
+    serial      : u64,
+    thread      : usize,
+    location    : &'static Location<'static>,
+}
+
+const HEADER_SIZE : usize = (core::mem::size_of::<Header>() + MIN_ALIGN - 1) & !(MIN_ALIGN - 1);
+
+/// Allocation counters, see `stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    pub liveAllocations     : usize,
+    pub liveBytes           : usize,
+    pub peakBytes           : usize,
+    pub totalAllocations    : usize,
+    pub totalFrees          : usize,
+}
+
+// a freed block not yet given back to the system
+#[derive(Clone, Copy)]
+struct Freed {
+    ptr         : *mut u8,
+    block       : *mut u8,
+    allocated   : &'static Location<'static>,
+    freed       : &'static Location<'static>,
+}
+
+struct State {
+    head        : *mut Header,
+    stats       : AllocStats,
+    serial      : u64,
+    quarantine  : [Option<Freed>; QUARANTINE_SIZE],
+    next        : usize,    // next quarantine slot to (re)use
+}
+
+static LOCK         : AtomicBool = AtomicBool::new(false);
+static REGISTERED   : AtomicBool = AtomicBool::new(false);
+static mut STATE    : State = State {
+    head    : ptr::null_mut(),
+    stats   : AllocStats { liveAllocations: 0, liveBytes: 0, peakBytes: 0, totalAllocations: 0, totalFrees: 0 },
+    serial  : 0,
+    quarantine  : [None; QUARANTINE_SIZE],
+    next        : 0,
+};
+
+// spin lock around STATE, nothing in here allocates
+fn withState<R, F: FnOnce(&mut State) -> R>(f: F) -> R {
+    while LOCK.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+        core::hint::spin_loop();
+    }
+    let r = f(unsafe { &mut *ptr::addr_of_mut!(STATE) });
+    LOCK.store(false, Ordering::Release);
+    r
+}
+
+fn currentThread() -> usize { unsafe { libc::pthread_self() as usize } }
+
+// writes straight to stderr, so reporting doesn't allocate
+struct StdErr;
+
+impl Write for StdErr {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        crate::io::fprintStrings(unsafe { crate::io::stderr }, &[s]);
+        Ok(())
+    }
+}
+
+extern "C" fn reportAtExit() {
+    report();
+}
+
+unsafe fn header(p: *mut u8) -> *mut Header { p.sub(HEADER_SIZE) as *mut Header }
+
+fn canary(p: *mut u8) -> usize { p as usize ^ 0xa110_c8ed }
+
+#[track_caller]
+unsafe fn track(size: usize, align: usize, location: &'static Location<'static>) -> *mut u8 {
+    if !REGISTERED.swap(true, Ordering::SeqCst) {
+        libc::atexit(reportAtExit);
+    }
+
+    let align   = usize::max(align, MIN_ALIGN);
+    let offset  = (HEADER_SIZE + align - 1) & !(align - 1);
+    let total   = match offset.checked_add(size) { Some(t) => t, None => return ptr::null_mut() };
+    let block   = sys::allocRawAligned(total, align);
+    if block.is_null() { return ptr::null_mut() }
+
+    let p = block.add(offset);
+    let h = header(p);
+    withState(|st| {
+        st.serial += 1;
+        h.write(Header { prev: ptr::null_mut(), next: st.head, size, offset, canary: canary(p), serial: st.serial, thread: currentThread(), location });
+        if !st.head.is_null() { (*st.head).prev = h }
+        st.head = h;
+
+        st.stats.liveAllocations    += 1;
+        st.stats.liveBytes          += size;
+        st.stats.totalAllocations   += 1;
+        st.stats.peakBytes          = usize::max(st.stats.peakBytes, st.stats.liveBytes);
+    });
+    p
+}
+
+enum Invalid {
+    DoubleFree(Freed),
+    Unknown,
+}
+
+// the header of the live block `p`. The quarantined blocks are still held, so they are looked up
+// first and their headers stay readable; anything else must carry the canary to be trusted
+unsafe fn validate(st: &State, p: *mut u8) -> Result<*mut Header, Invalid> {
+    for i in 1..=QUARANTINE_SIZE {
+        match st.quarantine[(st.next + QUARANTINE_SIZE - i) % QUARANTINE_SIZE] {
+            Some(f) if f.ptr == p => return Err(Invalid::DoubleFree(f)),
+            _ => (),
+        }
+    }
+    let h = header(p);
+    if (*h).canary != canary(p) { return Err(Invalid::Unknown) }
+    Ok(h)
+}
+
+#[track_caller]
+fn invalid(p: *mut u8, e: Invalid) -> ! {
+    let location = Location::caller();
+    match e {
+        Invalid::DoubleFree(f)  => panic!("double free of {:p} at {}, allocated at {}, first freed at {}", p, location, f.allocated, f.freed),
+        Invalid::Unknown        => panic!("free of {:p} at {}: not allocated by allocRaw", p, location),
+    }
+}
+
+// the size of the live block `p`, checked before anything reads or copies it
+#[track_caller]
+unsafe fn liveSize(p: *mut u8) -> usize {
+    match withState(|st| validate(st, p).map(|h| (*h).size)) {
+        Ok(size) => size,
+        Err(e) => invalid(p, e),
+    }
+}
+
+#[track_caller]
+unsafe fn untrack(p: *mut u8) {
+    if p.is_null() { return }
+
+    let location = Location::caller();
+    let r = withState(|st| {
+        let h = validate(st, p)?;
+
+        if (*h).prev.is_null() { st.head = (*h).next } else { (*(*h).prev).next = (*h).next }
+        if !(*h).next.is_null() { (*(*h).next).prev = (*h).prev }
+        (*h).canary = 0;
+
+        st.stats.liveAllocations    -= 1;
+        st.stats.liveBytes          -= (*h).size;
+        st.stats.totalFrees         += 1;
+
+        let freed   = Freed { ptr: p, block: p.sub((*h).offset), allocated: (*h).location, freed: location };
+        let evicted = st.quarantine[st.next].replace(freed).map(|f| f.block);
+        st.next = (st.next + 1) % QUARANTINE_SIZE;
+        Ok(evicted)
+    });
+
+    // free and panic outside of the lock
+    match r {
+        Ok(Some(block)) => sys::freeRaw(block),
+        Ok(None)        => (),
+        Err(e)          => invalid(p, e),
+    }
+}
+
+/// Tracked `sys::allocRaw`.
+///
+/// # Safety
+/// Same contract as `sys::allocRaw`.
+#[track_caller]
+pub unsafe fn allocRaw(size: usize) -> *mut u8 {
+    track(size, MIN_ALIGN, Location::caller())
+}
+
+/// Tracked `sys::allocRawAligned`.
+///
+/// # Safety
+/// Same contract as `sys::allocRawAligned`.
+#[track_caller]
+pub unsafe fn allocRawAligned(size: usize, align: usize) -> *mut u8 {
+    track(size, align, Location::caller())
+}
+
+/// Tracked `sys::reallocRaw`.
+///
+/// # Safety
+/// Same contract as `sys::reallocRaw`.
+#[track_caller]
+pub unsafe fn reallocRaw(arr: *mut u8, size: usize) -> *mut u8 {
+    reallocRawAligned(arr, if arr.is_null() { 0 } else { liveSize(arr) }, size, MIN_ALIGN)
+}
+
+/// Tracked `sys::reallocRawAligned`.
+///
+/// # Safety
+/// Same contract as `sys::reallocRawAligned`.
+#[track_caller]
+pub unsafe fn reallocRawAligned(arr: *mut u8, oldSize: usize, newSize: usize, align: usize) -> *mut u8 {
+    if !arr.is_null() { liveSize(arr); }
+    let addr = track(newSize, align, Location::caller());
+    if !addr.is_null() && !arr.is_null() {
+        ptr::copy_nonoverlapping(arr, addr, core::cmp::min(oldSize, newSize));
+        untrack(arr);
+    }
+    addr
+}
+
+/// Tracked `sys::freeRaw`.
+///
+/// # Safety
+/// Same contract as `sys::freeRaw`.
+#[track_caller]
+pub unsafe fn freeRaw(arr: *mut u8) {
+    untrack(arr)
+}
+
+pub fn stats() -> AllocStats {
+    withState(|st| st.stats)
+}
+
+// call `f` on the live allocations made by `thread` since `serial`
+fn liveSince<F: FnMut(&Header)>(serial: u64, thread: usize, mut f: F) {
+    withState(|st| {
+        let mut h = st.head;
+        while !h.is_null() {
+            let hdr = unsafe { &*h };
+            // the list is ordered newest first
+            if hdr.serial < serial { break }
+            if hdr.thread == thread { f(hdr) }
+            h = hdr.next;
+        }
+    });
+}
+
+/// Print the counters and the live allocations grouped by call site to stderr
+/// (called at exit once the first allocation is made).
+pub fn report() {
+    let s = stats();
+    let mut e = StdErr;
+    let _ = writeln!(e, "[debug-alloc] live: {} allocations, {} bytes - peak: {} bytes - total: {} allocations, {} frees",
+                     s.liveAllocations, s.liveBytes, s.peakBytes, s.totalAllocations, s.totalFrees);
+
+    // group by call site without allocating: report each location at its first (newest) occurrence
+    withState(|st| {
+        let mut h = st.head;
+        while !h.is_null() {
+            let hdr = unsafe { &*h };
+            let mut seen = false;
+            let mut o = st.head;
+            while o != h {
+                if unsafe { (*o).location } == hdr.location { seen = true; break }
+                o = unsafe { (*o).next };
+            }
+
+            if !seen {
+                let (mut count, mut bytes) = (0, 0);
+                let mut o = h;
+                while !o.is_null() {
+                    unsafe {
+                        if (*o).location == hdr.location { count += 1; bytes += (*o).size }
+                        o = (*o).next;
+                    }
+                }
+                let _ = writeln!(e, "[debug-alloc]   {}: {} allocations, {} bytes", hdr.location, count, bytes);
+            }
+            h = hdr.next;
+        }
+    });
+}
+
+/// Run `f` and panic if it left allocations made from the calling thread behind.
+pub fn assertNoLeaks<F: FnOnce()>(f: F) {
+    let serial = withState(|st| st.serial) + 1;
+    let thread = currentThread();
+    f();
+
+    let (mut count, mut bytes) = (0, 0);
+    let mut first = None;
+    liveSince(serial, thread, |h| {
+        count += 1;
+        bytes += h.size;
+        first = Some(h.location);
+    });
+
+    if let Some(location) = first {
+        panic!("{} allocations ({} bytes) leaked, the oldest one from {}", count, bytes, location);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testStats() {
+        let before = stats();
+        unsafe {
+            let p = allocRaw(100);
+            let p = reallocRaw(p, 200);
+            let q = allocRawAligned(10, 4096);
+            assert!(q as usize & 4095 == 0);
+            let s = stats();
+            assert!(s.totalAllocations >= before.totalAllocations + 3);
+            assert!(s.peakBytes >= 210);
+            freeRaw(p);
+            freeRaw(q);
+        }
+        assert!(stats().totalFrees >= before.totalFrees + 3);
+    }
+
+    #[test]
+    fn testNoLeaks() {
+        assertNoLeaks(|| {
+            let mut v = crate::vec::Vec::new();
+            for i in 0..1000 {
+                v.pushBack(crate::mem::Box::new(i));
+            }
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "1 allocations (8 bytes) leaked")]
+    fn testLeak() {
+        assertNoLeaks(|| {
+            let b = crate::mem::Box::new(1234u64);
+            b.intoRaw();
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "double free")]
+    fn testDoubleFree() {
+        unsafe {
+            let p = allocRaw(16);
+            freeRaw(p);
+            freeRaw(p);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "double free")]
+    fn testReallocFreed() {
+        unsafe {
+            let p = allocRaw(16);
+            freeRaw(p);
+            reallocRaw(p, 32);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not allocated by allocRaw")]
+    fn testFreeUnknown() {
+        unsafe {
+            let p = sys::allocRaw(HEADER_SIZE + 16);
+            freeRaw(p.add(HEADER_SIZE));
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//
+// Fixed size object pool: slots live in slabs that never move, free slots are
+// chained in a free list
+//
+use core::cell::{Cell, UnsafeCell};
+use core::mem::MaybeUninit;
+use core::ptr;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use crate::mem::*;
+use crate::vec::Vec;
+
+const NONE : usize = usize::MAX;
+
+// every pool gets its own id, so a handle is only accepted by the pool that made it
+static NEXT_ID : AtomicUsize = AtomicUsize::new(1);
+
+#[derive(Clone, Copy, PartialEq)]
+enum SlotState {
+    Free,
+    Handle, // reachable through a `Handle`
+    Boxed,  // owned by a `PoolBox`
+}
+
+struct Slot<T> {
+    generation  : u32,
+    state       : SlotState,
+    nextFree    : usize,
+    value       : MaybeUninit<T>,
+}
+
+/// Reference to a pool object. Handles of removed objects are detected through the generation,
+/// handles of another pool through the pool id.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Handle {
+    pool        : usize,
+    index       : usize,
+    generation  : u32,
+}
+
+impl Handle {
+    pub fn index(&self) -> usize { self.index }
+    pub fn generation(&self) -> u32 { self.generation }
+}
+
+/// Typed object pool with O(1) alloc/free, handle based access and optional growth in slabs.
+pub struct Pool<T, A: Allocator = LibcAllocator> {
+    id          : usize,
+    slabs       : UnsafeCell<Vec<*mut Slot<T>, A>>,    // the slabs come from the same allocator
+    slabSize    : usize,
+    growable    : bool,
+    freeHead    : Cell<usize>,
+    count       : Cell<usize>,
+}
+
+impl<T> Pool<T> {
+    /// A pool growing by slabs of `slabSize` objects.
+    pub fn new(slabSize: usize) -> Self { Self::newIn(slabSize, LibcAllocator) }
+
+    /// A pool of exactly `capacity` objects, inserting into a full pool fails.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacity(capacity: usize) -> Self { Self::withCapacityIn(capacity, LibcAllocator) }
+}
+
+impl<T, A: Allocator> Pool<T, A> {
+    /// A pool growing by slabs of `slabSize` objects (at least 1).
+    pub fn newIn(slabSize: usize, alloc: A) -> Self {
+        Self {
+            id          : NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            slabs       : UnsafeCell::new(Vec::newIn(alloc)),
+            slabSize    : usize::max(slabSize, 1),
+            growable    : true,
+            freeHead    : Cell::new(NONE),
+            count       : Cell::new(0),
+        }
+    }
+
+    /// A pool of exactly `capacity` objects, inserting into a full pool fails.
+    /// With a `capacity` of 0 nothing is allocated and the pool is always full.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacityIn(capacity: usize, alloc: A) -> Self {
+        let p = Self {
+            id          : NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            slabs       : UnsafeCell::new(Vec::newIn(alloc)),
+            slabSize    : capacity,
+            growable    : false,
+            freeHead    : Cell::new(NONE),
+            count       : Cell::new(0),
+        };
+        if capacity > 0 { p.addSlab() }
+        p
+    }
+
+    pub fn allocator(&self) -> &A { unsafe { (*self.slabs.get()).allocator() } }
+
+    pub fn len(&self) -> usize { self.count.get() }
+    pub fn isEmpty(&self) -> bool { self.count.get() == 0 }
+    pub fn capacity(&self) -> usize { self.slabCount() * self.slabSize }
+
+    #[inline]
+    fn slabCount(&self) -> usize { unsafe { (*self.slabs.get()).len() } }
+
+    // only called with `index < capacity()`, so `slabSize` isn't 0
+    #[inline]
+    fn slot(&self, index: usize) -> *mut Slot<T> {
+        unsafe { (&*self.slabs.get())[index / self.slabSize].add(index % self.slabSize) }
+    }
+
+    #[inline]
+    fn slabBytes(&self) -> usize { self.slabSize * core::mem::size_of::<Slot<T>>() }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    fn addSlab(&self) {
+        let first = self.capacity();
+        let align = core::mem::align_of::<Slot<T>>();
+        let size  = match self.slabSize.checked_mul(core::mem::size_of::<Slot<T>>()) {
+            Some(size) => size,
+            None => handleAllocError(AllocError { size: usize::MAX, align }),
+        };
+        let slab  = unsafe { self.allocator().alloc(size, align) as *mut Slot<T> };
+        if slab.is_null() { handleAllocError(AllocError { size, align }) }
+
+        // chain the new slots in front of the free list
+        for i in 0..self.slabSize {
+            let next = if i + 1 == self.slabSize { self.freeHead.get() } else { first + i + 1 };
+            unsafe { slab.add(i).write(Slot { generation: 0, state: SlotState::Free, nextFree: next, value: MaybeUninit::uninit() }) };
+        }
+        unsafe { (*self.slabs.get()).pushBack(slab) };
+        self.freeHead.set(first);
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    fn take(&self, v: T, state: SlotState) -> Result<usize, T> {
+        if self.freeHead.get() == NONE {
+            if !self.growable { return Err(v) }
+            self.addSlab();
+        }
+
+        let index = self.freeHead.get();
+        let s = self.slot(index);
+        unsafe {
+            self.freeHead.set((*s).nextFree);
+            (*s).state = state;
+            (*s).value = MaybeUninit::new(v);
+        }
+        self.count.set(self.count.get() + 1);
+        Ok(index)
+    }
+
+    // move the value out and put the slot back in the free list
+    fn release(&self, index: usize) -> T {
+        let s = self.slot(index);
+        unsafe {
+            let v = ptr::read((*s).value.as_ptr());
+            (*s).state = SlotState::Free;
+            (*s).generation = (*s).generation.wrapping_add(1);
+            (*s).nextFree = self.freeHead.get();
+            self.freeHead.set(index);
+            self.count.set(self.count.get() - 1);
+            v
+        }
+    }
+
+    fn find(&self, h: Handle) -> Option<*mut Slot<T>> {
+        if h.pool != self.id || h.index >= self.capacity() { return None }
+        let s = self.slot(h.index);
+        unsafe {
+            if (*s).state == SlotState::Handle && (*s).generation == h.generation { Some(s) } else { None }
+        }
+    }
+
+    /// Store `v` in the pool, gives it back if the pool is full and can't grow.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn insert(&mut self, v: T) -> Result<Handle, T> {
+        let index = self.take(v, SlotState::Handle)?;
+        Ok(Handle { pool: self.id, index, generation: unsafe { (*self.slot(index)).generation } })
+    }
+
+    /// Remove the object, `None` if the handle is stale.
+    pub fn remove(&mut self, h: Handle) -> Option<T> {
+        self.find(h)?;
+        Some(self.release(h.index))
+    }
+
+    pub fn contains(&self, h: Handle) -> bool { self.find(h).is_some() }
+
+    pub fn get(&self, h: Handle) -> Option<&T> {
+        self.find(h).map(|s| unsafe { &*(*s).value.as_ptr() })
+    }
+
+    pub fn getMut(&mut self, h: Handle) -> Option<&mut T> {
+        self.find(h).map(|s| unsafe { &mut *(*s).value.as_mut_ptr() })
+    }
+
+    /// Store `v` in a slot owned by the returned box, the slot goes back to the pool when the box is dropped.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn alloc(&self, v: T) -> Result<PoolBox<'_, T, A>, T> {
+        let index = self.take(v, SlotState::Boxed)?;
+        Ok(PoolBox { pool: self, index })
+    }
+}
+
+impl<T, A: Allocator> Drop for Pool<T, A> {
+    fn drop(&mut self) {
+        let slabs = unsafe { &*self.slabs.get() };
+        let align = core::mem::align_of::<Slot<T>>();
+        for slab in slabs.asArray() {
+            for i in 0..self.slabSize {
+                unsafe {
+                    let s = slab.add(i);
+                    if (*s).state != SlotState::Free {
+                        ptr::drop_in_place((*s).value.as_mut_ptr());
+                    }
+                }
+            }
+            unsafe { slabs.allocator().free(*slab as *mut u8, self.slabBytes(), align) };
+        }
+    }
+}
+
+/// Owning pointer to a pool slot, see `Pool::alloc`.
+pub struct PoolBox<'a, T, A: Allocator = LibcAllocator> {
+    pool    : &'a Pool<T, A>,
+    index   : usize,
+}
+
+impl<'a, T, A: Allocator> PoolBox<'a, T, A> {
+    /// Move the value out, giving the slot back to the pool.
+    pub fn unbox(self) -> T {
+        let m = core::mem::ManuallyDrop::new(self);
+        m.pool.release(m.index)
+    }
+}
+
+impl<'a, T, A: Allocator> core::ops::Deref for PoolBox<'a, T, A> {
+    type Target = T;
+    fn deref(&self) -> &T { unsafe { &*(*self.pool.slot(self.index)).value.as_ptr() } }
+}
+
+impl<'a, T, A: Allocator> core::ops::DerefMut for PoolBox<'a, T, A> {
+    fn deref_mut(&mut self) -> &mut T { unsafe { &mut *(*self.pool.slot(self.index)).value.as_mut_ptr() } }
+}
+
+impl<'a, T, A: Allocator> Drop for PoolBox<'a, T, A> {
+    fn drop(&mut self) {
+        drop(self.pool.release(self.index));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testInsertRemove() {
+        let mut p = Pool::new(8);
+        let mut handles = Vec::new();
+        for i in 0..100 {
+            handles.pushBack(p.insert(i).ok().unwrap());
+        }
+        assert!(p.len() == 100);
+        assert!(p.capacity() == 104);
+
+        for i in 0..50 {
+            assert!(p.remove(handles[i * 2]) == Some(i * 2));
+        }
+        assert!(p.len() == 50);
+
+        for i in 0..100 {
+            match p.get(handles[i]) {
+                Some(v) => assert!(i % 2 == 1 && *v == i),
+                None => assert!(i % 2 == 0),
+            }
+        }
+
+        // freed slots are reused, old handles stay stale
+        let h = p.insert(1000).ok().unwrap();
+        assert!(p.capacity() == 104);
+        assert!(h.index() == handles[98].index());
+        assert!(p.get(handles[98]).is_none());
+        assert!(p.remove(handles[98]).is_none());
+        *p.getMut(h).unwrap() += 1;
+        assert!(*p.get(h).unwrap() == 1001);
+    }
+
+    #[test]
+    fn testFixedCapacity() {
+        let mut p = Pool::withCapacity(4);
+        for i in 0..4 {
+            assert!(p.insert(i).is_ok());
+        }
+        assert!(p.insert(4).err() == Some(4));
+        assert!(p.capacity() == 4);
+    }
+
+    #[test]
+    fn testForeignHandle() {
+        let mut a = Pool::new(8);
+        let mut b = Pool::new(8);
+        let ha = a.insert(1).ok().unwrap();
+        let hb = b.insert(2).ok().unwrap();
+        // same slot and generation, different pools
+        assert!(ha.index() == hb.index() && ha.generation() == hb.generation() && ha != hb);
+        assert!(b.get(ha).is_none() && !b.contains(ha) && b.remove(ha).is_none());
+        assert!(a.getMut(hb).is_none() && a.get(ha) == Some(&1) && b.get(hb) == Some(&2));
+    }
+
+    #[test]
+    fn testZeroCapacity() {
+        let mut p = Pool::withCapacity(0);
+        assert!(p.capacity() == 0);
+        assert!(p.insert(1).err() == Some(1));
+        assert!(p.alloc(2).err() == Some(2));
+        assert!(p.isEmpty());
+    }
+
+    // counts the bytes it has out
+    struct CountingAllocator {
+        live    : Cell<usize>,
+    }
+
+    impl Allocator for CountingAllocator {
+        unsafe fn alloc(&self, size: usize, align: usize) -> *mut u8 {
+            self.live.set(self.live.get() + size);
+            LibcAllocator.alloc(size, align)
+        }
+
+        unsafe fn free(&self, ptr: *mut u8, size: usize, align: usize) {
+            self.live.set(self.live.get() - size);
+            LibcAllocator.free(ptr, size, align)
+        }
+    }
+
+    #[test]
+    fn testAllocator() {
+        let a = CountingAllocator { live: Cell::new(0) };
+        {
+            let mut p = Pool::newIn(8, &a);
+            for i in 0..20 {
+                assert!(p.insert(i).is_ok());
+            }
+            assert!(p.capacity() == 24);
+            // the 3 slabs and the slab table, which starts with room for 16 slabs
+            let slabs = 24 * core::mem::size_of::<Slot<i32>>();
+            assert!(a.live.get() == slabs + 16 * core::mem::size_of::<*mut Slot<i32>>());
+        }
+        assert!(a.live.get() == 0);
+
+        let mut p = Pool::withCapacityIn(0, &a);
+        assert!(p.insert(0).is_err());
+        assert!(a.live.get() == 0);
+    }
+
+    #[test]
+    fn testDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let rc = Rc::new(0);
+            {
+                let mut p = Pool::new(16);
+                let mut handles = Vec::new();
+                for _ in 0..100 {
+                    handles.pushBack(p.insert(rc.clone()).ok().unwrap());
+                }
+                for i in 0..10 {
+                    p.remove(handles[i]);
+                }
+                assert!(Rc::strongCount(&rc) == 91);
+            }
+            assert!(Rc::strongCount(&rc) == 1);
+        });
+    }
+
+    #[test]
+    fn testPoolBox() {
+        let rc = Rc::new(0);
+        let p = Pool::new(4);
+        {
+            let mut boxes = Vec::new();
+            for _ in 0..10 {
+                boxes.pushBack(p.alloc(rc.clone()).ok().unwrap());
+            }
+            assert!(p.len() == 10);
+            assert!(Rc::strongCount(&rc) == 11);
+            let b = boxes.pop().unwrap();
+            let v = b.unbox();
+            assert!(p.len() == 9);
+            drop(v);
+        }
+        assert!(p.len() == 0);
+        assert!(Rc::strongCount(&rc) == 1);
+
+        let sp = Pool::new(4);
+        let mut b = sp.alloc(crate::string::String::from("hello")).ok().unwrap();
+        crate::string::Append::append(&mut *b, " world");
+        assert!(*b == "hello world");
+    }
+}
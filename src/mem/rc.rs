@@ -0,0 +1,258 @@
+//
+// Single threaded reference counting (Rc/Weak)
+//
+use core::cell::Cell;
+use core::ptr;
+use crate::mem::*;
+
+struct RcBox<T> {
+    strong  : Cell<usize>,
+    weak    : Cell<usize>,  // weak references + 1 held collectively by the strong ones
+    value   : T,
+}
+
+/// Single threaded shared ownership of a `T` allocated from `A`.
+pub struct Rc<T, A: Allocator + Clone = LibcAllocator> {
+    ptr     : *mut RcBox<T>,
+    alloc   : A,
+}
+
+/// Non owning reference to an `Rc` allocation, see `Rc::downgrade` and `Weak::upgrade`.
+pub struct Weak<T, A: Allocator + Clone = LibcAllocator> {
+    ptr     : *mut RcBox<T>,
+    alloc   : A,
+}
+
+unsafe fn freeRcBox<T, A: Allocator>(alloc: &A, ptr: *mut RcBox<T>) {
+    alloc.free(ptr as *mut u8, core::mem::size_of::<RcBox<T>>(), core::mem::align_of::<RcBox<T>>())
+}
+
+impl<T> Rc<T> {
+    pub fn new(value: T) -> Self { Self::newIn(value, LibcAllocator) }
+}
+
+impl<T, A: Allocator + Clone> Rc<T, A> {
+    pub fn newIn(value: T, alloc: A) -> Self {
+        let size    = core::mem::size_of::<RcBox<T>>();
+        let align   = core::mem::align_of::<RcBox<T>>();
+        let ptr     = unsafe { alloc.alloc(size, align) as *mut RcBox<T> };
+        if ptr.is_null() { handleAllocError(AllocError { size, align }) }
+        unsafe { ptr.write(RcBox { strong: Cell::new(1), weak: Cell::new(1), value }) };
+        Self { ptr, alloc }
+    }
+
+    #[inline]
+    fn inner(&self) -> &RcBox<T> { unsafe { &*self.ptr } }
+
+    pub fn strongCount(this: &Self) -> usize { this.inner().strong.get() }
+    pub fn weakCount(this: &Self) -> usize { this.inner().weak.get() - 1 }
+
+    /// true if both point to the same allocation
+    pub fn ptrEq(this: &Self, other: &Self) -> bool { this.ptr == other.ptr }
+
+    pub fn downgrade(this: &Self) -> Weak<T, A> {
+        let inner = this.inner();
+        inner.weak.set(inner.weak.get() + 1);
+        Weak { ptr: this.ptr, alloc: this.alloc.clone() }
+    }
+
+    /// Mutable access to the value, only if there are no other `Rc` or `Weak` to it.
+    pub fn getMut(this: &mut Self) -> Option<&mut T> {
+        if Self::strongCount(this) == 1 && Self::weakCount(this) == 0 {
+            Some(unsafe { &mut (*this.ptr).value })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value if `this` is the only strong reference, otherwise gives `this` back.
+    pub fn tryUnwrap(this: Self) -> Result<T, Self> {
+        if Self::strongCount(&this) != 1 { return Err(this) }
+
+        unsafe {
+            let this    = core::mem::ManuallyDrop::new(this);
+            let inner   = this.inner();
+            let value   = ptr::read(&inner.value);
+            inner.strong.set(0);
+
+            // release the weak reference held by the strong ones
+            inner.weak.set(inner.weak.get() - 1);
+            let alloc   = ptr::read(&this.alloc);
+            if inner.weak.get() == 0 {
+                freeRcBox(&alloc, this.ptr);
+            }
+            Ok(value)
+        }
+    }
+}
+
+impl<T, A: Allocator + Clone> Clone for Rc<T, A> {
+    fn clone(&self) -> Self {
+        let inner = self.inner();
+        inner.strong.set(inner.strong.get() + 1);
+        Self { ptr: self.ptr, alloc: self.alloc.clone() }
+    }
+}
+
+impl<T, A: Allocator + Clone> core::ops::Deref for Rc<T, A> {
+    type Target = T;
+    fn deref(&self) -> &T { &self.inner().value }
+}
+
+impl<T, A: Allocator + Clone> Drop for Rc<T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            let inner = &*self.ptr;
+            inner.strong.set(inner.strong.get() - 1);
+            if inner.strong.get() == 0 {
+                ptr::drop_in_place(&mut (*self.ptr).value);
+
+                inner.weak.set(inner.weak.get() - 1);
+                if inner.weak.get() == 0 {
+                    freeRcBox(&self.alloc, self.ptr);
+                }
+            }
+        }
+    }
+}
+
+impl<T, A: Allocator + Clone> Weak<T, A> {
+    /// Get a strong reference back, or `None` if the value was already dropped.
+    pub fn upgrade(&self) -> Option<Rc<T, A>> {
+        let inner = unsafe { &*self.ptr };
+        if inner.strong.get() == 0 {
+            None
+        } else {
+            inner.strong.set(inner.strong.get() + 1);
+            Some(Rc { ptr: self.ptr, alloc: self.alloc.clone() })
+        }
+    }
+
+    pub fn strongCount(&self) -> usize { unsafe { (*self.ptr).strong.get() } }
+
+    pub fn ptrEq(&self, other: &Self) -> bool { self.ptr == other.ptr }
+}
+
+impl<T, A: Allocator + Clone> Clone for Weak<T, A> {
+    fn clone(&self) -> Self {
+        let inner = unsafe { &*self.ptr };
+        inner.weak.set(inner.weak.get() + 1);
+        Self { ptr: self.ptr, alloc: self.alloc.clone() }
+    }
+}
+
+impl<T, A: Allocator + Clone> Drop for Weak<T, A> {
+    fn drop(&mut self) {
+        unsafe {
+            let inner = &*self.ptr;
+            inner.weak.set(inner.weak.get() - 1);
+            if inner.weak.get() == 0 {
+                freeRcBox(&self.alloc, self.ptr);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::RefCell;
+    use crate::vec::*;
+
+    struct DropCounter<'a> {
+        id      : usize,
+        drops   : &'a RefCell<Vec<usize>>,
+    }
+
+    impl<'a> Drop for DropCounter<'a> {
+        fn drop(&mut self) {
+            self.drops.borrow_mut().pushBack(self.id);
+        }
+    }
+
+    #[test]
+    fn testDropVecVec() {
+        crate::mem::assertNoLeaks(|| {
+            let mut v = Vec::new();
+            for _ in 0..100 {
+                let mut vj = Vec::new();
+                for j in 0..100 {
+                    vj.pushBack(j);
+                }
+                v.pushBack(Rc::new(vj));
+            }
+            let v2 = v.clone();
+            drop(v);
+            assert!(Rc::strongCount(&v2[10]) == 1);
+            assert!(v2[10][99] == 99);
+        });
+    }
+
+    #[test]
+    fn testDropOrder() {
+        let drops = RefCell::new(Vec::new());
+        {
+            let a = Rc::new(DropCounter { id: 0, drops: &drops });
+            let b = Rc::new(DropCounter { id: 1, drops: &drops });
+            let a2 = a.clone();
+            let w = Rc::downgrade(&b);
+            assert!(Rc::strongCount(&a) == 2);
+            assert!(Rc::weakCount(&b) == 1);
+
+            drop(a);
+            assert!(drops.borrow().is_empty());
+            drop(b);
+            assert!(drops.borrow().len() == 1 && drops.borrow()[0] == 1);
+            assert!(w.upgrade().is_none());
+            assert!(a2.id == 0);
+        }
+        assert!(drops.borrow().len() == 2 && drops.borrow()[1] == 0);
+    }
+
+    struct Node<'a> {
+        _c      : DropCounter<'a>,
+        parent  : RefCell<Option<Weak<Node<'a>>>>,
+        children: RefCell<Vec<Rc<Node<'a>>>>,
+    }
+
+    #[test]
+    fn testCycleBreaking() {
+        crate::mem::assertNoLeaks(|| {
+            let drops = RefCell::new(Vec::new());
+            {
+                let root = Rc::new(Node { _c: DropCounter { id: 0, drops: &drops }, parent: RefCell::new(None), children: RefCell::new(Vec::new()) });
+                for i in 1..10 {
+                    let child = Rc::new(Node { _c: DropCounter { id: i, drops: &drops }, parent: RefCell::new(Some(Rc::downgrade(&root))), children: RefCell::new(Vec::new()) });
+                    root.children.borrow_mut().pushBack(child);
+                }
+
+                let child = root.children.borrow()[3].clone();
+                let parent = child.parent.borrow().as_ref().unwrap().upgrade().unwrap();
+                assert!(Rc::ptrEq(&parent, &root));
+                assert!(Rc::weakCount(&root) == 9);
+            }
+            // all the nodes were released, parents first then children in order
+            assert!(drops.borrow().len() == 10);
+            for i in 0..10 {
+                assert!(drops.borrow()[i] == i);
+            }
+        });
+    }
+
+    #[test]
+    fn testGetMutTryUnwrap() {
+        let mut a = Rc::new(1234);
+        *Rc::getMut(&mut a).unwrap() = 4321;
+        let b = a.clone();
+        assert!(Rc::getMut(&mut a).is_none());
+        let a = Rc::tryUnwrap(a).unwrap_err();
+        drop(b);
+
+        let w = Rc::downgrade(&a);
+        let mut a = a;
+        assert!(Rc::getMut(&mut a).is_none());
+        assert!(Rc::tryUnwrap(a).ok() == Some(4321));
+        assert!(w.upgrade().is_none());
+        assert!(w.strongCount() == 0);
+    }
+}
@@ -0,0 +1,369 @@
+//
+// Generational slot map: values are kept densely packed in a `Vec`, keys go through a slot
+// table that maps them to the dense index. Removed slots are chained in a free list and their
+// generation is bumped so the old keys are detected as stale.
+//
+use core::*;
+use crate::mem::*;
+use crate::vec::*;
+use crate::hash::*;
+
+const NONE : usize = usize::MAX;
+
+/// Stable reference to a `SlotMap` value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Key {
+    index       : usize,
+    generation  : u32,
+}
+
+impl Key {
+    pub fn index(&self) -> usize { self.index }
+    pub fn generation(&self) -> u32 { self.generation }
+}
+
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.writeU64(self.index as u64);
+        state.writeU64(self.generation as u64);
+    }
+}
+
+struct Slot {
+    generation  : u32,
+    occupied    : bool,
+    index       : usize,    // dense index when occupied, next free slot otherwise
+}
+
+pub struct SlotMap<T, A: Allocator + Clone = LibcAllocator> {
+    slots       : Vec<Slot, A>,
+    values      : Vec<T, A>,
+    slotOf      : Vec<usize, A>,    // dense index -> slot
+    freeHead    : usize,
+}
+
+impl<T> SlotMap<T> {
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacity(c: usize) -> Self { Self::withCapacityIn(c, LibcAllocator) }
+}
+
+impl<T, A: Allocator + Clone + Default> Default for SlotMap<T, A> {
+    fn default() -> Self { Self::newIn(A::default()) }
+}
+
+impl<T, A: Allocator + Clone> SlotMap<T, A> {
+    pub fn newIn(alloc: A) -> Self {
+        Self {
+            slots       : Vec::newIn(alloc.clone()),
+            values      : Vec::newIn(alloc.clone()),
+            slotOf      : Vec::newIn(alloc),
+            freeHead    : NONE,
+        }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacityIn(c: usize, alloc: A) -> Self {
+        Self {
+            slots       : Vec::withCapacityIn(c, alloc.clone()),
+            values      : Vec::withCapacityIn(c, alloc.clone()),
+            slotOf      : Vec::withCapacityIn(c, alloc),
+            freeHead    : NONE,
+        }
+    }
+
+    pub fn allocator(&self) -> &A { self.values.allocator() }
+
+    pub fn len(&self) -> usize { self.values.len() }
+    pub fn isEmpty(&self) -> bool { self.values.is_empty() }
+    pub fn capacity(&self) -> usize { self.values.capacity() }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn reserve(&mut self, additional: usize) {
+        self.values.reserve(additional);
+        self.slotOf.reserve(additional);
+        let free = self.slots.len() - self.values.len();
+        if additional > free { self.slots.reserve(additional - free) }
+    }
+
+    // dense index of the value `k` refers to
+    #[inline]
+    fn denseIndex(&self, k: Key) -> Option<usize> {
+        if k.index >= self.slots.len() { return None }
+        let s = &self.slots[k.index];
+        if s.occupied && s.generation == k.generation { Some(s.index) } else { None }
+    }
+
+    /// Insert `v`, returning its key. Freed slots are reused before growing.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn insert(&mut self, v: T) -> Key {
+        // make room first, so a failed allocation leaves the three tables in sync
+        self.values.reserve(1);
+        self.slotOf.reserve(1);
+        if self.freeHead == NONE { self.slots.reserve(1) }
+
+        let dense = self.values.len();
+        self.values.pushBack(v);
+        let index = if self.freeHead != NONE {
+            let i = self.freeHead;
+            let s = &mut self.slots[i];
+            self.freeHead = s.index;
+            s.occupied = true;
+            s.index = dense;
+            i
+        } else {
+            self.slots.pushBack(Slot { generation: 0, occupied: true, index: dense });
+            self.slots.len() - 1
+        };
+        self.slotOf.pushBack(index);
+        Key { index, generation: self.slots[index].generation }
+    }
+
+    /// Remove the value `k` refers to in O(1), `None` if the key is stale.
+    pub fn remove(&mut self, k: Key) -> Option<T> {
+        let dense = self.denseIndex(k)?;
+
+        // the last value moves into the hole
+        let v = self.values.swapRemove(dense);
+        self.slotOf.swapRemove(dense);
+        if dense < self.values.len() {
+            let moved = self.slotOf[dense];
+            self.slots[moved].index = dense;
+        }
+
+        self.release(k.index);
+        Some(v)
+    }
+
+    // bump the generation of a vacated slot so its keys go stale. A slot out of generations is
+    // retired rather than reused, its oldest keys would be valid again after wrapping around
+    fn release(&mut self, index: usize) {
+        let s = &mut self.slots[index];
+        s.occupied = false;
+        match s.generation.checked_add(1) {
+            Some(g) => {
+                s.generation = g;
+                s.index = self.freeHead;
+                self.freeHead = index;
+            }
+            None => s.index = NONE,
+        }
+    }
+
+    pub fn contains(&self, k: Key) -> bool { self.denseIndex(k).is_some() }
+
+    pub fn get(&self, k: Key) -> Option<&T> { self.denseIndex(k).map(|i| &self.values[i]) }
+
+    pub fn getMut(&mut self, k: Key) -> Option<&mut T> {
+        let i = self.denseIndex(k)?;
+        Some(&mut self.values[i])
+    }
+
+    /// Remove all the values, every key handed out so far becomes stale.
+    pub fn clear(&mut self) {
+        for i in 0..self.slotOf.len() {
+            self.release(self.slotOf[i]);
+        }
+        self.slotOf.clear();
+        self.values.clear();
+    }
+
+    /// Keep only the values for which `f` returns true.
+    pub fn retain<F: FnMut(Key, &mut T) -> bool>(&mut self, mut f: F) {
+        let mut i = 0;
+        while i < self.values.len() {
+            let index = self.slotOf[i];
+            let k = Key { index, generation: self.slots[index].generation };
+            if f(k, &mut self.values[i]) {
+                i += 1;
+            } else {
+                // the last value takes its place, look at index `i` again
+                self.remove(k);
+            }
+        }
+    }
+
+    /// The values, densely packed in no particular order.
+    pub fn values(&self) -> &[T] { self.values.asArray() }
+    pub fn valuesMut(&mut self) -> &mut [T] { self.values.asMutArray() }
+
+    pub fn keys(&self) -> Keys<'_> {
+        Keys { slotOf: self.slotOf.asArray().iter(), slots: self.slots.asArray() }
+    }
+
+    /// Iterate over `(key, value)` pairs, in the dense order.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { keys: self.keys(), values: self.values.asArray().iter() }
+    }
+
+    pub fn iterMut(&mut self) -> IterMut<'_, T> {
+        IterMut {
+            keys    : Keys { slotOf: self.slotOf.asArray().iter(), slots: self.slots.asArray() },
+            values  : self.values.asMutArray().iter_mut(),
+        }
+    }
+}
+
+impl<T, A: Allocator + Clone> ops::Index<Key> for SlotMap<T, A> {
+    type Output = T;
+    fn index(&self, k: Key) -> &T { self.get(k).expect("stale SlotMap key") }
+}
+
+impl<T, A: Allocator + Clone> ops::IndexMut<Key> for SlotMap<T, A> {
+    fn index_mut(&mut self, k: Key) -> &mut T { self.getMut(k).expect("stale SlotMap key") }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for SlotMap<T, A> {
+    fn clone(&self) -> Self {
+        let alloc = self.allocator().clone();
+        let mut slots = Vec::withCapacityIn(self.slots.len(), alloc.clone());
+        for s in self.slots.iter() {
+            slots.pushBack(Slot { generation: s.generation, occupied: s.occupied, index: s.index });
+        }
+        Self {
+            slots,
+            values      : self.values.clone(),
+            slotOf      : self.slotOf.clone(),
+            freeHead    : self.freeHead,
+        }
+    }
+}
+
+/// Iterator over the keys of a `SlotMap`.
+pub struct Keys<'a> {
+    slotOf  : slice::Iter<'a, usize>,
+    slots   : &'a [Slot],
+}
+
+impl<'a> Iterator for Keys<'a> {
+    type Item = Key;
+    fn next(&mut self) -> Option<Key> {
+        let index = *self.slotOf.next()?;
+        Some(Key { index, generation: self.slots[index].generation })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.slotOf.size_hint() }
+}
+
+/// Iterator over the `(key, value)` pairs of a `SlotMap`.
+pub struct Iter<'a, T> {
+    keys    : Keys<'a>,
+    values  : slice::Iter<'a, T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Key, &'a T);
+    fn next(&mut self) -> Option<(Key, &'a T)> { Some((self.keys.next()?, self.values.next()?)) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.values.size_hint() }
+}
+
+/// Iterator over the `(key, mutable value)` pairs of a `SlotMap`.
+pub struct IterMut<'a, T> {
+    keys    : Keys<'a>,
+    values  : slice::IterMut<'a, T>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = (Key, &'a mut T);
+    fn next(&mut self) -> Option<(Key, &'a mut T)> { Some((self.keys.next()?, self.values.next()?)) }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.values.size_hint() }
+}
+
+impl<'a, T, A: Allocator + Clone> IntoIterator for &'a SlotMap<T, A> {
+    type Item = (Key, &'a T);
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> { self.iter() }
+}
+
+impl<'a, T, A: Allocator + Clone> IntoIterator for &'a mut SlotMap<T, A> {
+    type Item = (Key, &'a mut T);
+    type IntoIter = IterMut<'a, T>;
+    fn into_iter(self) -> IterMut<'a, T> { self.iterMut() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::string::*;
+
+    #[test]
+    fn testInsertRemove() {
+        let mut m = SlotMap::new();
+        let a = m.insert(1);
+        let b = m.insert(2);
+        let c = m.insert(3);
+        assert!(m.len() == 3 && m[a] == 1 && m[b] == 2 && m[c] == 3);
+
+        // removing from the middle keeps the other keys valid
+        assert!(m.remove(a) == Some(1));
+        assert!(m.remove(a).is_none() && m.get(a).is_none() && !m.contains(a));
+        assert!(m[b] == 2 && m[c] == 3 && m.values().len() == 2);
+
+        // the slot is reused with a new generation
+        let d = m.insert(4);
+        assert!(d.index() == a.index() && d.generation() != a.generation());
+        assert!(m.get(a).is_none() && m[d] == 4);
+        *m.getMut(c).unwrap() += 10;
+        m[b] += 20;
+        assert!(m[c] == 13 && m[b] == 22);
+
+        let fake = Key { index: 100, generation: 0 };
+        assert!(m.get(fake).is_none() && m.remove(fake).is_none());
+    }
+
+    #[test]
+    fn testRetiredSlot() {
+        let mut m = SlotMap::new();
+        let a = m.insert(1);
+        m.slots[a.index].generation = u32::MAX;
+        let a = Key { index: a.index, generation: u32::MAX };
+        assert!(m.remove(a) == Some(1));
+
+        // out of generations, the slot isn't reused and its old keys stay stale
+        let b = m.insert(2);
+        assert!(b.index() != a.index() && m.get(a).is_none() && m[b] == 2);
+        m.clear();
+        let c = m.insert(3);
+        assert!(c.index() == b.index() && c.generation() == 1 && m.len() == 1);
+    }
+
+    #[test]
+    fn testIter() {
+        let mut m = SlotMap::new();
+        let mut keys = Vec::new();
+        for i in 0..100 {
+            keys.pushBack(m.insert(i));
+        }
+        for i in (0..100).step_by(3) {
+            m.remove(keys[i]);
+        }
+        assert!(m.len() == 66);
+        for (k, v) in m.iter() {
+            assert!(keys[*v as usize] == k);
+        }
+        for (_, v) in m.iterMut() {
+            *v *= 2;
+        }
+        assert!(m.keys().all(|k| m[k] % 2 == 0));
+        assert!(m.values().iter().sum::<i32>() == (0..100).filter(|i| i % 3 != 0).map(|i| i * 2).sum::<i32>());
+
+        m.retain(|_, v| *v < 100);
+        assert!(m.len() == 33 && m.iter().all(|(_, v)| *v < 100));
+        m.clear();
+        assert!(m.isEmpty() && keys.iter().all(|k| !m.contains(*k)));
+    }
+
+    #[test]
+    fn testDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let mut m = SlotMap::new();
+            let a = m.insert(String::from("a"));
+            let b = m.insert(String::from("b"));
+            let _ = m.insert(String::from("c"));
+            assert!(m.remove(a).unwrap() == "a");
+            let c = m.clone();
+            m.clear();
+            assert!(c[b] == "b" && c.len() == 2);
+        });
+    }
+}
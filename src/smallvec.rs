@@ -0,0 +1,348 @@
+use core::*;
+use crate::mem::*;
+use crate::vec::*;
+use crate::arrayvec::ArrayVec;
+
+// either the inline buffer or the heap vector, the allocator is kept aside until spilled
+enum Data<T, const N: usize, A: Allocator> {
+    Inline(ArrayVec<T, N>, A),
+    Heap(Vec<T, A>),
+}
+
+/// Vector storing up to `N` elements inline, spilling to an allocated `Vec` past that.
+pub struct SmallVec<T, const N: usize, A: Allocator = LibcAllocator> {
+    data    : Data<T, N, A>,
+}
+
+impl<T, const N: usize> SmallVec<T, N> {
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacity(c: usize) -> Self { Self::withCapacityIn(c, LibcAllocator) }
+}
+
+impl<T, const N: usize, A: Allocator + Default> Default for SmallVec<T, N, A> {
+    fn default() -> Self { Self::newIn(A::default()) }
+}
+
+impl<T, const N: usize, A: Allocator> SmallVec<T, N, A> {
+    pub fn newIn(alloc: A) -> Self {
+        Self { data: Data::Inline(ArrayVec::new(), alloc) }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacityIn(c: usize, alloc: A) -> Self {
+        let mut v = Self::newIn(alloc);
+        v.reserve(c);
+        v
+    }
+
+    pub fn allocator(&self) -> &A {
+        match &self.data { Data::Inline(_, a) => a, Data::Heap(h) => h.allocator() }
+    }
+
+    /// True once the elements moved to the heap.
+    pub fn isSpilled(&self) -> bool { matches!(self.data, Data::Heap(_)) }
+
+    pub fn asArray(&self) -> &[T] {
+        match &self.data { Data::Inline(i, _) => i.asArray(), Data::Heap(h) => h.asArray() }
+    }
+
+    pub fn asMutArray(&mut self) -> &mut [T] {
+        match &mut self.data { Data::Inline(i, _) => i.asMutArray(), Data::Heap(h) => h.asMutArray() }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.data { Data::Inline(i, _) => i.len(), Data::Heap(h) => h.len() }
+    }
+
+    pub fn isEmpty(&self) -> bool { self.asArray().is_empty() }
+
+    pub fn capacity(&self) -> usize {
+        match &self.data { Data::Inline(..) => N, Data::Heap(h) => h.capacity() }
+    }
+
+    // move the inline elements to a heap buffer of at least `c` elements
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    fn trySpill(&mut self, c: usize) -> Result<(), AllocError> {
+        let (inline, alloc) = match &mut self.data { Data::Inline(i, a) => (i, a), Data::Heap(_) => return Ok(()) };
+        // allocate through the borrowed allocator, it only moves once nothing can fail
+        let mut heap = Vec::newIn(&*alloc);
+        heap.tryReserveExact(usize::max(c, inline.len()))?;
+        for t in mem::take(inline) {
+            heap.pushBack(t);
+        }
+
+        let heap = mem::ManuallyDrop::new(heap);
+        let (elements, len, capacity) = (heap.asArray().as_ptr() as *mut T, heap.len(), heap.capacity());
+        unsafe {
+            // the inline part is empty, only the allocator is left in it
+            let alloc = ptr::read(alloc);
+            ptr::write(&mut self.data, Data::Heap(Vec::fromRawPartsIn(elements, len, capacity, alloc)));
+        }
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn tryReserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        let len = match &mut self.data { Data::Inline(i, _) => i.len(), Data::Heap(h) => return h.tryReserve(additional) };
+        let required = len.checked_add(additional).ok_or(AllocError { size: usize::MAX, align: mem::align_of::<T>() })?;
+        if required <= N { return Ok(()) }
+        self.trySpill(usize::max(required, N * 2))
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn reserve(&mut self, additional: usize) {
+        if let Err(e) = self.tryReserve(additional) { handleAllocError(e) }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn tryPushBack(&mut self, t: T) -> Result<(), AllocError> {
+        if matches!(&self.data, Data::Inline(i, _) if i.isFull()) {
+            self.trySpill(usize::max(N * 2, 4))?;
+        }
+        match &mut self.data {
+            Data::Inline(i, _) => { let _ = i.pushBack(t); Ok(()) },
+            Data::Heap(h) => h.tryPushBack(t),
+        }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn pushBack(&mut self, t: T) {
+        if let Err(e) = self.tryPushBack(t) { handleAllocError(e) }
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        match &mut self.data { Data::Inline(i, _) => i.pop(), Data::Heap(h) => h.pop() }
+    }
+
+    #[inline]
+    pub fn get(&self, idx: usize) -> &T { &self.asArray()[idx] }
+    #[inline]
+    pub fn getMut(&mut self, idx: usize) -> &mut T { &mut self.asMutArray()[idx] }
+
+    pub fn toIter(&self) -> slice::Iter<'_, T> { self.asArray().iter() }
+
+    pub fn last(&self) -> Option<&T> { self.asArray().last() }
+
+    /// Insert `t` at `index`, shifting the following elements up.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn insert(&mut self, index: usize, t: T) {
+        assert!(index <= self.len(), "insert index {} out of bounds (len {})", index, self.len());
+        self.reserve(1);
+        match &mut self.data {
+            Data::Inline(i, _) => { let _ = i.insert(index, t); },
+            Data::Heap(h) => h.insert(index, t),
+        }
+    }
+
+    /// Remove the element at `index`, shifting the following elements down.
+    pub fn remove(&mut self, index: usize) -> T {
+        match &mut self.data { Data::Inline(i, _) => i.remove(index), Data::Heap(h) => h.remove(index) }
+    }
+
+    /// Remove the element at `index` and replace it with the last one (O(1), doesn't keep the order).
+    pub fn swapRemove(&mut self, index: usize) -> T {
+        match &mut self.data { Data::Inline(i, _) => i.swapRemove(index), Data::Heap(h) => h.swapRemove(index) }
+    }
+
+    /// Drop the elements past `len`, keeping the capacity.
+    pub fn truncate(&mut self, len: usize) {
+        match &mut self.data { Data::Inline(i, _) => i.truncate(len), Data::Heap(h) => h.truncate(len) }
+    }
+
+    /// Drop all the elements, keeping the capacity.
+    pub fn clear(&mut self) { self.truncate(0) }
+
+    /// Move the elements back inline if they fit, otherwise shrink the heap buffer.
+    pub fn shrinkToFit(&mut self) {
+        let heap = match &mut self.data { Data::Heap(h) => h, Data::Inline(..) => return };
+        if heap.len() > N { return heap.shrinkToFit() }
+
+        let mut inline = ArrayVec::new();
+        for t in heap.drain(..) {
+            let _ = inline.pushBack(t);
+        }
+        heap.shrinkToFit();
+        unsafe {
+            // the heap vector is empty and unallocated, only the allocator is left in it
+            let heap = mem::ManuallyDrop::new(ptr::read(heap));
+            let alloc = ptr::read(heap.allocator());
+            ptr::write(&mut self.data, Data::Inline(inline, alloc));
+        }
+    }
+}
+
+impl<T: Copy, const N: usize, A: Allocator> SmallVec<T, N, A> {
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn extendFromSlice(&mut self, arr: &[T]) {
+        self.reserve(arr.len());
+        match &mut self.data {
+            Data::Inline(i, _) => { let _ = i.tryAppend(arr); },
+            Data::Heap(h) => h.extendFromSlice(arr),
+        }
+    }
+}
+
+impl<T, const N: usize, A: Allocator, I: slice::SliceIndex<[T]>> ops::Index<I> for SmallVec<T, N, A> {
+    type Output = I::Output;
+    #[inline]
+    fn index(&self, idx: I) -> &I::Output { &self.asArray()[idx] }
+}
+
+impl<T, const N: usize, A: Allocator, I: slice::SliceIndex<[T]>> ops::IndexMut<I> for SmallVec<T, N, A> {
+    #[inline]
+    fn index_mut(&mut self, idx: I) -> &mut I::Output { &mut self.asMutArray()[idx] }
+}
+
+impl<T, const N: usize, A: Allocator> ops::Deref for SmallVec<T, N, A> {
+    type Target = [T];
+    fn deref(&self) -> &[T] { self.asArray() }
+}
+
+impl<T, const N: usize, A: Allocator> ops::DerefMut for SmallVec<T, N, A> {
+    fn deref_mut(&mut self) -> &mut [T] { self.asMutArray() }
+}
+
+impl<T: Clone, const N: usize, A: Allocator + Clone> Clone for SmallVec<T, N, A> {
+    fn clone(&self) -> Self {
+        let mut c = Self::withCapacityIn(self.len(), self.allocator().clone());
+        for t in self.iter() {
+            c.pushBack(t.clone());
+        }
+        c
+    }
+}
+
+enum IterData<T, const N: usize, A: Allocator> {
+    Inline(crate::arrayvec::IntoIter<T, N>, A),
+    Heap(crate::vec::IntoIter<T, A>),
+}
+
+/// Owning iterator for `SmallVec`.
+pub struct IntoIter<T, const N: usize, A: Allocator = LibcAllocator> {
+    inner   : IterData<T, N, A>,
+}
+
+impl<T, const N: usize, A: Allocator> Iterator for IntoIter<T, N, A> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        match &mut self.inner { IterData::Inline(i, _) => i.next(), IterData::Heap(h) => h.next() }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner { IterData::Inline(i, _) => i.size_hint(), IterData::Heap(h) => h.size_hint() }
+    }
+}
+
+impl<T, const N: usize, A: Allocator> DoubleEndedIterator for IntoIter<T, N, A> {
+    fn next_back(&mut self) -> Option<T> {
+        match &mut self.inner { IterData::Inline(i, _) => i.next_back(), IterData::Heap(h) => h.next_back() }
+    }
+}
+
+impl<T, const N: usize, A: Allocator> IntoIterator for SmallVec<T, N, A> {
+    type Item = T;
+    type IntoIter = IntoIter<T, N, A>;
+    fn into_iter(self) -> IntoIter<T, N, A> {
+        let inner = match self.data {
+            Data::Inline(i, a) => IterData::Inline(i.into_iter(), a),
+            Data::Heap(h) => IterData::Heap(h.into_iter()),
+        };
+        IntoIter { inner }
+    }
+}
+
+impl<'a, T, const N: usize, A: Allocator> IntoIterator for &'a SmallVec<T, N, A> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+    fn into_iter(self) -> slice::Iter<'a, T> { self.asArray().iter() }
+}
+
+impl<'a, T, const N: usize, A: Allocator> IntoIterator for &'a mut SmallVec<T, N, A> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+    fn into_iter(self) -> slice::IterMut<'a, T> { self.asMutArray().iter_mut() }
+}
+
+impl<T, const N: usize, A: Allocator> iter::Extend<T> for SmallVec<T, N, A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        let it = it.into_iter();
+        self.reserve(it.size_hint().0);
+        for t in it {
+            self.pushBack(t);
+        }
+    }
+}
+
+impl<T, const N: usize, A: Allocator + Default> iter::FromIterator<T> for SmallVec<T, N, A> {
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Self {
+        let mut v = Self::newIn(A::default());
+        v.extend(it);
+        v
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::*;
+
+    // fails every allocation, inline storage must never reach it
+    struct NoAllocator;
+
+    impl Allocator for NoAllocator {
+        unsafe fn alloc(&self, _size: usize, _align: usize) -> *mut u8 { ptr::null_mut() }
+        unsafe fn free(&self, _ptr: *mut u8, _size: usize, _align: usize) { panic!("free without alloc") }
+    }
+
+    #[test]
+    fn testInline() {
+        let mut v = SmallVec::<i32, 8, NoAllocator>::newIn(NoAllocator);
+        assert!(v.isEmpty());
+        for i in 0..7 {
+            v.pushBack(i);
+        }
+        v.insert(0, 9);
+        assert!(v.len() == 8 && !v.isSpilled());
+        assert!(v[..3] == [9, 0, 1] && v.remove(0) == 9);
+        v.extendFromSlice(&[10]);
+        assert!(v.last() == Some(&10));
+        assert!(v.tryPushBack(11).is_err());
+        assert!(v.len() == 8 && v.pop() == Some(10));
+    }
+
+    #[test]
+    fn testSpill() {
+        crate::mem::assertNoLeaks(|| {
+            let mut v = SmallVec::<Vec<i32>, 4>::new();
+            for i in 0..4 {
+                v.pushBack(item(i));
+            }
+            assert!(!v.isSpilled() && v.capacity() == 4);
+            v.pushBack(item(4));
+            assert!(v.isSpilled() && v.capacity() >= 8);
+            for i in 0..5 {
+                assert!(v[i][0] == i as i32);
+            }
+
+            let c = v.clone();
+            v.truncate(2);
+            v.shrinkToFit();
+            assert!(!v.isSpilled() && v.len() == 2 && v[1][0] == 1);
+
+            let mut n = 0;
+            let mut it = c.into_iter();
+            assert!(it.next_back().unwrap()[0] == 4);
+            for x in it {
+                assert!(x[0] == n);
+                n += 1;
+            }
+            assert!(n == 4);
+
+            assert!(mem::size_of::<SmallVec<u8, 16>>() < mem::size_of::<ArrayVec<u8, 16>>() + mem::size_of::<Vec<u8>>());
+
+            let s : SmallVec<i32, 2> = (0..10).collect();
+            assert!(s.isSpilled() && s.iter().sum::<i32>() == 45);
+        });
+    }
+}
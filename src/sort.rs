@@ -0,0 +1,369 @@
+use core::*;
+use core::cmp::Ordering;
+use crate::mem::*;
+
+// runs shorter than this are insertion sorted
+const SMALL_SORT: usize = 20;
+
+/// Sorting and selection on slices (and through `Deref`, on `Vec` and the other containers).
+pub trait Sort<T> {
+    /// Stable merge sort, scratch space for half the elements comes from `LibcAllocator`.
+    fn sortStable(&mut self) where T: Ord;
+    fn sortStableBy<F: FnMut(&T, &T) -> Ordering>(&mut self, cmp: F);
+    /// Stable merge sort taking its scratch space (half the elements) from `alloc`.
+    fn sortStableByIn<F: FnMut(&T, &T) -> Ordering, A: Allocator>(&mut self, cmp: F, alloc: &A);
+    /// Stable sort by the key returned by `f`.
+    fn sortByKey<K: Ord, F: FnMut(&T) -> K>(&mut self, f: F);
+
+    /// Introsort: quicksort with a heapsort fallback, doesn't allocate.
+    fn sortUnstable(&mut self) where T: Ord;
+    fn sortUnstableBy<F: FnMut(&T, &T) -> Ordering>(&mut self, cmp: F);
+
+    /// Sort the `k` smallest elements into `[..k]`, the rest is left in unspecified order.
+    fn partialSort(&mut self, k: usize) where T: Ord;
+    fn partialSortBy<F: FnMut(&T, &T) -> Ordering>(&mut self, k: usize, cmp: F);
+
+    /// Reorder so the element at `n` is the one that would be there if sorted,
+    /// with the smaller ones before it and the greater ones after.
+    fn selectNth(&mut self, n: usize) -> &mut T where T: Ord;
+    fn selectNthBy<F: FnMut(&T, &T) -> Ordering>(&mut self, n: usize, cmp: F) -> &mut T;
+
+    fn isSorted(&self) -> bool where T: PartialOrd;
+    fn isSortedBy<F: FnMut(&T, &T) -> Ordering>(&self, cmp: F) -> bool;
+}
+
+impl<T> Sort<T> for [T] {
+    fn sortStable(&mut self) where T: Ord { self.sortStableBy(|a, b| a.cmp(b)) }
+
+    fn sortStableBy<F: FnMut(&T, &T) -> Ordering>(&mut self, cmp: F) { self.sortStableByIn(cmp, &LibcAllocator) }
+
+    fn sortStableByIn<F: FnMut(&T, &T) -> Ordering, A: Allocator>(&mut self, mut cmp: F, alloc: &A) {
+        let n = self.len();
+        if n <= SMALL_SORT || mem::size_of::<T>() == 0 { return insertionSort(self, &mut cmp) }
+
+        let scratchLen = n / 2;
+        let size = scratchLen * mem::size_of::<T>();
+        let scratch = unsafe { alloc.alloc(size, mem::align_of::<T>()) } as *mut T;
+        if scratch.is_null() { handleAllocError(AllocError { size, align: mem::align_of::<T>() }) }
+
+        // frees the scratch space even if `cmp` panics
+        struct Scratch<'a, A: Allocator> { ptr: *mut u8, size: usize, align: usize, alloc: &'a A }
+        impl<'a, A: Allocator> Drop for Scratch<'a, A> {
+            fn drop(&mut self) { unsafe { self.alloc.free(self.ptr, self.size, self.align) } }
+        }
+        let _guard = Scratch { ptr: scratch as *mut u8, size, align: mem::align_of::<T>(), alloc };
+
+        mergeSort(self, scratch, &mut cmp);
+    }
+
+    fn sortByKey<K: Ord, F: FnMut(&T) -> K>(&mut self, mut f: F) { self.sortStableBy(|a, b| f(a).cmp(&f(b))) }
+
+    fn sortUnstable(&mut self) where T: Ord { self.sortUnstableBy(|a, b| a.cmp(b)) }
+
+    fn sortUnstableBy<F: FnMut(&T, &T) -> Ordering>(&mut self, mut cmp: F) {
+        let limit = depthLimit(self.len());
+        introSort(self, &mut cmp, limit)
+    }
+
+    fn partialSort(&mut self, k: usize) where T: Ord { self.partialSortBy(k, |a, b| a.cmp(b)) }
+
+    fn partialSortBy<F: FnMut(&T, &T) -> Ordering>(&mut self, k: usize, mut cmp: F) {
+        assert!(k <= self.len(), "partialSort count {} out of bounds (len {})", k, self.len());
+        if k == 0 { return }
+        if k < self.len() { self.selectNthBy(k - 1, &mut cmp); }
+        self[..k].sortUnstableBy(cmp)
+    }
+
+    fn selectNth(&mut self, n: usize) -> &mut T where T: Ord { self.selectNthBy(n, |a, b| a.cmp(b)) }
+
+    fn selectNthBy<F: FnMut(&T, &T) -> Ordering>(&mut self, n: usize, mut cmp: F) -> &mut T {
+        assert!(n < self.len(), "selectNth index {} out of bounds (len {})", n, self.len());
+        let mut lo = 0;
+        let mut hi = self.len();
+        let mut limit = depthLimit(self.len());
+        loop {
+            let v = &mut self[lo..hi];
+            if v.len() <= SMALL_SORT || limit == 0 {
+                introSort(v, &mut cmp, 0);
+                break
+            }
+            limit -= 1;
+            let (lt, gt) = partition(v, &mut cmp);
+            if n - lo < lt { hi = lo + lt }
+            else if n - lo > gt { lo += gt + 1 }
+            else { break }
+        }
+        &mut self[n]
+    }
+
+    fn isSorted(&self) -> bool where T: PartialOrd {
+        self.windows(2).all(|w| w[0] <= w[1])
+    }
+
+    fn isSortedBy<F: FnMut(&T, &T) -> Ordering>(&self, mut cmp: F) -> bool {
+        self.windows(2).all(|w| cmp(&w[0], &w[1]) != Ordering::Greater)
+    }
+}
+
+fn depthLimit(n: usize) -> usize { 2 * (usize::BITS - n.leading_zeros()) as usize }
+
+// stable, swaps only so a panicking `cmp` leaves the slice valid
+fn insertionSort<T, F: FnMut(&T, &T) -> Ordering>(v: &mut [T], cmp: &mut F) {
+    for i in 1..v.len() {
+        let mut j = i;
+        while j > 0 && cmp(&v[j], &v[j - 1]) == Ordering::Less {
+            v.swap(j, j - 1);
+            j -= 1;
+        }
+    }
+}
+
+// `scratch` has room for `v.len() / 2` elements
+fn mergeSort<T, F: FnMut(&T, &T) -> Ordering>(v: &mut [T], scratch: *mut T, cmp: &mut F) {
+    let n = v.len();
+    if n <= SMALL_SORT { return insertionSort(v, cmp) }
+    let mid = n / 2;
+    mergeSort(&mut v[..mid], scratch, cmp);
+    mergeSort(&mut v[mid..], scratch, cmp);
+    // already in order
+    if cmp(&v[mid], &v[mid - 1]) != Ordering::Less { return }
+    unsafe { merge(v, mid, scratch, cmp) }
+}
+
+// The left run is moved to `scratch` and merged back with the right run. Until the merge is
+// done `dest..dest + (end - start)` are holes, the hole guard copies the rest of the left run
+// back on drop so a panicking `cmp` can't lose or duplicate elements.
+struct MergeHole<T> {
+    start   : *mut T,
+    end     : *mut T,
+    dest    : *mut T,
+}
+
+impl<T> Drop for MergeHole<T> {
+    fn drop(&mut self) {
+        unsafe {
+            let len = self.end.offset_from(self.start) as usize;
+            ptr::copy_nonoverlapping(self.start, self.dest, len);
+        }
+    }
+}
+
+unsafe fn merge<T, F: FnMut(&T, &T) -> Ordering>(v: &mut [T], mid: usize, scratch: *mut T, cmp: &mut F) {
+    let n = v.len();
+    let base = v.as_mut_ptr();
+    ptr::copy_nonoverlapping(base, scratch, mid);
+
+    let mut hole = MergeHole { start: scratch, end: scratch.add(mid), dest: base };
+    let mut right = base.add(mid);
+    let rightEnd = base.add(n);
+    while hole.start < hole.end && right < rightEnd {
+        // take from the left run on ties to keep the sort stable
+        let src = if cmp(&*right, &*hole.start) == Ordering::Less {
+            let r = right;
+            right = right.add(1);
+            r
+        } else {
+            let l = hole.start;
+            hole.start = hole.start.add(1);
+            l
+        };
+        ptr::copy_nonoverlapping(src, hole.dest, 1);
+        hole.dest = hole.dest.add(1);
+    }
+    // the remaining left elements are copied back by the guard, the right ones are in place
+}
+
+// Three way partition around a median of three pivot, returns `(lt, gt)`:
+// `[..lt]` is less than the pivot, `[lt..=gt]` equal and `[gt + 1..]` greater.
+fn partition<T, F: FnMut(&T, &T) -> Ordering>(v: &mut [T], cmp: &mut F) -> (usize, usize) {
+    let n = v.len();
+    let (a, b, c) = (0, n / 2, n - 1);
+    let mut m = b;
+    if cmp(&v[a], &v[b]) == Ordering::Less {
+        if cmp(&v[b], &v[c]) == Ordering::Greater { m = if cmp(&v[a], &v[c]) == Ordering::Less { c } else { a } }
+    } else if cmp(&v[b], &v[c]) == Ordering::Less {
+        m = if cmp(&v[a], &v[c]) == Ordering::Less { a } else { c }
+    }
+    v.swap(m, n - 1);
+
+    let (rest, pivot) = v.split_at_mut(n - 1);
+    let pivot = &pivot[0];
+    let (mut lt, mut i, mut gt) = (0, 0, rest.len());
+    while i < gt {
+        match cmp(&rest[i], pivot) {
+            Ordering::Less      => { rest.swap(lt, i); lt += 1; i += 1 }
+            Ordering::Greater   => { gt -= 1; rest.swap(i, gt) }
+            Ordering::Equal     => i += 1,
+        }
+    }
+    v.swap(gt, n - 1);
+    (lt, gt)
+}
+
+fn introSort<T, F: FnMut(&T, &T) -> Ordering>(mut v: &mut [T], cmp: &mut F, mut limit: usize) {
+    loop {
+        if v.len() <= SMALL_SORT { return insertionSort(v, cmp) }
+        if limit == 0 { return heapSort(v, cmp) }
+        limit -= 1;
+
+        let (lt, gt) = partition(v, cmp);
+        let (left, rest) = v.split_at_mut(lt);
+        let right = &mut rest[gt + 1 - lt..];
+        // recurse into the smaller side to bound the stack depth
+        if left.len() < right.len() {
+            introSort(left, cmp, limit);
+            v = right;
+        } else {
+            introSort(right, cmp, limit);
+            v = left;
+        }
+    }
+}
+
+fn heapSort<T, F: FnMut(&T, &T) -> Ordering>(v: &mut [T], cmp: &mut F) {
+    fn siftDown<T, F: FnMut(&T, &T) -> Ordering>(v: &mut [T], mut i: usize, end: usize, cmp: &mut F) {
+        loop {
+            let mut child = 2 * i + 1;
+            if child >= end { break }
+            if child + 1 < end && cmp(&v[child + 1], &v[child]) == Ordering::Greater { child += 1 }
+            if cmp(&v[i], &v[child]) != Ordering::Less { break }
+            v.swap(i, child);
+            i = child;
+        }
+    }
+
+    let n = v.len();
+    for i in (0..n / 2).rev() {
+        siftDown(v, i, n, cmp);
+    }
+    for end in (1..n).rev() {
+        v.swap(0, end);
+        siftDown(v, 0, end, cmp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::vec::*;
+    use crate::testutil::*;
+
+    const SIZES : [usize; 9] = [0, 1, 2, 3, 20, 21, 100, 1000, 5000];
+
+    #[test]
+    fn testSortUnstable() {
+        let mut rng = Lcg(3);
+        for n in SIZES {
+            for range in [2, 100, 1 << 30] {
+                let mut v = randomVec(&mut rng, n, range);
+                let mut reference = v.clone();
+                reference.sort_unstable();
+                v.sortUnstable();
+                assert!(v.asArray() == reference.asArray());
+                assert!(v.isSorted());
+            }
+        }
+
+        // presorted and reversed inputs
+        let mut v : Vec<u32> = (0..3000).collect();
+        v.sortUnstableBy(|a, b| b.cmp(a));
+        assert!(v[0] == 2999 && v.isSortedBy(|a, b| b.cmp(a)));
+        v.sortUnstable();
+        assert!(v.isSorted() && !v.isSortedBy(|a, b| b.cmp(a)));
+    }
+
+    #[test]
+    fn testSortStable() {
+        let mut rng = Lcg(5);
+        for n in SIZES {
+            // sort by key only and check equal keys kept their original order
+            let keys = randomVec(&mut rng, n, 16);
+            let mut v : Vec<(u32, usize)> = keys.iter().cloned().zip(0..n).collect();
+            v.sortByKey(|p| p.0);
+            assert!(v.isSortedBy(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1))));
+
+            let mut w = keys.clone();
+            let mut reference = keys.clone();
+            reference.sort_unstable();
+            w.sortStable();
+            assert!(w.asArray() == reference.asArray());
+        }
+    }
+
+    #[test]
+    fn testSortStableArena() {
+        let arena = Arena::new();
+        let mut rng = Lcg(11);
+        let mut v = randomVec(&mut rng, 2000, 1000);
+        v.sortStableByIn(|a, b| a.cmp(b), &arena);
+        assert!(v.isSorted());
+        assert!(arena.chunkCount() >= 1);
+    }
+
+    #[test]
+    fn testSelect() {
+        let mut rng = Lcg(13);
+        for n in SIZES {
+            if n == 0 { continue }
+            let v = randomVec(&mut rng, n, 50);
+            let mut reference = v.clone();
+            reference.sort_unstable();
+            for k in [0, n / 3, n / 2, n - 1] {
+                let mut w = v.clone();
+                assert!(*w.selectNth(k) == reference[k]);
+                assert!(w[..k].iter().all(|x| *x <= w[k]) && w[k + 1..].iter().all(|x| *x >= w[k]));
+
+                let mut w = v.clone();
+                w.partialSort(k);
+                assert!(w[..k] == reference[..k]);
+            }
+        }
+    }
+
+    #[test]
+    fn testSortDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let mut rng = Lcg(17);
+            let mut v : Vec<Vec<u32>> = Vec::new();
+            for _ in 0..500 {
+                let mut e = Vec::new();
+                e.pushBack(rng.next() % 100);
+                v.pushBack(e);
+            }
+            v.sortStableBy(|a, b| a[0].cmp(&b[0]));
+            assert!(v.isSortedBy(|a, b| a[0].cmp(&b[0])));
+            v.sortUnstableBy(|a, b| b[0].cmp(&a[0]));
+            assert!(v[0][0] >= v[499][0]);
+        });
+    }
+
+    #[test]
+    fn testSortPanic() {
+        crate::mem::assertNoLeaks(|| {
+            let mut rng = Lcg(19);
+            let mut v : Vec<Vec<u32>> = Vec::new();
+            for i in 0..300 {
+                let mut e = Vec::new();
+                e.pushBack(rng.next() % 100);
+                e.pushBack(i);
+                v.pushBack(e);
+            }
+
+            // panic halfway through merging, every element must still be there once
+            let mut calls = 0;
+            let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                v.sortStableBy(|a, b| {
+                    calls += 1;
+                    if calls == 1500 { panic!("compare") }
+                    a[0].cmp(&b[0])
+                })
+            }));
+            assert!(r.is_err());
+            let mut ids : Vec<u32> = v.iter().map(|e| e[1]).collect();
+            ids.sortUnstable();
+            assert!(ids.asArray() == (0..300).collect::<Vec<u32>>().asArray());
+        });
+    }
+}
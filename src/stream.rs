@@ -1,5 +1,6 @@
 use crate::vec::*;
 
+#[allow(drop_bounds)]
 pub trait Stream : Drop {
     /// get the current position
     fn tell(&self) -> usize;
@@ -8,15 +9,18 @@ pub trait Stream : Drop {
     fn size(&self) -> usize;
 }
 
+#[allow(clippy::result_unit_err)]
 pub trait StreamReader : Stream {
     fn read(&mut self, buff: &mut [u8]) -> Result<usize, ()>;
     fn isEOF(&self) -> bool;
 }
 
+#[allow(clippy::result_unit_err)]
 pub trait StreamWriter : Stream {
     fn write(&mut self, buff: &[u8]) -> Result<usize, ()>;
 }
 
+#[allow(clippy::result_unit_err)]
 pub trait StreamSeek : Stream {
     fn seek(&mut self, cursor: usize) -> Result<usize, ()>;
 }
@@ -35,6 +39,10 @@ impl MemoryStreamWriter {
     pub fn dataMut(&mut self) -> &mut Vec<u8> { &mut self.data }
 }
 
+impl Default for MemoryStreamWriter {
+    fn default() -> Self { Self::new() }
+}
+
 impl Drop for MemoryStreamWriter {
     fn drop(&mut self) {}
 }
@@ -50,11 +58,9 @@ impl StreamWriter for MemoryStreamWriter {
         let remaining   = if buff.len() < available { 0 } else { buff.len() - available };
         let first       = usize::min(available, buff.len());
 
-        for i in 0..first {
-            self.data[self.cursor + i] = buff[i];
-        }
-        for i in 0..remaining {
-            self.data.pushBack(buff[first + i]);
+        self.data[self.cursor..self.cursor + first].copy_from_slice(&buff[..first]);
+        for b in &buff[first..first + remaining] {
+            self.data.pushBack(*b);
         }
         self.cursor += buff.len();
         Ok(buff.len())
@@ -105,9 +111,7 @@ impl StreamReader for MemoryStreamReader {
             } else {
                 buff.len()
             };
-        for c in 0..readLen {
-            buff[c] = self.data[self.cursor + c];
-        }
+        buff[..readLen].copy_from_slice(&self.data[self.cursor..self.cursor + readLen]);
         self.cursor += readLen;
         Ok(readLen)
     }
@@ -1,11 +1,12 @@
 use crate::vec::*;
+use crate::mem::*;
 use ::core::*;
 use ::core::cmp::*;
 use crate::hash::*;
 
 #[repr(C)]
-pub struct String {
-    data    : Vec<u8>
+pub struct String<A: Allocator = LibcAllocator> {
+    data    : Vec<u8, A>
 }
 
 impl String {
@@ -14,14 +15,39 @@ impl String {
     }
 
     pub fn new() -> Self { Self { data: Vec::new() } }
-    pub fn from(s: &str) -> Self {
-        let mut st = Self::new();
-        for c in s.bytes() {
-            st.data.pushBack(c);
-        }
+    pub fn from(s: &str) -> Self { Self::fromIn(s, LibcAllocator) }
+}
+
+impl<A: Allocator + Default> Default for String<A> {
+    fn default() -> Self { Self::newIn(A::default()) }
+}
+
+impl<A: Allocator> String<A> {
+    pub fn withCapacityIn(c: usize, alloc: A) -> Self {
+        Self { data: Vec::withCapacityIn(c, alloc) }
+    }
+
+    pub fn newIn(alloc: A) -> Self { Self { data: Vec::newIn(alloc) } }
+    pub fn fromIn(s: &str, alloc: A) -> Self {
+        let mut st = Self::withCapacityIn(s.len(), alloc);
+        st.data.append(s.as_bytes());
         st
     }
 
+    pub fn len(&self) -> usize { self.data.len() }
+    pub fn isEmpty(&self) -> bool { self.data.is_empty() }
+    pub fn capacity(&self) -> usize { self.data.capacity() }
+    pub fn reserve(&mut self, additional: usize) { self.data.reserve(additional) }
+    pub fn tryReserve(&mut self, additional: usize) -> Result<(), AllocError> { self.data.tryReserve(additional) }
+    pub fn shrinkToFit(&mut self) { self.data.shrinkToFit() }
+
+    pub fn allocator(&self) -> &A { self.data.allocator() }
+
+    pub fn intoBoxedStr(self) -> Box<str, A> {
+        let (ptr, alloc) = self.data.intoBoxedSlice().intoRawWithAllocator();
+        Box::fromRawIn(ptr as *mut str, alloc)
+    }
+
     pub fn toStr(&self) -> &str {
         ::core::str::from_utf8(self.data.asArray()).expect("Error getting string out")
     }
@@ -30,6 +56,16 @@ impl String {
         self.data.pushBack(u);
     }
 
+    /// Append the UTF-8 encoding of `c`.
+    pub fn addChar(&mut self, c: char) {
+        self.data.append(c.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+
+    /// Append `s`, returning an error instead of calling the out of memory handler.
+    pub fn tryAppend(&mut self, s: &str) -> Result<(), AllocError> {
+        self.data.tryAppend(s.as_bytes())
+    }
+
     pub fn asArray(&self) -> &[u8] { self.data.asArray() }
     pub fn asMutArray(&mut self) -> &mut [u8] { self.data.asMutArray() }
 }
@@ -38,24 +74,20 @@ pub trait Append<T> {
     fn append(&mut self, other: T);
 }
 
-impl Append<&str> for String {
+impl<A: Allocator> Append<&str> for String<A> {
     fn append(&mut self, s: &str) {
-        for c in s.bytes() {
-            self.data.pushBack(c);
-        }
+        self.data.append(s.as_bytes());
     }
 }
 
-impl Append<&String> for String {
-    fn append(&mut self, s: &String) {
-        for c in s.toStr().bytes() {
-            self.data.pushBack(c);
-        }
+impl<A: Allocator, B: Allocator> Append<&String<B>> for String<A> {
+    fn append(&mut self, s: &String<B>) {
+        self.data.append(s.asArray());
     }
 }
 
-impl PartialEq<String> for String {
-    fn eq(&self, other: &Self) -> bool {
+impl<A: Allocator, B: Allocator> PartialEq<String<B>> for String<A> {
+    fn eq(&self, other: &String<B>) -> bool {
         let ls = self.data.len();
         let lo = other.data.len();
         if ls != lo { return false }
@@ -66,28 +98,34 @@ impl PartialEq<String> for String {
     }
 }
 
-impl Eq for String {}
+impl<A: Allocator> Eq for String<A> {}
 
-impl PartialEq<&str> for String {
+impl<A: Allocator> PartialEq<&str> for String<A> {
     fn eq(&self, other: &&str) -> bool {
-        let ob = (*other).as_bytes();
-        let ls = self.data.len();
-        let lo = ob.len();
-        if ls != lo { return false }
-        for i in 0..self.data.len() {
-            if self.data[i] != ob[i] { return false }
-        }
-        true
+        self.asArray() == other.as_bytes()
     }
 }
 
-impl Clone for String {
+// byte order, which is also the code point order
+impl<A: Allocator> PartialOrd for String<A> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl<A: Allocator> Ord for String<A> {
+    fn cmp(&self, other: &Self) -> Ordering { self.asArray().cmp(other.asArray()) }
+}
+
+impl<A: Allocator> borrow::Borrow<str> for String<A> {
+    fn borrow(&self) -> &str { self.toStr() }
+}
+
+impl<A: Allocator + Clone> Clone for String<A> {
     fn clone(&self) -> Self {
-        String::from(self.toStr())
+        String::fromIn(self.toStr(), self.allocator().clone())
     }
 }
 
-impl fmt::Write for String {
+impl<A: Allocator> fmt::Write for String<A> {
     #[inline]
     fn write_str(&mut self, s: &str) -> fmt::Result {
         self.append(s);
@@ -96,20 +134,22 @@ impl fmt::Write for String {
 
     #[inline]
     fn write_char(&mut self, c: char) -> fmt::Result {
-        self.add(c as u8);
+        self.addChar(c);
         Ok(())
     }
 }
 
-impl fmt::Display for String {
+impl<A: Allocator> fmt::Display for String<A> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.toStr())
     }
 }
 
-impl Hash for String {
-    fn hash(&self) -> usize {
-        self.asArray().hash()
+// same as `str`, so `String` keys can be looked up by `&str`
+impl<A: Allocator> Hash for String<A> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.writeBytes(self.asArray());
+        state.writeU8(0xff);
     }
 }
 
@@ -133,4 +173,16 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn testAddChar() {
+        use ::core::fmt::Write;
+        let mut s = String::from("caf");
+        s.addChar('é');
+        s.write_char('!').unwrap();
+        s.add(b'?');
+        assert!(s == "café!?" && s.len() == 7);
+        let b : &str = borrow::Borrow::borrow(&s);
+        assert!(b == "café!?");
+    }
 }
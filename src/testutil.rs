@@ -0,0 +1,29 @@
+//
+// Helpers shared by the unit tests
+//
+use crate::vec::*;
+
+// deterministic pseudo random numbers, the high bits of a 64 bit LCG
+pub struct Lcg(pub u64);
+
+impl Lcg {
+    pub fn next(&mut self) -> u32 {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (self.0 >> 33) as u32
+    }
+}
+
+pub fn randomVec(rng: &mut Lcg, n: usize, range: u32) -> Vec<u32> {
+    let mut v = Vec::new();
+    for _ in 0..n {
+        v.pushBack(rng.next() % range);
+    }
+    v
+}
+
+// a value that owns an allocation, so leaks and double drops show up
+pub fn item(i: i32) -> Vec<i32> {
+    let mut v = Vec::new();
+    v.pushBack(i);
+    v
+}
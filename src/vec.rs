@@ -1,30 +1,80 @@
 use core::*;
 use crate::mem::*;
+use crate::hash::*;
 
 #[repr(C)]
-pub struct Vec<T> {
+pub struct Vec<T, A: Allocator = LibcAllocator> {
     elements    : *mut T,
     count       : usize,
     capacity    : usize,
+    alloc       : A,
 }
 
+unsafe impl<T: Send, A: Allocator + Send> Send for Vec<T, A> {}
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for Vec<T, A> {}
+
 impl<T> Vec<T> {
-    pub fn withCapacity(c: usize) -> Self {
-        if c == 0 { Self::new() }
-        else {
-            Self {
-                elements: unsafe { allocRaw(c * mem::size_of::<T>()) as *mut T },
-                count   : 0,
-                capacity: c,
-            }
-        }
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacity(c: usize) -> Self { Self::withCapacityIn(c, LibcAllocator) }
+
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+}
+
+impl<T, A: Allocator + Default> Default for Vec<T, A> {
+    fn default() -> Self { Self::newIn(A::default()) }
+}
+
+impl<T, A: Allocator> Vec<T, A> {
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacityIn(c: usize, alloc: A) -> Self {
+        let mut v = Self::newIn(alloc);
+        v.reserveExact(c);
+        v
     }
 
-    pub fn new() -> Self {
+    pub fn newIn(alloc: A) -> Self {
         Self {
-            elements: ptr::null_mut(),
+            elements: ptr::NonNull::dangling().as_ptr(),
             count   : 0,
             capacity: 0,
+            alloc,
+        }
+    }
+
+    pub fn allocator(&self) -> &A { &self.alloc }
+
+    /// Build a vector from a buffer of `capacity` elements allocated by `alloc`, the first `len` being initialized.
+    ///
+    /// # Safety
+    /// `elements` must come from `alloc` with room for `capacity` values of `T` (or be dangling with
+    /// `capacity` 0) and its first `len` elements must be initialized. The vector takes ownership of it.
+    pub unsafe fn fromRawPartsIn(elements: *mut T, len: usize, capacity: usize, alloc: A) -> Self {
+        Self { elements, count: len, capacity, alloc }
+    }
+
+    /// Shrink the buffer to fit and hand it over to a `Box<[T]>`.
+    pub fn intoBoxedSlice(mut self) -> Box<[T], A> {
+        self.shrinkToFit();
+        let m = mem::ManuallyDrop::new(self);
+        let alloc = unsafe { ptr::read(&m.alloc) };
+        Box::fromRawIn(ptr::slice_from_raw_parts_mut(m.elements, m.count), alloc)
+    }
+
+    fn layoutError(c: usize) -> AllocError {
+        AllocError { size: c.saturating_mul(mem::size_of::<T>()), align: mem::align_of::<T>() }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    unsafe fn allocBuffer(alloc: &A, c: usize) -> Result<*mut T, AllocError> {
+        if mem::size_of::<T>() == 0 { return Ok(ptr::NonNull::dangling().as_ptr()) }
+        let size = c.checked_mul(mem::size_of::<T>()).ok_or_else(|| Self::layoutError(c))?;
+        let elements = alloc.alloc(size, mem::align_of::<T>()) as *mut T;
+        if elements.is_null() { Err(Self::layoutError(c)) } else { Ok(elements) }
+    }
+
+    unsafe fn freeBuffer(alloc: &A, elements: *mut T, c: usize) {
+        if c != 0 && mem::size_of::<T>() != 0 {
+            alloc.free(elements as *mut u8, c * mem::size_of::<T>(), mem::align_of::<T>())
         }
     }
 
@@ -33,22 +83,116 @@ impl<T> Vec<T> {
 
     pub fn len(&self) -> usize { self.count }
 
-    pub fn pushBack(&mut self, t: T) {
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    // moves the elements to a buffer of `newCap` elements through the allocator's realloc
+    // (`LibcAllocator` falls back to alloc + copy for types the system realloc can't align)
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    fn trySetCapacity(&mut self, newCap: usize) -> Result<(), AllocError> {
+        debug_assert!(newCap >= self.count);
+        if mem::size_of::<T>() == 0 {
+            self.capacity = newCap;
+            return Ok(());
+        }
+
+        let align = mem::align_of::<T>();
+        let newPtr =
+            if newCap == 0 {
+                unsafe { Self::freeBuffer(&self.alloc, self.elements, self.capacity) };
+                ptr::NonNull::dangling().as_ptr()
+            } else if self.capacity == 0 {
+                unsafe { Self::allocBuffer(&self.alloc, newCap)? }
+            } else {
+                let size = newCap.checked_mul(mem::size_of::<T>()).ok_or_else(|| Self::layoutError(newCap))?;
+                let p = unsafe { self.alloc.realloc(self.elements as *mut u8, self.capacity * mem::size_of::<T>(), size, align) as *mut T };
+                if p.is_null() { return Err(Self::layoutError(newCap)) }
+                p
+            };
+        self.elements   = newPtr;
+        self.capacity   = newCap;
+        Ok(())
+    }
+
+    fn growCapacity(&self, additional: usize) -> Result<Option<usize>, AllocError> {
+        let required = self.count.checked_add(additional).ok_or_else(|| Self::layoutError(usize::MAX))?;
+        if required > self.capacity {
+            let doubled = if self.capacity == 0 { 16 } else { self.capacity.saturating_mul(2) };
+            Ok(Some(usize::max(required, doubled)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Reserve room for at least `additional` more elements, growing geometrically.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn reserve(&mut self, additional: usize) {
+        if let Err(e) = self.tryReserve(additional) { handleAllocError(e) }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn tryReserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        match self.growCapacity(additional)? {
+            Some(newCap) => {
+                // the doubled capacity might be too much for the allocator, retry with what is required
+                if self.trySetCapacity(newCap).is_err() {
+                    self.tryReserveExact(additional)
+                } else {
+                    Ok(())
+                }
+            },
+            None => Ok(()),
+        }
+    }
+
+    /// Reserve room for exactly `additional` more elements.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn reserveExact(&mut self, additional: usize) {
+        if let Err(e) = self.tryReserveExact(additional) { handleAllocError(e) }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn tryReserveExact(&mut self, additional: usize) -> Result<(), AllocError> {
+        let required = self.count.checked_add(additional).ok_or_else(|| Self::layoutError(usize::MAX))?;
+        if required > self.capacity {
+            self.trySetCapacity(required)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Release the unused capacity.
+    pub fn shrinkToFit(&mut self) {
+        if self.capacity > self.count {
+            if let Err(e) = self.trySetCapacity(self.count) { handleAllocError(e) }
+        }
+    }
+
+    /// Drop the elements past `len`, keeping the capacity.
+    pub fn truncate(&mut self, len: usize) {
+        while self.count > len {
+            self.count -= 1;
+            unsafe { ptr::drop_in_place(self.elements.add(self.count)) };
+        }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn tryPushBack(&mut self, t: T) -> Result<(), AllocError> {
         if self.count >= self.capacity {
-            let newSize     = if self.capacity == 0 { 16 } else { self.capacity * 2 };
-            let newPtr      = unsafe { allocRaw(newSize * mem::size_of::<T>()) as *mut T };
-            let oldPtr      = self.elements;
-            self.capacity   = newSize;
+            self.tryReserve(1)?;
+        }
 
-            for i in 0..self.count {
-                let v = unsafe { oldPtr.offset(i as isize).read() };    // v = old[i];
-                unsafe { newPtr.offset(i as isize).write(v) };          // new[i] = v;
-            }
-            unsafe { free(self.elements) };
-            self.elements   = newPtr;
+        unsafe { self.elements.add(self.count).write(t) };
+        self.count += 1;
+        Ok(())
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn pushBack(&mut self, t: T) {
+        if self.count >= self.capacity {
+            self.reserve(1);
         }
 
-        unsafe { self.elements.offset(self.count as isize).write(t) };
+        unsafe { self.elements.add(self.count).write(t) };
         self.count += 1
     }
 
@@ -76,14 +220,11 @@ impl<T> Vec<T> {
 
     fn dropElements(&mut self) {
         let arr      = unsafe { core::slice::from_raw_parts_mut(self.elements, self.count) };
-        for i in 0..self.count {
-            unsafe { ptr::drop_in_place(&arr[i] as *const T as *mut T) };
-        }
+        unsafe { ptr::drop_in_place(arr) };
     }
 
-    pub fn toIter<'a>(&self) -> ::core::slice::Iter<'a, T> {
-        let arr      = unsafe { core::slice::from_raw_parts(self.elements, self.count) };
-        arr.into_iter()
+    pub fn toIter(&self) -> ::core::slice::Iter<'_, T> {
+        self.asArray().iter()
     }
 
     pub fn last(&self) -> Option<&T> {
@@ -93,42 +234,390 @@ impl<T> Vec<T> {
             Some(&self[self.count - 1])
         }
     }
+
+    /// Insert `t` at `index`, shifting the following elements up.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn insert(&mut self, index: usize, t: T) {
+        assert!(index <= self.count, "insert index {} out of bounds (len {})", index, self.count);
+        if self.count >= self.capacity {
+            self.reserve(1);
+        }
+
+        unsafe {
+            let p = self.elements.add(index);
+            ptr::copy(p, p.add(1), self.count - index);
+            p.write(t);
+        }
+        self.count += 1;
+    }
+
+    /// Remove the element at `index`, shifting the following elements down.
+    pub fn remove(&mut self, index: usize) -> T {
+        assert!(index < self.count, "remove index {} out of bounds (len {})", index, self.count);
+        unsafe {
+            let p = self.elements.add(index);
+            let v = p.read();
+            ptr::copy(p.add(1), p, self.count - index - 1);
+            self.count -= 1;
+            v
+        }
+    }
+
+    /// Remove the element at `index` and replace it with the last one (O(1), doesn't keep the order).
+    pub fn swapRemove(&mut self, index: usize) -> T {
+        assert!(index < self.count, "swapRemove index {} out of bounds (len {})", index, self.count);
+        unsafe {
+            let p = self.elements.add(index);
+            let v = p.read();
+            self.count -= 1;
+            ptr::copy(self.elements.add(self.count), p, 1);
+            v
+        }
+    }
+
+    /// Drop all the elements, keeping the capacity.
+    pub fn clear(&mut self) { self.truncate(0) }
+
+    /// Keep only the elements for which `f` returns true.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.retainMut(|t| f(t))
+    }
+
+    /// Keep only the elements for which `f` returns true, `f` can modify the elements.
+    pub fn retainMut<F: FnMut(&mut T) -> bool>(&mut self, mut f: F) {
+        // if `f` panics the guard closes the gap, so the vector stays valid
+        struct Guard<'a, T, A: Allocator> {
+            v       : &'a mut Vec<T, A>,
+            len     : usize,
+            processed   : usize,
+            deleted : usize,
+        }
+
+        impl<'a, T, A: Allocator> Drop for Guard<'a, T, A> {
+            fn drop(&mut self) {
+                unsafe {
+                    let p = self.v.elements.add(self.processed);
+                    ptr::copy(p, p.sub(self.deleted), self.len - self.processed);
+                }
+                self.v.count = self.len - self.deleted;
+            }
+        }
+
+        let len = self.count;
+        self.count = 0;
+        let mut g = Guard { v: self, len, processed: 0, deleted: 0 };
+        while g.processed < g.len {
+            let p = unsafe { g.v.elements.add(g.processed) };
+            if !f(unsafe { &mut *p }) {
+                g.processed += 1;
+                g.deleted += 1;
+                unsafe { ptr::drop_in_place(p) };
+            } else {
+                if g.deleted > 0 {
+                    unsafe { ptr::copy_nonoverlapping(p, p.sub(g.deleted), 1) };
+                }
+                g.processed += 1;
+            }
+        }
+    }
+
+    /// Remove the consecutive elements for which `sameBucket(current, previous)` returns true.
+    pub fn dedupBy<F: FnMut(&mut T, &mut T) -> bool>(&mut self, mut sameBucket: F) {
+        // if `sameBucket` panics the guard closes the gap, so the vector stays valid
+        struct Guard<'a, T, A: Allocator> {
+            v       : &'a mut Vec<T, A>,
+            len     : usize,
+            read    : usize,
+            write   : usize,
+        }
+
+        impl<'a, T, A: Allocator> Drop for Guard<'a, T, A> {
+            fn drop(&mut self) {
+                unsafe {
+                    let p = self.v.elements;
+                    ptr::copy(p.add(self.read), p.add(self.write), self.len - self.read);
+                }
+                self.v.count = self.write + self.len - self.read;
+            }
+        }
+
+        if self.count < 2 { return }
+
+        let len = self.count;
+        self.count = 0;
+        let mut g = Guard { v: self, len, read: 1, write: 1 };
+        while g.read < g.len {
+            unsafe {
+                let cur = g.v.elements.add(g.read);
+                let prev = g.v.elements.add(g.write - 1);
+                if sameBucket(&mut *cur, &mut *prev) {
+                    g.read += 1;
+                    ptr::drop_in_place(cur);
+                } else {
+                    ptr::copy(cur, g.v.elements.add(g.write), 1);
+                    g.read += 1;
+                    g.write += 1;
+                }
+            }
+        }
+    }
+
+    /// Remove the consecutive equal elements.
+    pub fn dedup(&mut self) where T: PartialEq {
+        self.dedupBy(|a, b| a == b)
+    }
+
+    /// Split the vector in two at `at`: `self` keeps `[0, at)` and `[at, len)` is returned.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn splitOff(&mut self, at: usize) -> Vec<T, A> where A: Clone {
+        assert!(at <= self.count, "splitOff index {} out of bounds (len {})", at, self.count);
+        let tail = self.count - at;
+        let mut other = Vec::withCapacityIn(tail, self.alloc.clone());
+        unsafe { ptr::copy_nonoverlapping(self.elements.add(at), other.elements, tail) };
+        other.count = tail;
+        self.count = at;
+        other
+    }
+
+    /// Remove the elements in `range`, returning them through an iterator.
+    /// The elements not consumed are dropped with the iterator.
+    pub fn drain<R: ops::RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, T, A> {
+        let start = match range.start_bound() {
+            ops::Bound::Included(&s)    => s,
+            ops::Bound::Excluded(&s)    => s + 1,
+            ops::Bound::Unbounded       => 0,
+        };
+        let end = match range.end_bound() {
+            ops::Bound::Included(&e)    => e + 1,
+            ops::Bound::Excluded(&e)    => e,
+            ops::Bound::Unbounded       => self.count,
+        };
+        assert!(start <= end && end <= self.count, "drain range {}..{} out of bounds (len {})", start, end, self.count);
+
+        let len = self.count;
+        // the drained and tail elements are owned by the iterator until it is dropped
+        self.count = start;
+        Drain { vec: self, index: start, end, tailStart: end, tailLen: len - end }
+    }
+}
+
+/// Draining iterator for `Vec`, see `Vec::drain`.
+pub struct Drain<'a, T, A: Allocator = LibcAllocator> {
+    vec         : &'a mut Vec<T, A>,
+    index       : usize,
+    end         : usize,
+    tailStart   : usize,
+    tailLen     : usize,
+}
+
+impl<'a, T, A: Allocator> Iterator for Drain<'a, T, A> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        if self.index == self.end { return None }
+        let v = unsafe { self.vec.elements.add(self.index).read() };
+        self.index += 1;
+        Some(v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let l = self.end - self.index;
+        (l, Some(l))
+    }
+}
+
+impl<'a, T, A: Allocator> DoubleEndedIterator for Drain<'a, T, A> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.index == self.end { return None }
+        self.end -= 1;
+        Some(unsafe { self.vec.elements.add(self.end).read() })
+    }
+}
+
+impl<'a, T, A: Allocator> ExactSizeIterator for Drain<'a, T, A> {}
+
+impl<'a, T, A: Allocator> Drop for Drain<'a, T, A> {
+    fn drop(&mut self) {
+        // drop what wasn't consumed, then move the tail back in place
+        self.by_ref().for_each(drop);
+
+        unsafe {
+            let start = self.vec.count;
+            ptr::copy(self.vec.elements.add(self.tailStart), self.vec.elements.add(start), self.tailLen);
+            self.vec.count = start + self.tailLen;
+        }
+    }
 }
 
 pub trait VecAppend<E: Copy> {
     fn append(&mut self, arr: &[E]);
 }
 
-impl<T : Copy> VecAppend<T> for Vec<T> {
-    fn append(&mut self, arr: &[T]) {
-        // TODO: optimize this
+impl<T : Copy, A: Allocator> Vec<T, A> {
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn tryAppend(&mut self, arr: &[T]) -> Result<(), AllocError> {
+        self.tryReserve(arr.len())?;
+        unsafe { ptr::copy_nonoverlapping(arr.as_ptr(), self.elements.add(self.count), arr.len()) };
+        self.count += arr.len();
+        Ok(())
+    }
+
+    /// Append a copy of `arr` (a single memcpy).
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn extendFromSlice(&mut self, arr: &[T]) {
+        if let Err(e) = self.tryAppend(arr) { handleAllocError(e) }
+    }
+}
+
+impl<T : Clone, A: Allocator> Vec<T, A> {
+    /// Append a clone of every element of `arr` (`extendFromSlice` is the memcpy path for `Copy` types).
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn extendFromSliceCloned(&mut self, arr: &[T]) {
+        self.reserve(arr.len());
         for e in arr {
             self.pushBack(e.clone());
         }
     }
 }
 
-impl<T> core::ops::Index<usize> for Vec<T> {
-    type Output = T;
+impl<T : Copy, A: Allocator> VecAppend<T> for Vec<T, A> {
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    fn append(&mut self, arr: &[T]) {
+        self.extendFromSlice(arr)
+    }
+}
+
+impl<T, A: Allocator, I: slice::SliceIndex<[T]>> core::ops::Index<I> for Vec<T, A> {
+    type Output = I::Output;
     #[inline]
-    fn index(&self, idx: usize) -> &Self::Output { self.get(idx) }
+    fn index(&self, idx: I) -> &Self::Output { &self.asArray()[idx] }
 }
 
-impl<T> core::ops::IndexMut<usize> for Vec<T> {
+impl<T, A: Allocator, I: slice::SliceIndex<[T]>> core::ops::IndexMut<I> for Vec<T, A> {
     #[inline]
-    fn index_mut(&mut self, idx: usize) -> &mut Self::Output { self.getMut(idx) }
+    fn index_mut(&mut self, idx: I) -> &mut Self::Output { &mut self.asMutArray()[idx] }
 }
 
-impl<T> Drop for Vec<T> {
+impl<T, A: Allocator> ops::Deref for Vec<T, A> {
+    type Target = [T];
+    #[inline]
+    fn deref(&self) -> &[T] { self.asArray() }
+}
+
+impl<T, A: Allocator> ops::DerefMut for Vec<T, A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [T] { self.asMutArray() }
+}
+
+impl<T, A: Allocator> convert::AsRef<[T]> for Vec<T, A> {
+    fn as_ref(&self) -> &[T] { self.asArray() }
+}
+
+impl<T, A: Allocator> convert::AsMut<[T]> for Vec<T, A> {
+    fn as_mut(&mut self) -> &mut [T] { self.asMutArray() }
+}
+
+/// Owning iterator for `Vec`, the elements not consumed are dropped with it.
+pub struct IntoIter<T, A: Allocator = LibcAllocator> {
+    elements    : *mut T,
+    capacity    : usize,
+    index       : usize,
+    end         : usize,
+    alloc       : A,
+}
+
+impl<T, A: Allocator> Iterator for IntoIter<T, A> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        if self.index == self.end { return None }
+        let v = unsafe { self.elements.add(self.index).read() };
+        self.index += 1;
+        Some(v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let l = self.end - self.index;
+        (l, Some(l))
+    }
+}
+
+impl<T, A: Allocator> DoubleEndedIterator for IntoIter<T, A> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.index == self.end { return None }
+        self.end -= 1;
+        Some(unsafe { self.elements.add(self.end).read() })
+    }
+}
+
+impl<T, A: Allocator> ExactSizeIterator for IntoIter<T, A> {}
+
+impl<T, A: Allocator> Drop for IntoIter<T, A> {
+    fn drop(&mut self) {
+        self.by_ref().for_each(drop);
+        unsafe { Vec::<T, A>::freeBuffer(&self.alloc, self.elements, self.capacity) }
+    }
+}
+
+impl<T, A: Allocator> IntoIterator for Vec<T, A> {
+    type Item = T;
+    type IntoIter = IntoIter<T, A>;
+    fn into_iter(self) -> IntoIter<T, A> {
+        let m = mem::ManuallyDrop::new(self);
+        IntoIter {
+            elements    : m.elements,
+            capacity    : m.capacity,
+            index       : 0,
+            end         : m.count,
+            alloc       : unsafe { ptr::read(&m.alloc) },
+        }
+    }
+}
+
+impl<'a, T, A: Allocator> IntoIterator for &'a Vec<T, A> {
+    type Item = &'a T;
+    type IntoIter = slice::Iter<'a, T>;
+    fn into_iter(self) -> slice::Iter<'a, T> { self.asArray().iter() }
+}
+
+impl<'a, T, A: Allocator> IntoIterator for &'a mut Vec<T, A> {
+    type Item = &'a mut T;
+    type IntoIter = slice::IterMut<'a, T>;
+    fn into_iter(self) -> slice::IterMut<'a, T> { self.asMutArray().iter_mut() }
+}
+
+impl<T, A: Allocator + Default> iter::FromIterator<T> for Vec<T, A> {
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Self {
+        let mut v = Vec::newIn(A::default());
+        v.extend(it);
+        v
+    }
+}
+
+impl<T, A: Allocator> iter::Extend<T> for Vec<T, A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        let it = it.into_iter();
+        self.reserve(it.size_hint().0);
+        for t in it {
+            self.pushBack(t);
+        }
+    }
+}
+
+impl<'a, T: Copy + 'a, A: Allocator> iter::Extend<&'a T> for Vec<T, A> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, it: I) {
+        self.extend(it.into_iter().copied())
+    }
+}
+
+impl<T, A: Allocator> Drop for Vec<T, A> {
     fn drop(&mut self) {
         self.dropElements();
-        unsafe { free(self.elements) }
+        unsafe { Self::freeBuffer(&self.alloc, self.elements, self.capacity) }
     }
 }
 
-impl<T : Clone> Clone for Vec<T> {
+impl<T : Clone, A: Allocator + Clone> Clone for Vec<T, A> {
     fn clone(&self) -> Self {
-        let mut c = Vec::<T>::new();
+        let mut c = Vec::<T, A>::withCapacityIn(self.count, self.alloc.clone());
         for i in 0..self.count {
             let v = self.get(i);
             c.pushBack(v.clone());
@@ -137,6 +626,21 @@ impl<T : Clone> Clone for Vec<T> {
     }
 }
 
+impl<T: PartialEq, A: Allocator, B: Allocator> PartialEq<Vec<T, B>> for Vec<T, A> {
+    fn eq(&self, other: &Vec<T, B>) -> bool { self.asArray() == other.asArray() }
+}
+
+impl<T: Eq, A: Allocator> Eq for Vec<T, A> {}
+
+impl<T, A: Allocator> borrow::Borrow<[T]> for Vec<T, A> {
+    fn borrow(&self) -> &[T] { self.asArray() }
+}
+
+// same as the slice, so `Vec<u8>` keys can be looked up by `&[u8]`
+impl<T: Hash, A: Allocator> Hash for Vec<T, A> {
+    fn hash<H: Hasher>(&self, state: &mut H) { self.asArray().hash(state) }
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -144,17 +648,20 @@ mod tests {
 
     #[test]
     fn testDestructor() {
-        let mut v = Vec::<Vec<i32>>::new();
-        for i in 0..100 {
-            let  mut vj = Vec::<i32>::new();
-            for j in 0..100 {
-                vj.pushBack(j * i);
+        crate::mem::assertNoLeaks(|| {
+            let mut v = Vec::<Vec<i32>>::new();
+            for i in 0..100 {
+                let  mut vj = Vec::<i32>::new();
+                for j in 0..100 {
+                    vj.pushBack(j * i);
+                }
+                v.pushBack(vj);
             }
-            v.pushBack(vj);
-        }
+        });
     }
 
     #[test]
+    #[allow(clippy::explicit_counter_loop)]
     fn testIter() {
         let mut v = Vec::new();
         for i in 0..4 {
@@ -169,48 +676,339 @@ mod tests {
         }
     }
     #[test]
+    #[allow(clippy::len_zero)]
     fn testPopDestructor() {
-        let mut v = Vec::<Vec<i32>>::new();
-        for i in 0..100 {
-            let  mut vj = Vec::<i32>::new();
-            for j in 0..100 {
-                vj.pushBack(j * i);
+        crate::mem::assertNoLeaks(|| {
+            let mut v = Vec::<Vec<i32>>::new();
+            for i in 0..100 {
+                let  mut vj = Vec::<i32>::new();
+                for j in 0..100 {
+                    vj.pushBack(j * i);
+                }
+                v.pushBack(vj);
             }
-            v.pushBack(vj);
+
+            assert!(v.len() == 100);
+            for _ in 0..100 {
+                v.pop();
+            }
+            assert!(v.len() == 0);
+        });
+    }
+
+    #[test]
+    #[allow(clippy::len_zero)]
+    fn testPopDestructorPush() {
+        crate::mem::assertNoLeaks(|| {
+            let mut v = Vec::<Vec<i32>>::new();
+            for i in 0..100 {
+                let  mut vj = Vec::<i32>::new();
+                for j in 0..100 {
+                    vj.pushBack(j * i);
+                }
+                v.pushBack(vj);
+            }
+
+            for _ in 0..100 {
+                v.pop();
+            }
+
+            assert!(v.len() == 0);
+
+            for i in 0..100 {
+                let  mut vj = Vec::<i32>::new();
+                for j in 0..100 {
+                    vj.pushBack(j * i);
+                }
+                v.pushBack(vj);
+            }
+
+            assert!(v.len() == 100);
+        });
+    }
+
+    #[test]
+    fn testReserve() {
+        let mut v = Vec::<u8>::new();
+        assert!(v.capacity() == 0);
+        v.reserveExact(10);
+        assert!(v.capacity() == 10);
+        v.reserve(11);
+        assert!(v.capacity() >= 11);
+
+        for i in 0..100000 {
+            v.pushBack(i as u8);
         }
+        assert!(v.capacity() >= 100000);
+        for i in 0..100000 {
+            assert!(v[i] == i as u8);
+        }
+
+        v.append(&[1, 2, 3]);
+        assert!(v.len() == 100003);
+        assert!(v[100002] == 3);
 
-        assert!(v.len() == 100);
-        for _ in 0..100 {
-            v.pop();
+        v.shrinkToFit();
+        assert!(v.capacity() == v.len());
+        for i in 0..100000 {
+            assert!(v[i] == i as u8);
         }
-        assert!(v.len() == 0);
     }
 
     #[test]
-    fn testPopDestructorPush() {
+    fn testTruncateDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let mut v = Vec::<Vec<i32>>::new();
+            for i in 0..100 {
+                let  mut vj = Vec::<i32>::new();
+                for j in 0..100 {
+                    vj.pushBack(j * i);
+                }
+                v.pushBack(vj);
+            }
+
+            v.truncate(200);
+            assert!(v.len() == 100);
+            v.truncate(10);
+            assert!(v.len() == 10);
+            assert!(v[9][1] == 9);
+            v.shrinkToFit();
+            assert!(v.capacity() == 10);
+            v.truncate(0);
+            v.shrinkToFit();
+            assert!(v.capacity() == 0);
+            v.pushBack(Vec::new());
+            assert!(v.len() == 1);
+        });
+    }
+    fn vecVec(n: i32) -> Vec<Vec<i32>> {
         let mut v = Vec::<Vec<i32>>::new();
-        for i in 0..100 {
+        for i in 0..n {
             let  mut vj = Vec::<i32>::new();
             for j in 0..100 {
                 vj.pushBack(j * i);
             }
             v.pushBack(vj);
         }
+        v
+    }
+
+    #[test]
+    fn testInsertRemoveDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let mut v = vecVec(100);
+            let mut vj = Vec::new();
+            vj.pushBack(-1);
+            v.insert(0, vj.clone());
+            v.insert(50, vj.clone());
+            v.insert(102, vj);
+            assert!(v.len() == 103);
+            assert!(v[0][0] == -1 && v[50][0] == -1 && v[102][0] == -1);
+            assert!(v[1][1] == 0 && v[51][1] == 49 && v[101][1] == 99);
+
+            let r = v.remove(50);
+            assert!(r[0] == -1);
+            assert!(v[50][1] == 49);
+            let r = v.swapRemove(0);
+            assert!(r[0] == -1);
+            assert!(v[0][0] == -1 && v.len() == 101);
+            v.swapRemove(100);
+            assert!(v.len() == 100);
+            assert!(v[0][0] == -1);
+
+            v.clear();
+            assert!(v.is_empty());
+        });
+    }
 
-        for _ in 0..100 {
-            v.pop();
+    #[test]
+    fn testRetainDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let mut v = vecVec(100);
+            v.retain(|vj| vj[1] % 2 == 0);
+            assert!(v.len() == 50);
+            for i in 0..50 {
+                assert!(v[i][1] == (i * 2) as i32);
+            }
+
+            v.retainMut(|vj| { vj.pushBack(0); vj[1] < 50 });
+            assert!(v.len() == 25);
+            assert!(v[24].len() == 101);
+        });
+    }
+
+    #[test]
+    fn testRetainPanic() {
+        extern crate std;
+        let rc = crate::mem::Rc::new(0);
+        let mut v = Vec::new();
+        for _ in 0..10 {
+            v.pushBack(rc.clone());
         }
 
-        assert!(v.len() == 0);
+        let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut n = 0;
+            v.retain(|_| { n += 1; if n == 5 { panic!("retain") } n % 2 == 0 });
+        }));
+        assert!(r.is_err());
+        // 2 removed before the panic, the rest is kept
+        assert!(v.len() == 8);
+        drop(v);
+        assert!(crate::mem::Rc::strongCount(&rc) == 1);
+    }
+
+    #[test]
+    fn testDedup() {
+        crate::mem::assertNoLeaks(|| {
+            let mut v = Vec::new();
+            for i in 0..100 {
+                let mut vj = Vec::new();
+                vj.pushBack(i / 10);
+                v.pushBack(vj);
+            }
+            v.dedupBy(|a, b| a[0] == b[0]);
+            assert!(v.len() == 10);
+            for i in 0..10 {
+                assert!(v[i][0] == i);
+            }
+
+            let mut v = Vec::new();
+            v.extendFromSlice(&[1, 1, 2, 3, 3, 3, 1]);
+            v.dedup();
+            assert!(v.asArray() == [1, 2, 3, 1]);
+        });
+    }
+
+    #[test]
+    fn testDedupPanic() {
+        extern crate std;
+        crate::mem::assertNoLeaks(|| {
+            let mut v = Vec::new();
+            for i in 0..10 {
+                v.pushBack(crate::mem::Box::new(i / 2));
+            }
+
+            let r = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                let mut n = 0;
+                v.dedupBy(|a, b| { n += 1; if n == 6 { panic!("dedup") } **a == **b });
+            }));
+            assert!(r.is_err());
+            // 3 duplicates removed before the panic, the rest is kept
+            assert!(v.len() == 7);
+            for i in 0..v.len() {
+                assert!(*v[i] == [0, 1, 2, 3, 3, 4, 4][i]);
+            }
+        });
+    }
 
+    #[test]
+    fn testSplitOffExtend() {
+        crate::mem::assertNoLeaks(|| {
+            let mut v = vecVec(100);
+            let t = v.splitOff(40);
+            assert!(v.len() == 40 && t.len() == 60);
+            assert!(t[0][1] == 40);
+            let e = v.splitOff(40);
+            assert!(e.is_empty());
+
+            v.extendFromSliceCloned(t.asArray());
+            assert!(v.len() == 100);
+            assert!(v[99][1] == 99);
+        });
+    }
+
+    #[test]
+    fn testDrainDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let mut v = vecVec(100);
+            {
+                let mut d = v.drain(10..20);
+                assert!(d.len() == 10);
+                let first = d.next().unwrap();
+                assert!(first[1] == 10);
+                let last = d.next_back().unwrap();
+                assert!(last[1] == 19);
+                // the 8 remaining elements are dropped with the iterator
+            }
+            assert!(v.len() == 90);
+            assert!(v[9][1] == 9 && v[10][1] == 20);
+
+            let all : i32 = v.drain(..).map(|vj| vj[1]).sum();
+            assert!(all == (0..100).sum::<i32>() - (10..20).sum::<i32>());
+            assert!(v.is_empty());
+
+            let mut v = vecVec(10);
+            v.drain(5..=9);
+            assert!(v.len() == 5);
+            v.drain(..0);
+            assert!(v.len() == 5);
+        });
+    }
+
+    #[test]
+    fn testSliceMethods() {
+        let mut v = Vec::new();
         for i in 0..100 {
-            let  mut vj = Vec::<i32>::new();
-            for j in 0..100 {
-                vj.pushBack(j * i);
+            v.pushBack((i * 37) % 100);
+        }
+        assert!(v.contains(&37));
+        v.sort();
+        assert!(v.binary_search(&42) == Ok(42));
+        assert!(v.iter().sum::<i32>() == 4950);
+        for x in v.iter_mut() {
+            *x *= 2;
+        }
+        assert!(v.first() == Some(&0) && v[99] == 198);
+        let s : &[i32] = v.as_ref();
+        assert!(s.len() == 100);
+        assert!(v[10..12] == [20, 22]);
+    }
+
+    #[test]
+    fn testIntoIterDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let v = vecVec(100);
+            let mut count = 0;
+            for vj in v {
+                assert!(vj[1] == count);
+                count += 1;
             }
-            v.pushBack(vj);
+            assert!(count == 100);
+
+            // partially consumed: the rest is dropped with the iterator
+            let v = vecVec(100);
+            let mut it = v.into_iter();
+            assert!(it.next().unwrap()[1] == 0);
+            assert!(it.next_back().unwrap()[1] == 99);
+            assert!(it.len() == 98);
+        });
+    }
+
+    #[test]
+    fn testRefIter() {
+        let mut v = vecVec(10);
+        for vj in &mut v {
+            vj.pushBack(-1);
+        }
+        let mut count = 0;
+        for vj in &v {
+            assert!(vj.len() == 101 && vj[100] == -1);
+            count += 1;
         }
+        assert!(count == 10);
+    }
+
+    #[test]
+    fn testCollectExtend() {
+        crate::mem::assertNoLeaks(|| {
+            let v : Vec<Vec<i32>> = (0..100).map(|i| { let mut vj = Vec::new(); vj.pushBack(i); vj }).collect();
+            assert!(v.len() == 100 && v[99][0] == 99);
 
-        assert!(v.len() == 100);
+            let mut w : Vec<i32> = v.iter().map(|vj| vj[0]).filter(|x| x % 2 == 0).collect();
+            assert!(w.len() == 50);
+            w.extend(0..10);
+            w.extend([1, 2, 3].iter());
+            assert!(w.len() == 63 && w[62] == 3);
+        });
     }
-}
\ No newline at end of file
+}
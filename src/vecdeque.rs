@@ -0,0 +1,584 @@
+use core::*;
+use crate::mem::*;
+
+/// Growable ring buffer: O(1) push/pop at both ends.
+pub struct VecDeque<T, A: Allocator = LibcAllocator> {
+    elements    : *mut T,
+    capacity    : usize,
+    head        : usize,    // physical index of the front element
+    count       : usize,
+    alloc       : A,
+}
+
+unsafe impl<T: Send, A: Allocator + Send> Send for VecDeque<T, A> {}
+unsafe impl<T: Sync, A: Allocator + Sync> Sync for VecDeque<T, A> {}
+
+impl<T> VecDeque<T> {
+    pub fn new() -> Self { Self::newIn(LibcAllocator) }
+    pub fn withCapacity(c: usize) -> Self { Self::withCapacityIn(c, LibcAllocator) }
+}
+
+impl<T, A: Allocator + Default> Default for VecDeque<T, A> {
+    fn default() -> Self { Self::newIn(A::default()) }
+}
+
+impl<T, A: Allocator> VecDeque<T, A> {
+    pub fn newIn(alloc: A) -> Self {
+        Self {
+            elements    : ptr::NonNull::dangling().as_ptr(),
+            capacity    : if mem::size_of::<T>() == 0 { usize::MAX } else { 0 },
+            head        : 0,
+            count       : 0,
+            alloc,
+        }
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn withCapacityIn(c: usize, alloc: A) -> Self {
+        let mut d = Self::newIn(alloc);
+        if c > d.capacity {
+            if let Err(e) = d.tryGrow(c) { handleAllocError(e) }
+        }
+        d
+    }
+
+    pub fn len(&self) -> usize { self.count }
+    pub fn isEmpty(&self) -> bool { self.count == 0 }
+    pub fn capacity(&self) -> usize { self.capacity }
+
+    #[inline]
+    fn physical(&self, idx: usize) -> usize {
+        let p = self.head.wrapping_add(idx);
+        if p >= self.capacity { p - self.capacity } else { p }
+    }
+
+    #[inline]
+    fn ptrAt(&self, idx: usize) -> *mut T { unsafe { self.elements.add(self.physical(idx)) } }
+
+    // move the elements into a new buffer of `newCap` elements, front first
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    fn tryGrow(&mut self, newCap: usize) -> Result<(), AllocError> {
+        let align = mem::align_of::<T>();
+        let size = newCap.checked_mul(mem::size_of::<T>()).ok_or(AllocError { size: usize::MAX, align })?;
+        let newElements = unsafe { self.alloc.alloc(size, align) as *mut T };
+        if newElements.is_null() { return Err(AllocError { size, align }) }
+
+        let (a, b) = self.asSlices();
+        unsafe {
+            ptr::copy_nonoverlapping(a.as_ptr(), newElements, a.len());
+            ptr::copy_nonoverlapping(b.as_ptr(), newElements.add(a.len()), b.len());
+        }
+        self.freeBuffer();
+        self.elements   = newElements;
+        self.capacity   = newCap;
+        self.head       = 0;
+        Ok(())
+    }
+
+    fn freeBuffer(&mut self) {
+        if self.capacity != 0 && mem::size_of::<T>() != 0 {
+            unsafe { self.alloc.free(self.elements as *mut u8, self.capacity * mem::size_of::<T>(), mem::align_of::<T>()) }
+        }
+    }
+
+    /// Reserve room for at least `additional` more elements.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn reserve(&mut self, additional: usize) {
+        if let Err(e) = self.tryReserve(additional) { handleAllocError(e) }
+    }
+
+    /// Same as `reserve`, but returns an error instead of calling the out of memory handler.
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn tryReserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        let required = self.count.checked_add(additional).ok_or(AllocError { size: usize::MAX, align: mem::align_of::<T>() })?;
+        if required <= self.capacity { return Ok(()) }
+        let doubled = if self.capacity == 0 { 16 } else { self.capacity.saturating_mul(2) };
+        // the doubled capacity might be too much for the allocator, retry with what is required
+        if doubled > required && self.tryGrow(doubled).is_ok() { return Ok(()) }
+        self.tryGrow(required)
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn pushBack(&mut self, t: T) {
+        if self.count == self.capacity { self.reserve(1) }
+        unsafe { self.ptrAt(self.count).write(t) };
+        self.count += 1;
+    }
+
+    #[cfg_attr(feature = "debug-alloc", track_caller)]
+    pub fn pushFront(&mut self, t: T) {
+        if self.count == self.capacity { self.reserve(1) }
+        self.head = if self.head == 0 { self.capacity - 1 } else { self.head - 1 };
+        unsafe { self.elements.add(self.head).write(t) };
+        self.count += 1;
+    }
+
+    pub fn popBack(&mut self) -> Option<T> {
+        if self.count == 0 { return None }
+        self.count -= 1;
+        Some(unsafe { self.ptrAt(self.count).read() })
+    }
+
+    pub fn popFront(&mut self) -> Option<T> {
+        if self.count == 0 { return None }
+        let v = unsafe { self.elements.add(self.head).read() };
+        self.head = self.physical(1);
+        self.count -= 1;
+        Some(v)
+    }
+
+    pub fn front(&self) -> Option<&T> { if self.count == 0 { None } else { Some(self.get(0)) } }
+    pub fn back(&self) -> Option<&T> { if self.count == 0 { None } else { Some(self.get(self.count - 1)) } }
+
+    #[inline]
+    pub fn get(&self, idx: usize) -> &T {
+        assert!(idx < self.count, "index {} out of bounds (len {})", idx, self.count);
+        unsafe { &*self.ptrAt(idx) }
+    }
+
+    #[inline]
+    pub fn getMut(&mut self, idx: usize) -> &mut T {
+        assert!(idx < self.count, "index {} out of bounds (len {})", idx, self.count);
+        unsafe { &mut *self.ptrAt(idx) }
+    }
+
+    /// The elements in order, as the two contiguous parts of the ring buffer.
+    pub fn asSlices(&self) -> (&[T], &[T]) {
+        unsafe {
+            let first = usize::min(self.count, self.capacity - self.head);
+            (slice::from_raw_parts(self.elements.add(self.head), first),
+             slice::from_raw_parts(self.elements, self.count - first))
+        }
+    }
+
+    pub fn asMutSlices(&mut self) -> (&mut [T], &mut [T]) {
+        unsafe {
+            let first = usize::min(self.count, self.capacity - self.head);
+            (slice::from_raw_parts_mut(self.elements.add(self.head), first),
+             slice::from_raw_parts_mut(self.elements, self.count - first))
+        }
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        let (a, b) = self.asSlices();
+        Iter { inner: a.iter().chain(b.iter()) }
+    }
+
+    pub fn iterMut(&mut self) -> IterMut<'_, T> {
+        let (a, b) = self.asMutSlices();
+        IterMut { inner: a.iter_mut().chain(b.iter_mut()) }
+    }
+
+    // move `len` elements from the physical index `src` to `dst`, both ranges may wrap around and
+    // overlap: copy in the order that reads every element before it gets overwritten
+    unsafe fn wrapCopy(&mut self, src: usize, dst: usize, len: usize) {
+        if src == dst || mem::size_of::<T>() == 0 { return }
+        let cap = self.capacity;
+        let wrap = |i: usize| if i >= cap { i - cap } else { i };
+        let ahead = wrap(dst + cap - src);
+        if ahead < len {
+            // `dst` overlaps the end of the source, from the back
+            let mut left = len;
+            while left > 0 {
+                let (s, d) = (wrap(src + left - 1), wrap(dst + left - 1));
+                let chunk = usize::min(left, usize::min(s, d) + 1);
+                ptr::copy(self.elements.add(s + 1 - chunk), self.elements.add(d + 1 - chunk), chunk);
+                left -= chunk;
+            }
+        } else {
+            let mut done = 0;
+            while done < len {
+                let (s, d) = (wrap(src + done), wrap(dst + done));
+                let chunk = usize::min(len - done, cap - usize::max(s, d));
+                ptr::copy(self.elements.add(s), self.elements.add(d), chunk);
+                done += chunk;
+            }
+        }
+    }
+
+    /// Rotate the elements `n` places to the left: the element at `n` becomes the front.
+    pub fn rotateLeft(&mut self, n: usize) {
+        assert!(n <= self.count, "rotate by {} out of bounds (len {})", n, self.count);
+        if n > self.count - n { return self.rotateRight(self.count - n) }
+        // the first `n` elements move after the last one
+        unsafe { self.wrapCopy(self.head, self.physical(self.count), n) };
+        self.head = self.physical(n);
+    }
+
+    /// Rotate the elements `n` places to the right: the element at `len - n` becomes the front.
+    pub fn rotateRight(&mut self, n: usize) {
+        assert!(n <= self.count, "rotate by {} out of bounds (len {})", n, self.count);
+        if n > self.count - n { return self.rotateLeft(self.count - n) }
+        // the last `n` elements move before the first one
+        let head = self.physical(self.capacity - n);
+        unsafe { self.wrapCopy(self.physical(self.count - n), head, n) };
+        self.head = head;
+    }
+
+    /// Drop the elements past `len`.
+    pub fn truncate(&mut self, len: usize) {
+        while self.count > len {
+            drop(self.popBack());
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.truncate(0);
+        self.head = 0;
+    }
+
+    /// Remove the elements in `range`, returning them through an iterator.
+    /// The elements not consumed are dropped with the iterator.
+    pub fn drain<R: ops::RangeBounds<usize>>(&mut self, range: R) -> Drain<'_, T, A> {
+        let start = match range.start_bound() {
+            ops::Bound::Included(&s)    => s,
+            ops::Bound::Excluded(&s)    => s + 1,
+            ops::Bound::Unbounded       => 0,
+        };
+        let end = match range.end_bound() {
+            ops::Bound::Included(&e)    => e + 1,
+            ops::Bound::Excluded(&e)    => e,
+            ops::Bound::Unbounded       => self.count,
+        };
+        assert!(start <= end && end <= self.count, "drain range {}..{} out of bounds (len {})", start, end, self.count);
+
+        let len = self.count;
+        // the drained and tail elements are owned by the iterator until it is dropped
+        self.count = start;
+        Drain { deque: self, index: start, end, tailStart: end, tailLen: len - end }
+    }
+}
+
+impl<T, A: Allocator> ops::Index<usize> for VecDeque<T, A> {
+    type Output = T;
+    #[inline]
+    fn index(&self, idx: usize) -> &T { self.get(idx) }
+}
+
+impl<T, A: Allocator> ops::IndexMut<usize> for VecDeque<T, A> {
+    #[inline]
+    fn index_mut(&mut self, idx: usize) -> &mut T { self.getMut(idx) }
+}
+
+impl<T, A: Allocator> Drop for VecDeque<T, A> {
+    fn drop(&mut self) {
+        let (a, b) = self.asMutSlices();
+        unsafe {
+            ptr::drop_in_place(a);
+            ptr::drop_in_place(b);
+        }
+        self.freeBuffer();
+    }
+}
+
+impl<T: Clone, A: Allocator + Clone> Clone for VecDeque<T, A> {
+    fn clone(&self) -> Self {
+        let mut c = Self::withCapacityIn(self.count, self.alloc.clone());
+        for t in self.iter() {
+            c.pushBack(t.clone());
+        }
+        c
+    }
+}
+
+/// Iterator over `VecDeque` references, front to back.
+pub struct Iter<'a, T> {
+    inner   : iter::Chain<slice::Iter<'a, T>, slice::Iter<'a, T>>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> { self.inner.next() }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+}
+
+impl<'a, T> DoubleEndedIterator for Iter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> { self.inner.next_back() }
+}
+
+/// Iterator over `VecDeque` mutable references, front to back.
+pub struct IterMut<'a, T> {
+    inner   : iter::Chain<slice::IterMut<'a, T>, slice::IterMut<'a, T>>,
+}
+
+impl<'a, T> Iterator for IterMut<'a, T> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<&'a mut T> { self.inner.next() }
+    fn size_hint(&self) -> (usize, Option<usize>) { self.inner.size_hint() }
+}
+
+impl<'a, T> DoubleEndedIterator for IterMut<'a, T> {
+    fn next_back(&mut self) -> Option<&'a mut T> { self.inner.next_back() }
+}
+
+/// Owning iterator for `VecDeque`, the elements not consumed are dropped with it.
+pub struct IntoIter<T, A: Allocator = LibcAllocator> {
+    deque   : VecDeque<T, A>,
+}
+
+impl<T, A: Allocator> Iterator for IntoIter<T, A> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> { self.deque.popFront() }
+    fn size_hint(&self) -> (usize, Option<usize>) { (self.deque.len(), Some(self.deque.len())) }
+}
+
+impl<T, A: Allocator> DoubleEndedIterator for IntoIter<T, A> {
+    fn next_back(&mut self) -> Option<T> { self.deque.popBack() }
+}
+
+impl<T, A: Allocator> IntoIterator for VecDeque<T, A> {
+    type Item = T;
+    type IntoIter = IntoIter<T, A>;
+    fn into_iter(self) -> IntoIter<T, A> { IntoIter { deque: self } }
+}
+
+impl<'a, T, A: Allocator> IntoIterator for &'a VecDeque<T, A> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> { self.iter() }
+}
+
+impl<'a, T, A: Allocator> IntoIterator for &'a mut VecDeque<T, A> {
+    type Item = &'a mut T;
+    type IntoIter = IterMut<'a, T>;
+    fn into_iter(self) -> IterMut<'a, T> { self.iterMut() }
+}
+
+impl<T, A: Allocator> iter::Extend<T> for VecDeque<T, A> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, it: I) {
+        let it = it.into_iter();
+        self.reserve(it.size_hint().0);
+        for t in it {
+            self.pushBack(t);
+        }
+    }
+}
+
+impl<T, A: Allocator + Default> iter::FromIterator<T> for VecDeque<T, A> {
+    fn from_iter<I: IntoIterator<Item = T>>(it: I) -> Self {
+        let mut d = Self::newIn(A::default());
+        d.extend(it);
+        d
+    }
+}
+
+/// Draining iterator for `VecDeque`, see `VecDeque::drain`.
+pub struct Drain<'a, T, A: Allocator = LibcAllocator> {
+    deque       : &'a mut VecDeque<T, A>,
+    index       : usize,
+    end         : usize,
+    tailStart   : usize,
+    tailLen     : usize,
+}
+
+impl<'a, T, A: Allocator> Iterator for Drain<'a, T, A> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        if self.index == self.end { return None }
+        let v = unsafe { self.deque.ptrAt(self.index).read() };
+        self.index += 1;
+        Some(v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let l = self.end - self.index;
+        (l, Some(l))
+    }
+}
+
+impl<'a, T, A: Allocator> DoubleEndedIterator for Drain<'a, T, A> {
+    fn next_back(&mut self) -> Option<T> {
+        if self.index == self.end { return None }
+        self.end -= 1;
+        Some(unsafe { self.deque.ptrAt(self.end).read() })
+    }
+}
+
+impl<'a, T, A: Allocator> ExactSizeIterator for Drain<'a, T, A> {}
+
+impl<'a, T, A: Allocator> Drop for Drain<'a, T, A> {
+    fn drop(&mut self) {
+        // drop what wasn't consumed, then move the tail back in place (the addresses may wrap around)
+        self.by_ref().for_each(drop);
+
+        let start = self.deque.count;
+        for i in 0..self.tailLen {
+            unsafe { ptr::copy(self.deque.ptrAt(self.tailStart + i), self.deque.ptrAt(start + i), 1) };
+        }
+        self.deque.count = start + self.tailLen;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vec::*;
+    use crate::testutil::*;
+
+    // a deque of [from, to) that wraps around the end of its buffer
+    fn wrapped(from: i32, to: i32) -> VecDeque<Vec<i32>> {
+        let mut d = VecDeque::withCapacity(16);
+        for i in 0..10 {
+            d.pushBack(item(i));
+        }
+        for _ in 0..10 {
+            d.popFront();
+        }
+        for i in from..to {
+            d.pushBack(item(i));
+        }
+        d
+    }
+
+    #[test]
+    fn testPushPop() {
+        crate::mem::assertNoLeaks(|| {
+            let mut d = VecDeque::new();
+            for i in 0..100 {
+                d.pushBack(item(i));
+                d.pushFront(item(-i));
+            }
+            assert!(d.len() == 200);
+            assert!(d.front().unwrap()[0] == -99 && d.back().unwrap()[0] == 99);
+            assert!(d[100][0] == 0 && d[99][0] == 0 && d[101][0] == 1);
+
+            for i in (0..100).rev() {
+                assert!(d.popBack().unwrap()[0] == i);
+                assert!(d.popFront().unwrap()[0] == -i);
+            }
+            assert!(d.isEmpty());
+            assert!(d.popBack().is_none() && d.popFront().is_none());
+        });
+    }
+
+    #[test]
+    fn testWrapAroundDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let d = wrapped(0, 12);
+            let (a, b) = d.asSlices();
+            assert!(a.len() == 6 && b.len() == 6);
+            assert!(a[0][0] == 0 && b[5][0] == 11);
+
+            // growing a wrapped buffer keeps the order
+            let mut d = wrapped(0, 12);
+            for i in 12..100 {
+                d.pushBack(item(i));
+            }
+            let mut n = 0;
+            for v in d.iter() {
+                assert!(v[0] == n);
+                n += 1;
+            }
+            assert!(n == 100);
+        });
+    }
+
+    #[test]
+    fn testIter() {
+        let mut d = wrapped(0, 12);
+        for v in d.iterMut() {
+            v[0] *= 2;
+        }
+        for (i, v) in (&d).into_iter().enumerate() {
+            assert!(v[0] == i as i32 * 2);
+        }
+        assert!(d.iter().next_back().unwrap()[0] == 22);
+
+        let mut n = 0;
+        let mut it = d.into_iter();
+        assert!(it.next_back().unwrap()[0] == 22);
+        for v in it {
+            assert!(v[0] == n * 2);
+            n += 1;
+        }
+        assert!(n == 11);
+
+        let d : VecDeque<i32> = (0..10).collect();
+        assert!(d.len() == 10 && d[9] == 9);
+    }
+
+    #[test]
+    fn testRotate() {
+        crate::mem::assertNoLeaks(|| {
+            let mut d = wrapped(0, 12);
+            d.rotateLeft(3);
+            assert!(d[0][0] == 3 && d[11][0] == 2);
+            d.rotateRight(5);
+            assert!(d[0][0] == 10 && d[2][0] == 0);
+            d.rotateLeft(12);
+            assert!(d[0][0] == 10);
+            d.rotateRight(0);
+            assert!(d[0][0] == 10 && d.len() == 12);
+        });
+    }
+
+    #[test]
+    fn testRotateWrapped() {
+        // every head position, length and rotation of a 16 slot buffer
+        for head in 0..16 {
+            for len in 0..=16 {
+                for n in 0..=len {
+                    let mut d = VecDeque::withCapacity(16);
+                    for _ in 0..head { d.pushBack(0) }
+                    for _ in 0..head { d.popFront(); }
+                    for i in 0..len { d.pushBack(i) }
+
+                    d.rotateLeft(n);
+                    assert!(d.capacity() == 16 && d.iter().copied().eq((n..len).chain(0..n)));
+                    d.rotateRight(n);
+                    assert!(d.iter().copied().eq(0..len));
+                }
+            }
+        }
+
+        let mut z = VecDeque::new();
+        for _ in 0..10 { z.pushBack(()) }
+        z.rotateLeft(3);
+        z.rotateRight(7);
+        assert!(z.len() == 10);
+    }
+
+    #[test]
+    fn testTryReserve() {
+        let mut d : VecDeque<u64> = VecDeque::new();
+        assert!(d.tryReserve(usize::MAX).is_err() && d.tryReserve(usize::MAX / 4).is_err());
+        assert!(d.tryReserve(10).is_ok() && d.capacity() == 16 && d.isEmpty());
+    }
+
+    #[test]
+    fn testDrainDestructor() {
+        crate::mem::assertNoLeaks(|| {
+            let mut d = wrapped(0, 12);
+            {
+                // the drained range and the tail both cross the end of the buffer
+                let mut dr = d.drain(4..8);
+                assert!(dr.next().unwrap()[0] == 4);
+                assert!(dr.next_back().unwrap()[0] == 7);
+            }
+            assert!(d.len() == 8);
+            let expected = [0, 1, 2, 3, 8, 9, 10, 11];
+            for i in 0..8 {
+                assert!(d[i][0] == expected[i]);
+            }
+
+            let all : i32 = d.drain(..).map(|v| v[0]).sum();
+            assert!(all == 44);
+            assert!(d.isEmpty());
+            d.pushBack(item(1));
+            assert!(d[0][0] == 1);
+        });
+    }
+
+    #[test]
+    fn testTruncateClone() {
+        crate::mem::assertNoLeaks(|| {
+            let mut d = wrapped(0, 12);
+            let c = d.clone();
+            d.truncate(3);
+            assert!(d.len() == 3 && c.len() == 12);
+            assert!(c[11][0] == 11);
+            d.clear();
+            assert!(d.isEmpty());
+        });
+    }
+}